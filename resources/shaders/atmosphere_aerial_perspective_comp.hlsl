@@ -0,0 +1,25 @@
+#include "atmosphere_scattering.hlsl"
+
+//Froxel volume over the view frustum: xy - screen, z - distance from the camera.
+//Stores in-scattered luminance in rgb and mean transmittance in a
+
+[[vk::binding(9)]] LUT_IMAGE_FORMAT RWTexture3D<float4> aerialPerspectiveOut;
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID)
+{
+	uint width, height, depth;
+	aerialPerspectiveOut.GetDimensions(width, height, depth);
+	if(id.x >= width || id.y >= height || id.z >= depth)
+		return;
+
+	const float2 uv = (float2(id.xy) + 0.5) / float2(width, height);
+	const float3 viewDir = ClipToWorldDir(uv * 2.0f - 1.0f);
+	const float sliceDistance = AerialPerspectiveSliceToDistance(id.z + 0.5f, depth);
+
+	//Roughly one sample per slice travelled
+	float3 transmittance;
+	const float3 l = IntegrateScattering(CameraSkyPos(), viewDir, sliceDistance, id.z + 1, transmittance);
+
+	aerialPerspectiveOut[id] = float4(l, dot(transmittance, 1.0f / 3.0f));
+}
@@ -0,0 +1,121 @@
+#ifndef PI
+#define PI 3.1415926535897932
+#endif
+
+struct FSampleData
+{
+	float4 planetPos;
+	float planetRadius;
+	float atmosphereThickness;
+	float sampleCount;
+	float sampleCountLight;
+	float scale;
+
+	float4 lightDir;
+	float4 lightIntensity;
+};
+
+struct FMedium
+{
+	float scaleHeightR;
+	float scaleHeightM;
+	float mieG;
+	float ozoneCenter;
+	float ozoneWidth;
+
+	//Rayleigh
+	float4 scatteringR;
+	float4 absorptionR;
+	float4 extinctionR;
+
+	//Mie
+	float4 scatteringM;
+	float4 absorptionM;
+	float4 extinctionM;
+
+	//Ozone, absorption only
+	float4 absorptionO;
+};
+
+ConstantBuffer<FMedium> medium : register(b2);
+ConstantBuffer<FSampleData> sampleData : register(b3);
+
+//Storage images of the LUTs, matching LUT_FORMAT of the renderer. DXC and glslang spell the format differently
+#define LUT_IMAGE_FORMAT [[vk::image_format("rgba16f")]][[spv::format_rgba16f]]
+
+float2 RayIntersectSphere(float3 rayOrigin, float3 rayDirection, float4 sphere)
+{
+	float3 localPosition = rayOrigin - sphere.xyz;
+	float localPositionSqr = dot(localPosition, localPosition);
+
+	float3 quadraticCoef;
+	quadraticCoef.x = dot(rayDirection, rayDirection);
+	quadraticCoef.y = 2 * dot(rayDirection, localPosition);
+	quadraticCoef.z = localPositionSqr - sphere.w * sphere.w;
+
+	float discriminant = quadraticCoef.y * quadraticCoef.y - 4 * quadraticCoef.x * quadraticCoef.z;
+
+	float2 intersections = -1;
+
+	// Only continue if the ray intersects the sphere
+	if (discriminant >= 0)
+	{
+		float SqrtDiscriminant = sqrt(discriminant);
+		intersections = (-quadraticCoef.y + float2(-1, 1) * SqrtDiscriminant) / (2 * quadraticCoef.x);
+	}
+
+	return intersections;
+}
+
+// Theta - angle between light direction and view direction
+float PhaseRayleigh(const float cosTheta)
+{
+	return (3.0f / (16.0f * PI)) * (1.0f + cosTheta * cosTheta);
+}
+
+// Cornette-Shanks, g - asymmetry
+float PhaseMie(const float cosTheta, const float g)
+{
+	const float g2 = g * g;
+	const float k = (3.0f / (8.0f * PI)) * (1.0f - g2) / (2.0f + g2);
+	return k * (1.0f + cosTheta * cosTheta) / pow(1.0f + g2 - 2.0f * g * cosTheta, 1.5f);
+}
+
+// Rayleigh in x, Mie in y, Ozone in z
+float3 Density(const float h)
+{
+	const float densityM = medium.scaleHeightM > 0 ? exp(-h / medium.scaleHeightM) : 0;
+	const float densityO = medium.ozoneWidth > 0 ? max(0, 1 - abs(h - medium.ozoneCenter) / (medium.ozoneWidth * 0.5)) : 0;
+	return float3(exp(-h / medium.scaleHeightR), densityM, densityO);
+}
+
+// Optical depth as returned by accumulating Density
+float3 Extinction(const float3 opticalDepth)
+{
+	return medium.extinctionR.xyz * opticalDepth.x + medium.extinctionM.xyz * opticalDepth.y + medium.absorptionO.xyz * opticalDepth.z;
+}
+
+float3 Scattering(const float3 density)
+{
+	return medium.scatteringR.xyz * density.x + medium.scatteringM.xyz * density.y;
+}
+
+//============ LUTs
+//Both LUTs are indexed by the cosine of the light zenith angle (u) and altitude (v)
+//ToDo: Non-linear mapping to get more precision near the horizon
+float2 LutUv(const float h, const float cosZenith)
+{
+	return float2(saturate(cosZenith * 0.5f + 0.5f), saturate(h / sampleData.atmosphereThickness));
+}
+
+void LutParams(const float2 uv, out float h, out float cosZenith)
+{
+	h = uv.y * sampleData.atmosphereThickness;
+	cosZenith = uv.x * 2.0f - 1.0f;
+}
+
+float3 SampleLut(Texture2D<float4> lut, SamplerState lutSampler, const float3 x, const float3 lightDir)
+{
+	const float r = length(x);
+	return lut.SampleLevel(lutSampler, LutUv(r - sampleData.planetRadius, dot(x / r, lightDir)), 0).rgb;
+}
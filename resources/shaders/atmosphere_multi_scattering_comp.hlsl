@@ -0,0 +1,77 @@
+#include "atmosphere_common.hlsl"
+
+// Hillaire 2020, "A Scalable and Production Ready Sky and Atmosphere Rendering Technique"
+// Second order scattering towards x, assuming isotropic phase, extended to infinite orders with 1 / (1 - fms)
+
+#define SQRT_DIRECTION_COUNT 8
+#define STEP_COUNT 20
+
+[[vk::combined_image_sampler]][[vk::binding(4)]] Texture2D<float4> transmittanceLut;
+[[vk::combined_image_sampler]][[vk::binding(4)]] SamplerState transmittanceLutSampler;
+
+[[vk::binding(7)]] LUT_IMAGE_FORMAT RWTexture2D<float4> multiScatteringLutOut;
+
+float3 MultiScattering(const float h, const float cosSunZenith)
+{
+	const float3 x = float3(0, sampleData.planetRadius + h, 0);
+	const float3 sunDir = float3(sqrt(saturate(1 - cosSunZenith * cosSunZenith)), cosSunZenith, 0);
+
+	const float4 planet = float4(0, 0, 0, sampleData.planetRadius);
+	const float4 atmosphere = float4(0, 0, 0, sampleData.planetRadius + sampleData.atmosphereThickness);
+	const float isotropicPhase = 1.0f / (4.0f * PI);
+
+	float3 l2 = 0;
+	float3 fms = 0;
+
+	for(int i = 0; i < SQRT_DIRECTION_COUNT; i++)
+	{
+		for(int j = 0; j < SQRT_DIRECTION_COUNT; j++)
+		{
+			//Uniform directions over the sphere
+			const float cosTheta = 1.0f - 2.0f * (i + 0.5f) / SQRT_DIRECTION_COUNT;
+			const float sinTheta = sqrt(saturate(1 - cosTheta * cosTheta));
+			const float phi = 2.0f * PI * (j + 0.5f) / SQRT_DIRECTION_COUNT;
+			const float3 dir = float3(sinTheta * cos(phi), cosTheta, sinTheta * sin(phi));
+
+			const float2 pPlanet = RayIntersectSphere(x, dir, planet);
+			const float tMax = pPlanet.x > 0 ? pPlanet.x : RayIntersectSphere(x, dir, atmosphere).y;
+			const float ds = max(tMax, 0.0f) / STEP_COUNT;
+
+			float3 opticalDepth = 0;
+			for(int k = 0; k < STEP_COUNT; k++)
+			{
+				const float3 xs = x + dir * (ds * (k + 0.5));
+				const float3 density = Density(length(xs) - sampleData.planetRadius) * ds;
+				opticalDepth += density;
+
+				const float3 transmittance = exp(-Extinction(opticalDepth));
+				const float3 scattering = Scattering(density);
+				const float3 sunTransmittance = SampleLut(transmittanceLut, transmittanceLutSampler, xs, sunDir);
+
+				l2 += transmittance * scattering * sunTransmittance * isotropicPhase;
+				fms += transmittance * scattering;
+			}
+		}
+	}
+
+	//Integration against the uniform phase function is the average over all directions
+	const float directionCount = SQRT_DIRECTION_COUNT * SQRT_DIRECTION_COUNT;
+	l2 /= directionCount;
+	fms /= directionCount;
+
+	return l2 / (1.0f - fms);
+}
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID)
+{
+	uint width, height;
+	multiScatteringLutOut.GetDimensions(width, height);
+	if(id.x >= width || id.y >= height)
+		return;
+
+	float h, cosSunZenith;
+	LutParams((float2(id.xy) + 0.5) / float2(width, height), h, cosSunZenith);
+
+	multiScatteringLutOut[id.xy] = float4(MultiScattering(h, cosSunZenith), 1);
+}
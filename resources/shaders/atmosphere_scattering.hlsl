@@ -0,0 +1,134 @@
+#include "common.hlsl"
+#include "atmosphere_common.hlsl"
+
+//Aerial perspective froxels are spread linearly up to this distance from the camera (sky units)
+#define AERIAL_PERSPECTIVE_DISTANCE 0.032f
+
+[[vk::combined_image_sampler]][[vk::binding(4)]] Texture2D<float4> transmittanceLut;
+[[vk::combined_image_sampler]][[vk::binding(4)]] SamplerState transmittanceLutSampler;
+
+[[vk::combined_image_sampler]][[vk::binding(5)]] Texture2D<float4> multiScatteringLut;
+[[vk::combined_image_sampler]][[vk::binding(5)]] SamplerState multiScatteringLutSampler;
+
+//Camera position in sky units, relative to the planet center
+float3 CameraSkyPos()
+{
+	return (resolvedView.worldCameraOrigin.xyz - sampleData.planetPos.xyz) * sampleData.scale;
+}
+
+bool IsInsideAtmosphere(const float3 skyPos)
+{
+	return length(skyPos) < sampleData.planetRadius + sampleData.atmosphereThickness;
+}
+
+//Luminance scattered towards viewPos (sky units, planet centered) along viewDir, up to tMaxLimit
+float3 IntegrateScattering(float3 viewPos, const float3 viewDir, const float tMaxLimit, const float sampleCount, out float3 viewTransmittance)
+{
+	viewTransmittance = 1;
+
+	const float4 atmosphere = float4(0, 0, 0, sampleData.planetRadius + sampleData.atmosphereThickness);
+	
+	float2 p = RayIntersectSphere(viewPos, viewDir, atmosphere);
+	if(p.x < 0 && p.y < 0)
+		return 0;
+
+	float2 pPlanet = RayIntersectSphere(viewPos, viewDir, float4(0, 0, 0, sampleData.planetRadius));
+	if(pPlanet.x > 0)
+		p.y = pPlanet.x;
+	
+	p.x = max(p.x, 0.0f);
+	p.y = min(p.y, tMaxLimit);
+	if(p.y <= p.x)
+		return 0;
+
+	//Accumulated light
+	float3 aR = 0;
+	float3 aM = 0;
+	float3 aMs = 0;
+	
+	float3 opticalDepth = 0;
+	float currentSample = p.x;
+	// Sample size
+	const float ds = (p.y - p.x) / sampleCount;
+
+	for(int i = 0; i < sampleCount; i++)
+	{
+		//Sample point X
+		const float3 x = viewPos + viewDir * (currentSample + ds * 0.5);
+
+		const float h = length(x) - sampleData.planetRadius;
+
+		const float3 densityX = Density(h) * ds;
+		opticalDepth += densityX;
+
+		viewTransmittance = exp(-Extinction(opticalDepth));
+
+		//Light transmittance, zero when the planet is in the way
+		const float3 transmittance = viewTransmittance * SampleLut(transmittanceLut, transmittanceLutSampler, x, sampleData.lightDir.xyz);
+		aR += transmittance * densityX.x;
+		aM += transmittance * densityX.y;
+
+		//Multiple scattering, phase is already accounted for
+		aMs += viewTransmittance * Scattering(densityX) * SampleLut(multiScatteringLut, multiScatteringLutSampler, x, sampleData.lightDir.xyz);
+
+		currentSample += ds;
+	}
+
+	const float cosTheta = dot(sampleData.lightDir.xyz, viewDir);
+	const float phaseR = PhaseRayleigh(cosTheta);
+	const float phaseM = PhaseMie(cosTheta, medium.mieG);
+	float3 l = sampleData.lightIntensity.xyz * (phaseR * medium.scatteringR.xyz * aR + phaseM * medium.scatteringM.xyz * aM + aMs);
+
+	return l;
+}
+
+//============ Sky-view LUT
+//Latitude/longitude around the camera. Longitude is measured from the light and only covers [0, PI],
+//the sky is symmetric about the plane containing the light and the up vector
+void SkyViewBasis(const float3 skyPos, out float3 up, out float3 forward, out float3 right)
+{
+	up = normalize(skyPos);
+
+	const float3 lightDir = sampleData.lightDir.xyz;
+	forward = lightDir - up * dot(lightDir, up);
+	//Light straight above or below, any horizontal direction works
+	if(dot(forward, forward) < 1e-8)
+		forward = abs(up.y) < 0.99 ? cross(up, float3(0, 1, 0)) : cross(up, float3(1, 0, 0));
+	forward = normalize(forward);
+	right = cross(up, forward);
+}
+
+//Latitude is stored non-linearly to keep detail near the horizon
+float2 SkyViewUv(const float3 viewDir, const float3 up, const float3 forward, const float3 right)
+{
+	const float latitude = asin(clamp(dot(viewDir, up), -1.0f, 1.0f));
+	const float v = 0.5f + 0.5f * sign(latitude) * sqrt(abs(latitude) / (0.5f * PI));
+
+	const float2 horizontal = float2(dot(viewDir, forward), abs(dot(viewDir, right)));
+	const float longitude = dot(horizontal, horizontal) > 0 ? atan2(horizontal.y, horizontal.x) : 0;
+
+	return float2(longitude / PI, v);
+}
+
+float3 SkyViewDir(const float2 uv, const float3 up, const float3 forward, const float3 right)
+{
+	const float vc = uv.y * 2.0f - 1.0f;
+	const float latitude = sign(vc) * vc * vc * 0.5f * PI;
+	const float longitude = uv.x * PI;
+
+	return cos(latitude) * (cos(longitude) * forward + sin(longitude) * right) + sin(latitude) * up;
+}
+
+//============ Aerial perspective
+//Clip space xy in [-1, 1] to a world space direction from the camera
+float3 ClipToWorldDir(const float2 clipXy)
+{
+	const float4 world = mul(resolvedView.clipToWorld, float4(clipXy, 0.5f, 1.0f));
+	return normalize(world.xyz / world.w - resolvedView.worldCameraOrigin.xyz);
+}
+
+//Slice is continuous, the center of froxel i is at i + 0.5
+float AerialPerspectiveSliceToDistance(const float slice, const float sliceCount)
+{
+	return slice / sliceCount * AERIAL_PERSPECTIVE_DISTANCE;
+}
@@ -0,0 +1,26 @@
+#include "atmosphere_scattering.hlsl"
+
+#define SKY_VIEW_SAMPLE_COUNT 30
+
+[[vk::binding(8)]] LUT_IMAGE_FORMAT RWTexture2D<float4> skyViewLutOut;
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID)
+{
+	uint width, height;
+	skyViewLutOut.GetDimensions(width, height);
+	if(id.x >= width || id.y >= height)
+		return;
+
+	const float3 skyPos = CameraSkyPos();
+
+	float3 up, forward, right;
+	SkyViewBasis(skyPos, up, forward, right);
+
+	const float3 viewDir = SkyViewDir((float2(id.xy) + 0.5) / float2(width, height), up, forward, right);
+
+	float3 transmittance;
+	const float3 l = IntegrateScattering(skyPos, viewDir, 9000000.0f, SKY_VIEW_SAMPLE_COUNT, transmittance);
+
+	skyViewLutOut[id.xy] = float4(l, 1);
+}
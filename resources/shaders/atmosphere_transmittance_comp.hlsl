@@ -0,0 +1,41 @@
+#include "atmosphere_common.hlsl"
+
+[[vk::binding(6)]] LUT_IMAGE_FORMAT RWTexture2D<float4> transmittanceLutOut;
+
+//Transmittance from altitude h towards the top of the atmosphere, zero if the ray hits the planet
+float3 TransmittanceToTop(const float h, const float cosZenith)
+{
+	const float3 x = float3(0, sampleData.planetRadius + h, 0);
+	const float3 dir = float3(sqrt(saturate(1 - cosZenith * cosZenith)), cosZenith, 0);
+
+	//x is never below the surface, so a downward ray hitting the planet (even at x itself) is blocked
+	const float2 pPlanet = RayIntersectSphere(x, dir, float4(0, 0, 0, sampleData.planetRadius));
+	if(cosZenith < 0 && pPlanet.y > 0)
+		return 0;
+
+	const float2 p = RayIntersectSphere(x, dir, float4(0, 0, 0, sampleData.planetRadius + sampleData.atmosphereThickness));
+	const float ds = max(p.y, 0.0f) / float(sampleData.sampleCountLight);
+
+	float3 opticalDepth = 0;
+	for(int i = 0; i < sampleData.sampleCountLight; i++)
+	{
+		const float3 xLight = x + dir * (ds * (i + 0.5));
+		opticalDepth += Density(length(xLight) - sampleData.planetRadius) * ds;
+	}
+
+	return exp(-Extinction(opticalDepth));
+}
+
+[numthreads(8, 8, 1)]
+void main(uint3 id : SV_DispatchThreadID)
+{
+	uint width, height;
+	transmittanceLutOut.GetDimensions(width, height);
+	if(id.x >= width || id.y >= height)
+		return;
+
+	float h, cosZenith;
+	LutParams((float2(id.xy) + 0.5) / float2(width, height), h, cosZenith);
+
+	transmittanceLutOut[id.xy] = float4(TransmittanceToTop(h, cosZenith), 1);
+}
@@ -0,0 +1,27 @@
+// Shared by the luminance histogram and exposure adaptation passes
+
+#define HISTOGRAM_BIN_COUNT 256
+
+//Matches AutoExposureConstants in vulkan_auto_exposure.rs
+struct AutoExposureConstants
+{
+    float minLogLuminance;
+    float logLuminanceRange;
+    float deltaTime;
+    float adaptationRate;
+    float minEv100;
+    float maxEv100;
+    float compensation;
+    uint pixelCount;
+};
+
+//x - adapted EV100, y - exposure multiplier, z - 1 once initialized
+struct ExposureState
+{
+    float4 value;
+};
+
+[[vk::push_constant]] ConstantBuffer<AutoExposureConstants> constants;
+
+[[vk::binding(1)]] RWStructuredBuffer<uint> histogram;
+[[vk::binding(2)]] RWStructuredBuffer<ExposureState> exposure;
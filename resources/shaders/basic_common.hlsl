@@ -0,0 +1,11 @@
+//Per draw constants of the scene pipeline, see PushConstants
+struct MeshConstants
+{
+    float4x4 model;
+    //Linear, multiplies the vertex color and the base color texture
+    float4 baseColor;
+    //Metallic, roughness
+    float4 metallicRoughness;
+};
+
+[[vk::push_constant]] ConstantBuffer<MeshConstants> pcs;
@@ -1,4 +1,5 @@
-#include "common.hlsl"
+#include "atmosphere_scattering.hlsl"
+#include "basic_common.hlsl"
 
 struct PS_INPUT
 {
@@ -6,6 +7,7 @@ struct PS_INPUT
     float4 fragColor: COLOR0;
     float3 normal: TEXCOORD0;
     float4 worldPos: TEXCOORD1;
+    float2 texCoord: TEXCOORD2;
 };
 
 struct PS_OUTPUT
@@ -13,30 +15,89 @@ struct PS_OUTPUT
     float4 color: SV_Target;
 };
 
+[[vk::combined_image_sampler]][[vk::binding(7)]] Texture3D<float4> aerialPerspectiveVolume;
+[[vk::combined_image_sampler]][[vk::binding(7)]] SamplerState aerialPerspectiveSampler;
 
-//Test
-struct FSampleData
+//Set 1 is bound per mesh, white for meshes without a texture
+[[vk::combined_image_sampler]][[vk::binding(0, 1)]] Texture2D<float4> baseColorTexture;
+[[vk::combined_image_sampler]][[vk::binding(0, 1)]] SamplerState baseColorSampler;
+
+//In-scattering and extinction between the camera and an opaque surface within the aerial perspective volume
+float3 ApplyAerialPerspective(const float3 color, const float3 worldPos)
 {
-	float4 planetPos;
-	float planetRadius;
-	float atmosphereThickness;
-	float sampleCount;
-	float sampleCountLight;
-	float scale;
-
-	float4 lightDir;
-	float4 lightIntensity;
-};
+	const float4 clip = mul(transform.projection, mul(transform.view, float4(worldPos, 1.0)));
+	const float2 uv = clip.xy / clip.w * 0.5f + 0.5f;
+
+	uint width, height, depth;
+	aerialPerspectiveVolume.GetDimensions(width, height, depth);
+
+	const float surfaceDistance = length(worldPos - resolvedView.worldCameraOrigin.xyz) * sampleData.scale;
+	const float slice = surfaceDistance / AERIAL_PERSPECTIVE_DISTANCE * depth;
+
+	float4 ap = aerialPerspectiveVolume.SampleLevel(aerialPerspectiveSampler, float3(uv, saturate(slice / depth)), 0);
+
+	//Nearer than the first froxel center, fade towards no scattering instead of clamping to it
+	const float weight = saturate(slice * 2.0f);
+	ap.rgb *= weight;
+	ap.a = lerp(1.0f, ap.a, weight);
+
+	return color * ap.a + ap.rgb;
+}
+
+//Beyond the volume, e.g. the planet seen from orbit, the scattering along the view ray is integrated directly
+float3 ApplyAtmosphere(const float3 color, const float3 worldPos)
+{
+	const float3 viewPos = CameraSkyPos();
+	const float3 toSurface = (worldPos - resolvedView.worldCameraOrigin.xyz) * sampleData.scale;
+	const float surfaceDistance = length(toSurface);
+
+	if(surfaceDistance <= AERIAL_PERSPECTIVE_DISTANCE && IsInsideAtmosphere(viewPos))
+		return ApplyAerialPerspective(color, worldPos);
+
+	float3 transmittance;
+	const float3 inScattering = IntegrateScattering(viewPos, toSurface / surfaceDistance, surfaceDistance, sampleData.sampleCount, transmittance);
+
+	return color * transmittance + inScattering;
+}
+
+//Metallic-roughness surface lit by the sunlight left after passing through the atmosphere. Lambertian diffuse and
+//a GGX specular lobe with Smith visibility and Schlick's Fresnel
+float3 SunLight(const float3 albedo, const float metallic, const float roughness, const float3 normal, const float3 worldPos)
+{
+	const float3 skyPos = (worldPos - sampleData.planetPos.xyz) * sampleData.scale;
+	const float3 sunTransmittance = SampleLut(transmittanceLut, transmittanceLutSampler, skyPos, sampleData.lightDir.xyz);
+
+	const float3 l = sampleData.lightDir.xyz;
+	const float3 v = normalize(resolvedView.worldCameraOrigin.xyz - worldPos);
+	const float3 h = normalize(l + v);
+
+	const float nDotL = saturate(dot(normal, l));
+	const float nDotV = max(dot(normal, v), 1e-4f);
+	const float nDotH = saturate(dot(normal, h));
+
+	const float alpha = max(roughness * roughness, 1e-3f);
+	const float alpha2 = alpha * alpha;
+	const float d = alpha2 / (PI * pow(nDotH * nDotH * (alpha2 - 1.0f) + 1.0f, 2.0f));
+
+	const float k = alpha / 2.0f;
+	const float visibility = 1.0f / (4.0f * (nDotL * (1.0f - k) + k) * (nDotV * (1.0f - k) + k));
+
+	const float3 f0 = lerp(0.04f, albedo, metallic);
+	const float3 fresnel = f0 + (1.0f - f0) * pow(1.0f - saturate(dot(v, h)), 5.0f);
+
+	const float3 diffuse = (1.0f - fresnel) * (1.0f - metallic) * albedo / PI;
+	const float3 specular = fresnel * d * visibility;
 
-ConstantBuffer<FSampleData> sampleData : register(b3);
+	return (diffuse + specular) * sampleData.lightIntensity.xyz * sunTransmittance * nDotL;
+}
 
 PS_OUTPUT main(PS_INPUT input)
 {
     PS_OUTPUT result;
 
-    float diffuse = saturate(dot(input.normal, sampleData.lightDir.xyz));
-//     result.color = float4(input.normal, 1.0);
-    result.color = diffuse;
+    const float3 albedo = input.fragColor.rgb * pcs.baseColor.rgb * baseColorTexture.Sample(baseColorSampler, input.texCoord).rgb;
+    const float3 color = SunLight(albedo, pcs.metallicRoughness.x, pcs.metallicRoughness.y, normalize(input.normal), input.worldPos.xyz);
+    result.color = float4(ApplyAtmosphere(color, input.worldPos.xyz), 1.0);
 
     return result;
-}
\ No newline at end of file
+}
@@ -1,15 +1,12 @@
 #include "common.hlsl"
+#include "basic_common.hlsl"
 
 struct VS_INPUT
 {
-    float3 position : POSITION;
-    float3 normal : NORMAL;
-    float4 color : COLOR0;
-};
-
-struct VS_PUSH_CONSTANTS
-{
-    float4x4 model;
+    [[vk::location(0)]] float3 position : POSITION;
+    [[vk::location(1)]] float3 normal : NORMAL;
+    [[vk::location(2)]] float4 color : COLOR0;
+    [[vk::location(3)]] float2 texCoord : TEXCOORD0;
 };
 
 struct VS_OUTPUT
@@ -18,9 +15,22 @@ struct VS_OUTPUT
     float4 fragColor: COLOR0;
     float3 normal: TEXCOORD0;
     float4 worldPos: TEXCOORD1;
+    float2 texCoord: TEXCOORD2;
 };
 
-[[vk::push_constant]] VS_PUSH_CONSTANTS pcs;
+//Normals stay perpendicular to the surface under non-uniform scale when transformed by the inverse-transpose of the
+//model matrix. The cofactor matrix is that up to the determinant, of which only the sign matters before normalizing
+float3 TransformNormal(const float4x4 model, const float3 normal)
+{
+    const float3 r0 = model[0].xyz;
+    const float3 r1 = model[1].xyz;
+    const float3 r2 = model[2].xyz;
+
+    const float3x3 cofactor = float3x3(cross(r1, r2), cross(r2, r0), cross(r0, r1));
+    const float determinant = dot(r0, cross(r1, r2));
+
+    return mul(cofactor, normal) * (determinant < 0.0f ? -1.0f : 1.0f);
+}
 
 VS_OUTPUT main(VS_INPUT input)
 {
@@ -35,8 +45,9 @@ VS_OUTPUT main(VS_INPUT input)
     pos = mul(transform.projection, pos);
     result.position = pos;
 
-    result.normal = input.normal;
+    result.normal = TransformNormal(pcs.model, input.normal);
     result.fragColor = input.color;
+    result.texCoord = input.texCoord;
 
     return result;
 }
\ No newline at end of file
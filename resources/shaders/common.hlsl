@@ -12,6 +12,7 @@ struct ViewState
 	float4 worldCameraOrigin;
 	float4 atmosphereLightDirection;
 	float4 atmosphereLightIlluminanceOuterSpace;
+	float4x4 clipToWorld;
 };
 
 ConstantBuffer<Transformation> transform : register(b0);
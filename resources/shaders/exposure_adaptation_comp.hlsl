@@ -0,0 +1,41 @@
+#include "auto_exposure_common.hlsl"
+
+groupshared float weightedCounts[HISTOGRAM_BIN_COUNT];
+
+[numthreads(HISTOGRAM_BIN_COUNT, 1, 1)]
+void main(uint groupIndex : SV_GroupIndex)
+{
+    const uint count = histogram[groupIndex];
+    weightedCounts[groupIndex] = count * groupIndex;
+
+    //Ready for the next frame
+    histogram[groupIndex] = 0;
+    GroupMemoryBarrierWithGroupSync();
+
+    [unroll]
+    for(uint stride = HISTOGRAM_BIN_COUNT / 2; stride > 0; stride >>= 1)
+    {
+        if(groupIndex < stride)
+            weightedCounts[groupIndex] += weightedCounts[groupIndex + stride];
+
+        GroupMemoryBarrierWithGroupSync();
+    }
+
+    if(groupIndex != 0)
+        return;
+
+    //Black pixels are ignored, count is the content of bin 0 read above
+    const float litPixels = max(float(constants.pixelCount) - float(count), 1.0f);
+    const float averageBin = weightedCounts[0] / litPixels - 1.0f;
+    const float averageLuminance = exp2(averageBin / (HISTOGRAM_BIN_COUNT - 2) * constants.logLuminanceRange + constants.minLogLuminance);
+
+    //Lagarde & de Rousiers 2014, "Moving Frostbite to PBR", average luminance based EV100 with K = 12.5
+    const float targetEv100 = clamp(log2(averageLuminance * 100.0f / 12.5f), constants.minEv100, constants.maxEv100);
+
+    const float4 previous = exposure[0].value;
+    const float ev100 = previous.z > 0.0f
+        ? lerp(previous.x, targetEv100, 1.0f - exp(-constants.deltaTime * constants.adaptationRate))
+        : targetEv100;
+
+    exposure[0].value = float4(ev100, exp2(constants.compensation) / (1.2f * exp2(ev100)), 1.0f, 0.0f);
+}
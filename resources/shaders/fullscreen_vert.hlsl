@@ -0,0 +1,18 @@
+// Single triangle covering the screen at the far plane, draw with 3 vertices and no vertex buffer
+
+struct VS_OUTPUT
+{
+    float4 position: SV_POSITION;
+    float2 uv: TEXCOORD0;
+};
+
+VS_OUTPUT main(uint vertexId : SV_VertexID)
+{
+    VS_OUTPUT result;
+
+    //(0, 0), (2, 0), (0, 2), top-left origin as in Vulkan clip space
+    result.uv = float2((vertexId << 1) & 2, vertexId & 2);
+    result.position = float4(result.uv * 2.0f - 1.0f, 1.0f, 1.0f);
+
+    return result;
+}
@@ -0,0 +1,36 @@
+#include "auto_exposure_common.hlsl"
+
+[[vk::combined_image_sampler]][[vk::binding(0)]] Texture2D<float4> hdrColor;
+[[vk::combined_image_sampler]][[vk::binding(0)]] SamplerState hdrColorSampler;
+
+groupshared uint localBins[HISTOGRAM_BIN_COUNT];
+
+// Bin 0 holds black pixels, the rest are log2 luminance spread over [minLogLuminance, minLogLuminance + logLuminanceRange]
+uint LuminanceBin(float3 color)
+{
+    const float luminance = dot(color, float3(0.2126f, 0.7152f, 0.0722f));
+    if(luminance < 1e-5f)
+        return 0;
+
+    const float logLuminance = saturate((log2(luminance) - constants.minLogLuminance) / constants.logLuminanceRange);
+    return uint(logLuminance * (HISTOGRAM_BIN_COUNT - 2) + 1.0f);
+}
+
+[numthreads(16, 16, 1)]
+void main(uint3 id : SV_DispatchThreadID, uint groupIndex : SV_GroupIndex)
+{
+    localBins[groupIndex] = 0;
+    GroupMemoryBarrierWithGroupSync();
+
+    uint width, height;
+    hdrColor.GetDimensions(width, height);
+
+    if(id.x < width && id.y < height)
+    {
+        const uint bin = LuminanceBin(hdrColor.Load(int3(id.xy, 0)).rgb);
+        InterlockedAdd(localBins[bin], 1);
+    }
+
+    GroupMemoryBarrierWithGroupSync();
+    InterlockedAdd(histogram[groupIndex], localBins[groupIndex]);
+}
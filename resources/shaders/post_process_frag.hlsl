@@ -0,0 +1,98 @@
+// Exposure and tonemapping of the HDR scene color, output is linear and encoded by the sRGB swapchain
+
+//Matches Tonemapper in config.rs
+#define TONEMAPPER_ACES 0
+#define TONEMAPPER_REINHARD 1
+#define TONEMAPPER_AGX 2
+#define TONEMAPPER_LINEAR 3
+
+struct PS_INPUT
+{
+    float4 position: SV_POSITION;
+    float2 uv: TEXCOORD0;
+};
+
+struct PostProcessConstants
+{
+    float exposure;
+    uint tonemapper;
+    //Exposure is read from the adapted state instead
+    uint autoExposure;
+};
+
+//Written by exposure_adaptation_comp.hlsl, y is the exposure multiplier
+struct ExposureState
+{
+    float4 value;
+};
+
+[[vk::push_constant]] ConstantBuffer<PostProcessConstants> constants;
+
+[[vk::combined_image_sampler]][[vk::binding(0)]] Texture2D<float4> hdrColor;
+[[vk::combined_image_sampler]][[vk::binding(0)]] SamplerState hdrColorSampler;
+[[vk::binding(1)]] StructuredBuffer<ExposureState> adaptedExposure;
+
+// Narkowicz 2015, "ACES Filmic Tone Mapping Curve"
+float3 Aces(float3 x)
+{
+    x *= 0.6f;
+    return saturate((x * (2.51f * x + 0.03f)) / (x * (2.43f * x + 0.59f) + 0.14f));
+}
+
+float3 Reinhard(float3 x)
+{
+    return x / (1.0f + x);
+}
+
+// Wrensch 2023, "Minimal AgX Implementation", 6th order fit of the default contrast curve
+float3 AgxContrastApprox(float3 x)
+{
+    const float3 x2 = x * x;
+    const float3 x4 = x2 * x2;
+
+    return 15.5f * x4 * x2 - 40.14f * x4 * x + 31.96f * x4 - 6.868f * x2 * x + 0.4298f * x2 + 0.1191f * x - 0.00232f;
+}
+
+float3 Agx(float3 x)
+{
+    //Row vectors, so these read the same as the column-major GLSL reference
+    const float3x3 inset = float3x3(
+        0.842479062253094f, 0.0423282422610123f, 0.0423756549057051f,
+        0.0784335999999992f, 0.878468636469772f, 0.0784336f,
+        0.0792237451477643f, 0.0791661274605434f, 0.879142973793104f);
+
+    const float3x3 outset = float3x3(
+        1.19687900512017f, -0.0528968517574562f, -0.0529716355144438f,
+        -0.0980208811401368f, 1.15190312990417f, -0.0980434501171241f,
+        -0.0990297440797205f, -0.0989611768448433f, 1.15107367264116f);
+
+    const float minEv = -12.47393f;
+    const float maxEv = 4.026069f;
+
+    x = mul(x, inset);
+    x = clamp(log2(max(x, 1e-10f)), minEv, maxEv);
+    x = (x - minEv) / (maxEv - minEv);
+    x = AgxContrastApprox(x);
+    x = mul(x, outset);
+
+    //AgX outputs display encoded values
+    return pow(saturate(x), 2.2f);
+}
+
+float4 main(PS_INPUT input) : SV_TARGET
+{
+    const float exposure = constants.autoExposure != 0 ? adaptedExposure[0].value.y : constants.exposure;
+    const float3 color = hdrColor.SampleLevel(hdrColorSampler, input.uv, 0).rgb * exposure;
+
+    switch(constants.tonemapper)
+    {
+        case TONEMAPPER_REINHARD:
+            return float4(Reinhard(color), 1);
+        case TONEMAPPER_AGX:
+            return float4(Agx(color), 1);
+        case TONEMAPPER_LINEAR:
+            return float4(saturate(color), 1);
+        default:
+            return float4(Aces(color), 1);
+    }
+}
@@ -0,0 +1,48 @@
+// Sky behind all scene geometry, drawn as a fullscreen triangle at the far plane
+
+#include "atmosphere_scattering.hlsl"
+
+[[vk::combined_image_sampler]][[vk::binding(6)]] Texture2D<float4> skyViewLut;
+[[vk::combined_image_sampler]][[vk::binding(6)]] SamplerState skyViewLutSampler;
+
+float3 SingleScattering(const float3 worldPos, float3 viewPos)
+{
+	const float3 viewDir = normalize(worldPos - viewPos);
+	viewPos = (viewPos - sampleData.planetPos.xyz) * sampleData.scale;
+
+	//The sky-view LUT only has enough resolution around the camera inside the atmosphere
+	if(!IsInsideAtmosphere(viewPos))
+	{
+		float3 transmittance;
+		return IntegrateScattering(viewPos, viewDir, 9000000.0f, sampleData.sampleCount, transmittance);
+	}
+
+	float3 up, forward, right;
+	SkyViewBasis(viewPos, up, forward, right);
+
+	return skyViewLut.SampleLevel(skyViewLutSampler, SkyViewUv(viewDir, up, forward, right), 0).rgb;
+}
+
+struct PS_INPUT
+{
+    float4 position: SV_POSITION;
+    float2 uv: TEXCOORD0;
+};
+
+struct PS_OUTPUT
+{
+    float4 Color : SV_Target;
+};
+
+PS_OUTPUT main(PS_INPUT input)
+{
+	PS_OUTPUT result;
+
+	//View ray through the pixel, reconstructed from the inverse view projection
+	const float3 cameraPos = resolvedView.worldCameraOrigin.xyz;
+	const float3 viewDir = ClipToWorldDir(input.uv * 2.0f - 1.0f);
+
+	result.Color = float4(SingleScattering(cameraPos + viewDir, cameraPos), 1.0);
+
+	return result;
+}
@@ -12,6 +12,9 @@ Get-ChildItem -Path $shader_path -Filter *.hlsl -Recurse -File -Name| ForEach-Ob
     ElseIf ($filename.EndsWith('frag')) {
         $shader_type = 'ps_6_0'
     }
+    ElseIf ($filename.EndsWith('comp')) {
+        $shader_type = 'cs_6_0'
+    }
     Else {
         continue
     }
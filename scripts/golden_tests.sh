@@ -0,0 +1,20 @@
+#!/bin/sh
+# Runs the golden image tests on the lavapipe software Vulkan driver, so no GPU is needed.
+# Pass --update to rewrite the reference images in resources/tests/golden instead of comparing against them.
+set -e
+cd "$(dirname "$0")/.."
+
+if [ -z "$VK_ICD_FILENAMES" ]; then
+    VK_ICD_FILENAMES=$(ls /usr/share/vulkan/icd.d/lvp_icd.*.json 2>/dev/null | head -n 1)
+    if [ -z "$VK_ICD_FILENAMES" ]; then
+        echo "lavapipe was not found, install Mesa's Vulkan drivers (mesa-vulkan-drivers) or set VK_ICD_FILENAMES" >&2
+        exit 1
+    fi
+    export VK_ICD_FILENAMES
+fi
+
+if [ "$1" = "--update" ]; then
+    export UPDATE_GOLDEN=1
+fi
+
+cargo test --features golden-tests golden
@@ -1,4 +1,5 @@
 pub use app::App;
 
-pub(crate) mod app;
+#[allow(clippy::module_inception)]
+pub mod app;
 
@@ -1,7 +1,9 @@
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use anyhow::Result;
-use log::info;
+use anyhow::{anyhow, Result};
+use log::{error, info};
 use winit::application::ApplicationHandler;
 use winit::dpi::{PhysicalSize, Size};
 use winit::error::EventLoopError;
@@ -11,14 +13,28 @@ use winit::keyboard::{Key, NamedKey, PhysicalKey};
 use winit::platform::modifier_supplement::KeyEventExtModifierSupplement;
 use winit::window::{Window, WindowId};
 
+use crate::assets::gltf_loader::GltfScene;
+use crate::assets::mesh_loader::MeshLoadError;
+use crate::config::atmosphere_settings::AtmosphereSettings;
 use crate::config::config::{Config, GraphicsApiType};
+use crate::controls::atmosphere_controls::AtmosphereControls;
+use crate::graphics::frame_capture::FrameCapture;
 use crate::graphics::rhi::RHI;
 use crate::graphics::vulkan::vulkan_rhi::RHIVulkan;
+use crate::world::clock::ManualClock;
 use crate::world::game_object::GameObject;
+use crate::world::scene::SceneFile;
+use crate::world::time_of_day::TimeOfDay;
+use crate::world::timestep::FixedTimestep;
 use crate::world::world::World;
 
+const SCREENSHOT_DIR: &str = "./screenshots";
+//Scene is saved here when none is configured
+const DEFAULT_SCENE_PATH: &str = "./scenes/scene.json";
+
 pub struct App {
     config: Config,
+    atmosphere_controls: AtmosphereControls,
     window: Option<Window>,
     graphics: Option<RHIVulkan>,
     world_ref: Arc<RwLock<World>>
@@ -34,10 +50,13 @@ impl ApplicationHandler for App {
 
         if self.graphics.is_none() {
             info!("Creating graphics...");
-            let mut api = RHIVulkan::new(self.window.as_ref().unwrap(), self.config.graphics.get(&GraphicsApiType::Vulkan).cloned().unwrap());
-            api.initialize(self.world_ref.clone()).unwrap();
-
-            self.graphics = Some(api);
+            match self.create_graphics() {
+                Ok(api) => self.graphics = Some(api),
+                Err(e) => {
+                    error!("Failed to create graphics: {:#}", e);
+                    event_loop.exit();
+                }
+            }
         }
     }
 
@@ -57,7 +76,7 @@ impl ApplicationHandler for App {
 
                 {
                     let mut world = self.world_ref.write().unwrap();
-                    world.update(0.0);
+                    world.advance_frame();
                 }
 
                 // Draw.
@@ -70,12 +89,44 @@ impl ApplicationHandler for App {
                 // can render here instead.
                 self.window.as_ref().unwrap().request_redraw();
             },
-            WindowEvent::KeyboardInput {device_id, event, is_synthetic} => {
+            WindowEvent::KeyboardInput {event, ..} => {
+                if let PhysicalKey::Code(key_code) = event.physical_key {
+                    let settings_changed = {
+                        let mut world = self.world_ref.write().unwrap();
+                        let direction = world.sun().direction();
+                        let settings_changed = self.atmosphere_controls.handle_input_key(key_code, event.state, world.sun_mut());
+
+                        //The simulated clock would move the sun straight back on the next tick
+                        if world.sun().direction() != direction && world.time_of_day().is_some() {
+                            info!("Sun moved manually, time of day stopped");
+                            world.set_time_of_day(None);
+                        }
+
+                        settings_changed
+                    };
+
+                    if settings_changed {
+                        if let Some(graphics) = self.graphics.as_mut() {
+                            graphics.set_atmosphere(self.atmosphere_controls.sample_data(), self.atmosphere_controls.medium());
+                        }
+                    }
+                }
+
                 if let Key::Named(named_key) = event.key_without_modifiers() {
                     match named_key {
                         NamedKey::Escape if event.state == ElementState::Pressed => {
                             event_loop.exit();
                         }
+                        NamedKey::F5 if event.state == ElementState::Pressed && !event.repeat => {
+                            if let Err(e) = self.save_scene() {
+                                error!("Failed to save scene: {}", e);
+                            }
+                        }
+                        NamedKey::F12 if event.state == ElementState::Pressed && !event.repeat => {
+                            if let Err(e) = self.take_screenshot() {
+                                error!("Failed to take screenshot: {}", e);
+                            }
+                        }
                         _ => ()
                     }
                 }
@@ -84,7 +135,7 @@ impl ApplicationHandler for App {
         }
     }
 
-    fn device_event(&mut self, event_loop: &ActiveEventLoop, device_id: DeviceId, event: DeviceEvent) {
+    fn device_event(&mut self, _event_loop: &ActiveEventLoop, _device_id: DeviceId, event: DeviceEvent) {
         match event {
             DeviceEvent::MouseMotion {delta} => {
                 let mut world = self.world_ref.write().unwrap();
@@ -94,12 +145,12 @@ impl ApplicationHandler for App {
                 //
                 // world.active_camera_mut().transform_mut().rotate(y as f32, x as f32, 0.0);
 
-                world.main_camera.handle_mouse_move(delta);
+                world.main_camera_mut().handle_mouse_move(delta);
             },
             DeviceEvent::Key(key) => {
                 if let PhysicalKey::Code(key_code) = key.physical_key {
                     let mut world = self.world_ref.write().unwrap();
-                    world.main_camera.handle_input_key(key_code, key.state);
+                    world.main_camera_mut().handle_input_key(key_code, key.state);
                 }
             }
             _ => ()
@@ -109,34 +160,147 @@ impl ApplicationHandler for App {
     fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
         info!("Destroying app");
 
-        match self.graphics.as_mut() {
-            Some(x) => x.destroy(),
-            None => ()
+        if let Some(graphics) = self.graphics.as_mut() {
+            graphics.destroy();
         }
+
+        self.world_ref.write().unwrap().destroy();
     }
 }
 
 impl App {
-    pub(crate) fn new(config: Config) -> Self {
-        let world = World::new();
+    //The built-in scene is used without a scene file
+    pub fn new(config: Config, atmosphere: AtmosphereSettings, scene: Option<SceneFile>) -> Result<Self> {
+        let mut world = scene.as_ref().map_or_else(World::new, World::from_scene);
+        world.sun_mut().set_illuminance(atmosphere.light_intensity);
+        world.set_time_of_day(config.time_of_day.as_ref().map(TimeOfDay::new));
+        world.set_timestep(FixedTimestep::try_from(config.simulation)?);
 
-        Self {
+        if let Some(path) = config.model.as_deref().filter(|path| GltfScene::is_gltf(path)) {
+            let scene = GltfScene::load(path).map_err(|source| MeshLoadError::Gltf { path: path.to_string(), source })?;
+            let entities = world.add_model(&scene, path);
+            info!("Spawned {} entities from '{}'", entities.len(), path);
+        }
+
+        world.start();
+
+        Ok(Self {
             config,
+            atmosphere_controls: AtmosphereControls::new(atmosphere),
             window: None,
             graphics: None,
             world_ref: Arc::new(RwLock::new(world))
-        }
+        })
     }
 
-    pub(crate) fn run(&mut self) -> Result<(), EventLoopError> {
+    pub fn run(&mut self) -> Result<(), EventLoopError> {
         let event_loop = EventLoop::new().unwrap();
         event_loop.set_control_flow(ControlFlow::Poll);
         event_loop.run_app(self)
     }
 
+    fn create_graphics(&self) -> Result<RHIVulkan> {
+        let window = self.window.as_ref().ok_or_else(|| anyhow!("Window is not created"))?;
+        let graphics_config = self.config.graphics.get(&GraphicsApiType::Vulkan).cloned().unwrap();
+
+        let mut api = RHIVulkan::new(window, graphics_config, self.atmosphere_controls.settings(), self.config.model.as_deref())?;
+        if let Err(e) = api.initialize(self.world_ref.clone()) {
+            api.destroy();
+            return Err(e);
+        }
+
+        api.set_atmosphere(self.atmosphere_controls.sample_data(), self.atmosphere_controls.medium());
+        Ok(api)
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.config.headless.is_some()
+    }
+
+    pub fn run_headless(&mut self) -> Result<()> {
+        let headless = self.config.headless.clone().ok_or_else(|| anyhow!("Headless config is missing"))?;
+        let graphics_config = self.config.graphics.get(&GraphicsApiType::Vulkan).cloned().unwrap();
+
+        info!("Creating headless graphics...");
+        let mut api = RHIVulkan::new_headless(headless.width, headless.height, graphics_config, self.atmosphere_controls.settings(), self.config.model.as_deref())?;
+        info!("Rendering {} frames offscreen at {}x{}", headless.frames, api.get_width(), api.get_height());
+        api.initialize(self.world_ref.clone())?;
+        api.set_atmosphere(self.atmosphere_controls.sample_data(), self.atmosphere_controls.medium());
+
+        //One tick per frame, so captures don't depend on how fast the frames render
+        let clock = ManualClock::default();
+        let tick = {
+            let mut world = self.world_ref.write().unwrap();
+            world.set_clock(clock.clone());
+            world.timestep().tick()
+        };
+
+        for frame in 0..headless.frames {
+            clock.advance(tick);
+            {
+                let mut world = self.world_ref.write().unwrap();
+                world.advance_frame();
+            }
+
+            if frame + 1 == headless.frames && headless.capture_path.is_some() {
+                api.request_capture()?;
+            }
+
+            api.render_offscreen()?;
+        }
+
+        if let Some(capture_path) = headless.capture_path {
+            let capture = api.take_capture()?.ok_or_else(|| anyhow!("No frame was rendered to capture"))?;
+            capture.save_png(Path::new(&capture_path))?;
+            info!("Saved last frame to {}", capture_path);
+        }
+
+        api.destroy();
+        self.world_ref.write().unwrap().destroy();
+
+        Ok(())
+    }
+
+    //The screenshot is saved once the next frame is rendered
+    fn take_screenshot(&mut self) -> Result<()> {
+        let graphics = self.graphics.as_mut().ok_or_else(|| anyhow!("Graphics are not initialized"))?;
+        graphics.request_capture()
+    }
+
+    fn save_screenshot(capture: FrameCapture) -> Result<()> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        let path = PathBuf::from(SCREENSHOT_DIR).join(format!("screenshot_{}.png", timestamp));
+
+        capture.save_png(&path)?;
+        info!("Saved screenshot to {}", path.display());
+
+        Ok(())
+    }
+
+    //Overwrites the configured scene file if there is one
+    fn save_scene(&self) -> Result<()> {
+        let path = self.config.scene.as_deref().unwrap_or(DEFAULT_SCENE_PATH);
+
+        self.world_ref.read().unwrap().to_scene().save(path)?;
+        info!("Saved scene to {}", path);
+
+        Ok(())
+    }
+
     fn render(&mut self) -> Result<()> {
-        if self.graphics.is_some() {
-            return self.graphics.as_mut().unwrap().render(self.window.as_ref().unwrap());
+        if let Some(graphics) = self.graphics.as_mut() {
+            graphics.render(self.window.as_ref().unwrap())?;
+
+            //A failed screenshot must not stop the application, like a failed request for one
+            match graphics.take_capture() {
+                Ok(Some(capture)) => {
+                    if let Err(e) = Self::save_screenshot(capture) {
+                        error!("Failed to save screenshot: {}", e);
+                    }
+                }
+                Ok(None) => (),
+                Err(e) => error!("Failed to take screenshot: {}", e),
+            }
         }
 
         Ok(())
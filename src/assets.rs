@@ -0,0 +1,5 @@
+pub mod vertex;
+pub mod mesh;
+pub mod material;
+pub mod mesh_loader;
+pub mod gltf_loader;
\ No newline at end of file
@@ -0,0 +1,434 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, Matrix3, Point3, SquareMatrix, Transform as cgTransform};
+use gltf::buffer::Data as BufferData;
+use gltf::image::{Data as ImageData, Format};
+use gltf::mesh::Mode;
+use gltf::{Document, Gltf};
+use log::warn;
+use thiserror::Error;
+
+use crate::assets::material::{Material, Texture};
+use crate::assets::mesh::Mesh;
+use crate::assets::mesh_loader::smooth_normals;
+use crate::assets::vertex::Vertex;
+use crate::utils::math::{Matrix4x4, Vector2, Vector3, Vector4};
+use crate::world::transform::Transform;
+
+#[derive(Debug, Error)]
+pub enum GltfError {
+    #[error("{0}.")]
+    Import(#[from] gltf::Error),
+    #[error("Mesh {0} has an index past its vertex count.")]
+    IndexOutOfRange(usize),
+    #[error("Attribute counts of mesh {0} differ.")]
+    AttributeCount(usize),
+    #[error("Node {0} is its own ancestor.")]
+    NodeCycle(usize),
+}
+
+//Imported glTF scene. The triangle primitives of a glTF mesh are merged per material, so a glTF mesh becomes one
+//Mesh for every material it uses
+#[derive(Debug, Default)]
+pub struct GltfScene {
+    pub meshes: Vec<Mesh>,
+    //Nodes of the default scene, parents before children
+    pub nodes: Vec<GltfNode>,
+}
+
+#[derive(Debug)]
+pub struct GltfNode {
+    pub name: String,
+    //Indices into GltfScene::meshes
+    pub meshes: Vec<usize>,
+    //Relative to the scene root, column vectors as in glTF
+    pub world_matrix: Matrix4x4,
+}
+
+impl GltfNode {
+    //Assumes the node chain has no shear, which glTF requires for animated nodes only
+    pub fn transform(&self) -> Transform {
+        Transform::from_matrix(self.world_matrix)
+    }
+}
+
+impl GltfScene {
+    pub fn is_gltf(path: impl AsRef<Path>) -> bool {
+        path.as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("gltf") || e.eq_ignore_ascii_case("glb"))
+    }
+
+    //Accepts both .gltf with external or embedded buffers and binary .glb
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, GltfError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(gltf::Error::Io)?;
+
+        Self::from_bytes(&bytes, path.parent().unwrap_or(Path::new(".")))
+    }
+
+    //External buffers and images are resolved relative to `base_dir`
+    pub fn from_bytes(bytes: &[u8], base_dir: &Path) -> Result<Self, GltfError> {
+        let Gltf { document, blob } = Gltf::from_slice(bytes)?;
+        let buffers = gltf::import_buffers(&document, Some(base_dir), blob)?;
+        let images = gltf::import_images(&document, Some(base_dir), &buffers)?;
+
+        Self::from_document(&document, &buffers, &images)
+    }
+
+    fn from_document(document: &Document, buffers: &[BufferData], images: &[ImageData]) -> Result<Self, GltfError> {
+        let textures = images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| texture(index, image).map(Arc::new))
+            .collect::<Vec<_>>();
+
+        //Indices into `meshes` by glTF mesh
+        let mut mesh_groups = Vec::new();
+        let mut meshes = Vec::new();
+
+        for mesh in document.meshes() {
+            let group = read_mesh(&mesh, buffers, &textures)?;
+            mesh_groups.push((meshes.len()..meshes.len() + group.len()).collect::<Vec<_>>());
+            meshes.extend(group);
+        }
+
+        Ok(Self { meshes, nodes: scene_nodes(document, &mesh_groups)? })
+    }
+
+    //Every mesh instance moved into scene space, for drawing the whole scene as one Mesh. Base colors are baked
+    //into the vertex colors, textures are lost
+    pub fn flatten(&self) -> Mesh {
+        let mut result = Mesh::default();
+
+        for node in &self.nodes {
+            let normal_matrix = Matrix3::from_cols(node.world_matrix.x.truncate(), node.world_matrix.y.truncate(), node.world_matrix.z.truncate())
+                .invert()
+                .map(|m| m.transpose())
+                .unwrap_or(Matrix3::identity());
+
+            for mesh in node.meshes.iter().map(|&index| &self.meshes[index]) {
+                let base_color = mesh.material.base_color;
+
+                let base_index = result.vertices.len() as u32;
+                result.vertices.extend(mesh.vertices.iter().map(|v| {
+                    let position = node.world_matrix.transform_point(Point3::from_vec(v.position()));
+                    let normal = normal_matrix * v.normal();
+                    let normal = if normal.magnitude2() > 0.0 { normal.normalize() } else { normal };
+
+                    Vertex::new(position.to_vec(), normal, v.color().zip(base_color, |a, b| a * b)).with_tex_coord(v.tex_coord())
+                }));
+                result.indices.extend(mesh.indices.iter().map(|i| base_index + i));
+            }
+        }
+
+        result
+    }
+}
+
+//One Mesh per material, in the order the materials are first used
+fn read_mesh(mesh: &gltf::Mesh, buffers: &[BufferData], textures: &[Option<Arc<Texture>>]) -> Result<Vec<Mesh>, GltfError> {
+    let mut result = Vec::<(Option<usize>, Mesh)>::new();
+
+    for primitive in mesh.primitives() {
+        if primitive.mode() != Mode::Triangles {
+            warn!("Skipping glTF primitive of mesh {} with mode {:?}, only triangles are supported", mesh.index(), primitive.mode());
+            continue;
+        }
+
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+
+        let Some(positions) = reader.read_positions() else {
+            continue;
+        };
+        let positions = positions.map(Vector3::from).collect::<Vec<_>>();
+
+        let indices = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect::<Vec<_>>(),
+        };
+
+        if indices.iter().any(|&i| i as usize >= positions.len()) {
+            return Err(GltfError::IndexOutOfRange(mesh.index()));
+        }
+
+        let normals = match reader.read_normals() {
+            Some(normals) => normals.map(Vector3::from).collect(),
+            None => smooth_normals(&positions, &indices),
+        };
+
+        let colors = match reader.read_colors(0) {
+            Some(colors) => colors.into_rgba_f32().map(Vector4::from).collect(),
+            None => vec![Vector4::new(1.0, 1.0, 1.0, 1.0); positions.len()],
+        };
+
+        let material = primitive.material();
+        let tex_coord_set = material.pbr_metallic_roughness().base_color_texture().map_or(0, |info| info.tex_coord());
+
+        let tex_coords = match reader.read_tex_coords(tex_coord_set) {
+            Some(tex_coords) => tex_coords.into_f32().map(Vector2::from).collect(),
+            None => vec![Vector2::new(0.0, 0.0); positions.len()],
+        };
+
+        if normals.len() != positions.len() || colors.len() != positions.len() || tex_coords.len() != positions.len() {
+            return Err(GltfError::AttributeCount(mesh.index()));
+        }
+
+        let group = match result.iter().position(|(index, _)| *index == material.index()) {
+            Some(group) => &mut result[group].1,
+            None => {
+                result.push((material.index(), Mesh { material: read_material(&material, textures), ..Default::default() }));
+                &mut result.last_mut().unwrap().1
+            }
+        };
+
+        let base_index = group.vertices.len() as u32;
+        group.vertices.extend((0..positions.len()).map(|i| Vertex::new(positions[i], normals[i], colors[i]).with_tex_coord(tex_coords[i])));
+        group.indices.extend(indices.iter().map(|i| base_index + i));
+    }
+
+    Ok(result.into_iter().map(|(_, mesh)| mesh).collect())
+}
+
+//Primitives without a material get glTF's default one
+fn read_material(material: &gltf::Material, textures: &[Option<Arc<Texture>>]) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+
+    Material {
+        base_color: Vector4::from(pbr.base_color_factor()),
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        base_color_texture: pbr.base_color_texture().and_then(|info| textures[info.texture().source().index()].clone()),
+    }
+}
+
+//Decoded image expanded to RGBA8, gray images are spread over RGB and 16 bit channels keep their high byte
+fn texture(index: usize, image: &ImageData) -> Option<Texture> {
+    let (channels, channel_size) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        format => {
+            warn!("Skipping glTF image {} with format {:?}", index, format);
+            return None;
+        }
+    };
+
+    let pixels = image.pixels
+        .chunks_exact(channels * channel_size)
+        .flat_map(|pixel| {
+            let channel = |c: usize| match channel_size {
+                1 => pixel[c],
+                _ => (u16::from_ne_bytes([pixel[2 * c], pixel[2 * c + 1]]) >> 8) as u8,
+            };
+
+            match channels {
+                1 => [channel(0), channel(0), channel(0), u8::MAX],
+                2 => [channel(0), channel(0), channel(0), channel(1)],
+                3 => [channel(0), channel(1), channel(2), u8::MAX],
+                _ => [channel(0), channel(1), channel(2), channel(3)],
+            }
+        })
+        .collect();
+
+    Some(Texture { width: image.width, height: image.height, pixels })
+}
+
+//Walks the default scene, or all root nodes when the file has no scenes
+fn scene_nodes(document: &Document, mesh_groups: &[Vec<usize>]) -> Result<Vec<GltfNode>, GltfError> {
+    let roots = match document.default_scene().or_else(|| document.scenes().next()) {
+        Some(scene) => scene.nodes().collect::<Vec<_>>(),
+        None => {
+            let children = document.nodes().flat_map(|n| n.children().map(|c| c.index())).collect::<HashSet<_>>();
+            document.nodes().filter(|n| !children.contains(&n.index())).collect()
+        }
+    };
+
+    let mut result = Vec::<GltfNode>::new();
+    let mut stack = roots.into_iter().rev().map(|node| (node, None)).collect::<Vec<_>>();
+
+    while let Some((node, parent)) = stack.pop() {
+        //glTF requires the hierarchy to be a forest, this keeps a malformed file from looping
+        if result.len() > document.nodes().len() {
+            return Err(GltfError::NodeCycle(node.index()));
+        }
+
+        let parent_matrix = parent.map(|p: usize| result[p].world_matrix).unwrap_or(Matrix4x4::identity());
+
+        result.push(GltfNode {
+            name: node.name().map_or_else(|| format!("node_{}", node.index()), str::to_string),
+            meshes: node.mesh().map(|mesh| mesh_groups[mesh.index()].clone()).unwrap_or_default(),
+            world_matrix: parent_matrix * Matrix4x4::from(node.transform().matrix()),
+        });
+
+        let this = result.len() - 1;
+        stack.extend(node.children().collect::<Vec<_>>().into_iter().rev().map(|child| (child, Some(this))));
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLB_MAGIC: u32 = 0x4654_6C67;
+    const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+    const GLB_CHUNK_BIN: u32 = 0x004E_4942;
+
+    //Triangle in the XY plane: 3 VEC3 float positions, 3 VEC2 float texture coordinates, 3 u16 indices and
+    //2 bytes of padding, followed by the PNG image
+    fn triangle_buffer() -> Vec<u8> {
+        let positions: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let tex_coords: [f32; 6] = [0.0, 1.0, 1.0, 1.0, 0.0, 0.0];
+        let indices: [u16; 4] = [0, 1, 2, 0];
+
+        positions.iter().chain(tex_coords.iter()).flat_map(|p| p.to_le_bytes())
+            .chain(indices.iter().flat_map(|i| i.to_le_bytes()))
+            .chain(png_image())
+            .collect()
+    }
+
+    //2x1 image, a red and a half transparent blue pixel
+    fn png_image() -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(&[255, 0, 0, 255, 0, 0, 255, 128]).unwrap();
+
+        bytes
+    }
+
+    fn encode_base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+        bytes.chunks(3).flat_map(|chunk| {
+            let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &b)| bits | (b as u32) << (16 - 8 * i));
+            (0..4).map(move |i| if i <= chunk.len() { ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char } else { '=' })
+        }).collect()
+    }
+
+    //The second primitive reuses the triangle with another material, the image is given as JSON
+    fn triangle_document(buffer_uri: Option<String>, image: &str) -> String {
+        let buffer_length = triangle_buffer().len();
+        let uri = buffer_uri.map(|uri| format!(r#""uri": "{}", "#, uri)).unwrap_or_default();
+
+        format!(r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scene": 0,
+            "scenes": [{{ "nodes": [0] }}],
+            "nodes": [
+                {{ "name": "parent", "translation": [0.0, 2.0, 0.0], "children": [1] }},
+                {{ "name": "child", "mesh": 0, "scale": [2.0, 2.0, 2.0], "rotation": [0.0, 0.0, 0.7071068, 0.7071068] }}
+            ],
+            "meshes": [{{ "primitives": [
+                {{ "attributes": {{ "POSITION": 0, "TEXCOORD_0": 1 }}, "indices": 2, "material": 0 }},
+                {{ "attributes": {{ "POSITION": 0 }}, "indices": 2, "material": 1 }}
+            ] }}],
+            "materials": [
+                {{ "pbrMetallicRoughness": {{ "baseColorFactor": [1.0, 0.5, 0.0, 1.0], "metallicFactor": 0.0, "roughnessFactor": 0.25, "baseColorTexture": {{ "index": 0 }} }} }},
+                {{ "pbrMetallicRoughness": {{ "metallicFactor": 1.0 }} }}
+            ],
+            "textures": [{{ "source": 0 }}],
+            "images": [{}],
+            "accessors": [
+                {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "max": [1.0, 1.0, 0.0], "min": [0.0, 0.0, 0.0] }},
+                {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC2" }},
+                {{ "bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+            ],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                {{ "buffer": 0, "byteOffset": 36, "byteLength": 24 }},
+                {{ "buffer": 0, "byteOffset": 60, "byteLength": 6 }},
+                {{ "buffer": 0, "byteOffset": 68, "byteLength": {} }}
+            ],
+            "buffers": [{{ {}"byteLength": {} }}]
+        }}"#, image, buffer_length.saturating_sub(68), uri, buffer_length)
+    }
+
+    fn assert_triangle_scene(scene: &GltfScene) {
+        assert_eq!(scene.meshes.len(), 2);
+        assert_eq!(scene.meshes[0].indices, vec![0, 1, 2]);
+        assert!(scene.meshes[0].vertices.iter().all(|v| v.normal() == Vector3::unit_z() && v.color() == Vector4::new(1.0, 1.0, 1.0, 1.0)));
+        assert_eq!(scene.meshes[0].vertices[2].tex_coord(), Vector2::new(0.0, 0.0));
+        assert_eq!(scene.meshes[0].vertices[1].tex_coord(), Vector2::new(1.0, 1.0));
+
+        let material = &scene.meshes[0].material;
+        assert_eq!(material.base_color, Vector4::new(1.0, 0.5, 0.0, 1.0));
+        assert_eq!((material.metallic, material.roughness), (0.0, 0.25));
+
+        let texture = material.base_color_texture.as_ref().unwrap();
+        assert_eq!((texture.width, texture.height), (2, 1));
+        assert_eq!(texture.pixels, vec![255, 0, 0, 255, 0, 0, 255, 128]);
+
+        //glTF defaults for what the material leaves out
+        let material = &scene.meshes[1].material;
+        assert_eq!((material.base_color, material.metallic, material.roughness), (Vector4::new(1.0, 1.0, 1.0, 1.0), 1.0, 1.0));
+        assert!(material.base_color_texture.is_none());
+
+        assert_eq!(scene.nodes.len(), 2);
+        assert_eq!((scene.nodes[1].name.as_str(), scene.nodes[1].meshes.as_slice()), ("child", &[0, 1][..]));
+
+        //Scaled by 2, turned 90 degrees around Z, then moved up by the parent
+        let transform = scene.nodes[1].transform();
+        assert!((transform.location() - Vector3::new(0.0, 2.0, 0.0)).magnitude() < 1e-5);
+        assert!((transform.scale() - Vector3::new(2.0, 2.0, 2.0)).magnitude() < 1e-5);
+
+        let flat = scene.flatten();
+        assert_eq!(flat.vertices.len(), 6);
+        assert!((flat.vertices[1].position() - Vector3::new(0.0, 4.0, 0.0)).magnitude() < 1e-5);
+        assert!((flat.vertices[1].normal() - Vector3::unit_z()).magnitude() < 1e-5);
+        assert_eq!(flat.vertices[0].color(), Vector4::new(1.0, 0.5, 0.0, 1.0));
+    }
+
+    #[test]
+    fn gltf_with_embedded_buffer_and_image_imports() {
+        let buffer_uri = format!("data:application/octet-stream;base64,{}", encode_base64(&triangle_buffer()));
+        let image = format!(r#"{{ "uri": "data:image/png;base64,{}" }}"#, encode_base64(&png_image()));
+        let document = triangle_document(Some(buffer_uri), &image);
+
+        assert_triangle_scene(&GltfScene::from_bytes(document.as_bytes(), Path::new(".")).unwrap());
+    }
+
+    #[test]
+    fn glb_with_buffer_view_image_imports() {
+        let image = r#"{ "bufferView": 3, "mimeType": "image/png" }"#;
+        let mut json = triangle_document(None, image).into_bytes();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut bin = triangle_buffer();
+        bin.resize(bin.len().next_multiple_of(4), 0);
+        json.resize(json.len().next_multiple_of(4), b' ');
+
+        let mut glb = Vec::new();
+        glb.extend(GLB_MAGIC.to_le_bytes());
+        glb.extend(2u32.to_le_bytes());
+        glb.extend(((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        glb.extend((json.len() as u32).to_le_bytes());
+        glb.extend(GLB_CHUNK_JSON.to_le_bytes());
+        glb.extend(json);
+        glb.extend((bin.len() as u32).to_le_bytes());
+        glb.extend(GLB_CHUNK_BIN.to_le_bytes());
+        glb.extend(bin);
+
+        assert_triangle_scene(&GltfScene::from_bytes(&glb, Path::new(".")).unwrap());
+    }
+
+    #[test]
+    fn gray_images_expand_to_rgba() {
+        let gray = ImageData { pixels: vec![10, 200], format: Format::R8G8, width: 1, height: 1 };
+        assert_eq!(texture(0, &gray).unwrap().pixels, vec![10, 10, 10, 200]);
+
+        let deep = ImageData { pixels: [0x1234u16, 0xABCD, 0xFFFF].iter().flat_map(|c| c.to_ne_bytes()).collect(), format: Format::R16G16B16, width: 1, height: 1 };
+        assert_eq!(texture(0, &deep).unwrap().pixels, vec![0x12, 0xAB, 0xFF, 0xFF]);
+    }
+}
@@ -0,0 +1,48 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use crate::utils::math::Vector4;
+
+//RGBA8 pixels with sRGB encoded colors, rows from top to bottom
+#[derive(Clone, PartialEq)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Texture {
+    //1x1 white, samples like no texture at all
+    pub fn white() -> Self {
+        Self { width: 1, height: 1, pixels: vec![u8::MAX; 4] }
+    }
+}
+
+impl Debug for Texture {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Texture({}x{})", self.width, self.height)
+    }
+}
+
+//Metallic-roughness parameters as in glTF. The base color multiplies the vertex colors and the texture
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    //Linear RGBA
+    pub base_color: Vector4,
+    pub metallic: f32,
+    pub roughness: f32,
+    //Shared by the materials using the same image
+    pub base_color_texture: Option<Arc<Texture>>,
+}
+
+impl Default for Material {
+    //Rough dielectric, the look of meshes that come without a material
+    fn default() -> Self {
+        Self {
+            base_color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+            metallic: 0.0,
+            roughness: 1.0,
+            base_color_texture: None,
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use cgmath::{vec3, vec4, InnerSpace};
+
+use crate::assets::material::Material;
+use crate::assets::vertex::Vertex;
+use crate::utils::math::{Vector3, Vector4, VECTOR3_BACKWARD, VECTOR3_DOWN, VECTOR3_FORWARD, VECTOR3_LEFT, VECTOR3_RIGHT, VECTOR3_UP};
+
+//Cube without normals with shared vertices
+// static VERTICES: [Vertex; 8] = [
+//     Vertex::new(Vector3::new(-1.0, -1.0, -1.0), Vector3::zero(),  Vector4::new(0.1, 0.1, 0.1, 1.0)), //0
+//     Vertex::new(Vector3::new(1.0, -1.0, -1.0), Vector3::zero(), Vector4::new(1.0, 0.0, 0.0, 1.0)), //1
+//     Vertex::new(Vector3::new(1.0, 1.0, -1.0), Vector3::zero(), Vector4::new(1.0, 1.0, 0.0, 1.0)), //2
+//
+//     Vertex::new(Vector3::new(-1.0, 1.0, -1.0), Vector3::zero(), Vector4::new(0.0, 1.0, 0.0, 1.0)), //3
+//     Vertex::new(Vector3::new(-1.0, -1.0, 1.0), Vector3::zero(), Vector4::new(0.0, 0.0, 1.0, 1.0)), //4
+//     Vertex::new(Vector3::new(1.0, -1.0, 1.0), Vector3::zero(), Vector4::new(1.0, 0.0, 1.0, 1.0)), //5
+//
+//     Vertex::new(Vector3::new(1.0, 1.0, 1.0), Vector3::zero(), Vector4::new(1.0, 1.0, 1.0, 1.0)),  //6
+//     Vertex::new(Vector3::new(-1.0, 1.0, 1.0), Vector3::zero(), Vector4::new(0.0, 1.0, 1.0, 1.0)), //7
+// ];
+//
+//
+// static INDICES: &[u16] = &[
+//     0, 1, 3, 3, 1, 2,
+//     1, 5, 2, 2, 5, 6,
+//     5, 4, 6, 6, 4, 7,
+//     4, 0, 7, 7, 0, 3,
+//     3, 2, 7, 7, 2, 6,
+//     4, 5, 0, 0, 5, 1
+// ];
+
+//Cube with per vertex normals
+static VERTICES: [Vertex; 24] = [
+    // Front face
+    Vertex::new(vec3(-1.0, -1.0,  1.0), VECTOR3_BACKWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-left
+    Vertex::new(vec3( 1.0, -1.0,  1.0), VECTOR3_BACKWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-right
+    Vertex::new(vec3( 1.0,  1.0,  1.0), VECTOR3_BACKWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-right
+    Vertex::new(vec3(-1.0,  1.0,  1.0), VECTOR3_BACKWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-left
+
+    // Back face
+    Vertex::new(vec3(-1.0, -1.0, -1.0), VECTOR3_FORWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-left
+    Vertex::new(vec3( 1.0, -1.0, -1.0), VECTOR3_FORWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-right
+    Vertex::new(vec3( 1.0,  1.0, -1.0), VECTOR3_FORWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-right
+    Vertex::new(vec3(-1.0,  1.0, -1.0), VECTOR3_FORWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-left
+
+    // Left face
+    Vertex::new(vec3(-1.0,  1.0,  1.0), VECTOR3_LEFT, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-left
+    Vertex::new(vec3(-1.0,  1.0, -1.0), VECTOR3_LEFT, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-right
+    Vertex::new(vec3(-1.0, -1.0, -1.0), VECTOR3_LEFT, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-right
+    Vertex::new(vec3(-1.0, -1.0,  1.0), VECTOR3_LEFT, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-left
+
+    // Right face
+    Vertex::new(vec3( 1.0,  1.0,  1.0), VECTOR3_RIGHT, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-left
+    Vertex::new(vec3( 1.0,  1.0, -1.0), VECTOR3_RIGHT, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-right
+    Vertex::new(vec3( 1.0, -1.0, -1.0), VECTOR3_RIGHT, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-right
+    Vertex::new(vec3( 1.0, -1.0,  1.0), VECTOR3_RIGHT, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-left
+
+    // Top face
+    Vertex::new(vec3(-1.0,  1.0, -1.0), VECTOR3_UP, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-left
+    Vertex::new(vec3( 1.0,  1.0, -1.0), VECTOR3_UP, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-right
+    Vertex::new(vec3( 1.0,  1.0,  1.0), VECTOR3_UP, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-right
+    Vertex::new(vec3(-1.0,  1.0,  1.0), VECTOR3_UP, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-left
+
+    // Bottom face
+    Vertex::new(vec3(-1.0, -1.0, -1.0), VECTOR3_DOWN, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-left
+    Vertex::new(vec3( 1.0, -1.0, -1.0), VECTOR3_DOWN, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-right
+    Vertex::new(vec3( 1.0, -1.0,  1.0), VECTOR3_DOWN, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-right
+    Vertex::new(vec3(-1.0, -1.0,  1.0), VECTOR3_DOWN, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-left
+];
+
+
+static INDICES: &[u16] = &[
+    // Front face
+    0,  1,  2,  2,  3,  0,
+
+    // Back face
+    4,  5,  6,  6,  7,  4,
+
+    // Left face
+    8,  9,  10, 10, 11, 8,
+
+    // Right face
+    12, 13, 14, 14, 15, 12,
+
+    // Top face
+    16, 17, 18, 18, 19, 16,
+
+    // Bottom face
+    20, 21, 22, 22, 23, 20,
+];
+
+
+//Triangle
+// static VERTICES: [Vertex; 3] = [
+//     Vertex::new(Vector3::new(-0.5, -0.5, 0.0), Vector4::new(0.0, 0.0, 1.0, 1.0)),
+//     Vertex::new(Vector3::new(0.0, 0.5, 0.0), Vector4::new(0.0, 1.0, 0.0, 1.0)),
+//     Vertex::new(Vector3::new(0.5, -0.5, 0.0), Vector4::new(1.0, 0.0, 0.0, 1.0)),
+// ];
+//
+// pub static INDICES: &[u16] = &[0, 1, 2];
+
+//Triangle list in CPU memory, independent of the renderer that draws it
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub material: Material,
+}
+
+impl Mesh {
+    //Built-in cube with per face normals, see VERTICES
+    pub fn cube() -> Self {
+        Self {
+            vertices: VERTICES.to_vec(),
+            indices: INDICES.iter().map(|&i| i as u32).collect(),
+            material: Material::default(),
+        }
+    }
+
+    //Unit sphere made by subdividing an icosahedron, every subdivision splits each triangle into four.
+    //Has 10 * 4^subdivisions + 2 vertices, normals point outwards
+    pub fn icosphere(subdivisions: u32, color: Vector4) -> Self {
+        let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+
+        let mut positions = [
+            (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+            (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+            (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0),
+        ]
+            .map(|(x, y, z)| Vector3::new(x, y, z).normalize())
+            .to_vec();
+
+        let mut triangles: Vec<[u32; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            //Edges are shared by two triangles, their midpoint is only added once
+            let mut midpoints = HashMap::<(u32, u32), u32>::new();
+            let mut midpoint = |a: u32, b: u32| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    positions.push((positions[a as usize] + positions[b as usize]).normalize());
+                    positions.len() as u32 - 1
+                })
+            };
+
+            triangles = triangles
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        Self {
+            vertices: positions.iter().map(|p| Vertex::new(*p, *p, color)).collect(),
+            indices: triangles.into_iter().flatten().collect(),
+            material: Material::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icosphere_is_closed_unit_sphere() {
+        for subdivisions in 0..4 {
+            let mesh = Mesh::icosphere(subdivisions, Vector4::new(1.0, 1.0, 1.0, 1.0));
+
+            assert_eq!(mesh.vertices.len(), 10 * 4usize.pow(subdivisions) + 2);
+            assert_eq!(mesh.indices.len(), 20 * 4usize.pow(subdivisions) * 3);
+            assert!(mesh.vertices.iter().all(|v| (v.position().magnitude() - 1.0).abs() < 1e-5 && v.position() == v.normal()));
+
+            //Euler characteristic of a sphere, V - E + F = 2 with every edge shared by two faces
+            let faces = mesh.indices.len() / 3;
+            assert_eq!(mesh.vertices.len() as i64 - (faces * 3 / 2) as i64 + faces as i64, 2);
+        }
+    }
+
+    #[test]
+    fn icosphere_faces_point_outwards() {
+        let mesh = Mesh::icosphere(2, Vector4::new(1.0, 1.0, 1.0, 1.0));
+
+        for triangle in mesh.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize].position());
+            assert!((b - a).cross(c - a).dot(a + b + c) > 0.0);
+        }
+    }
+}
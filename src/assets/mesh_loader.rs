@@ -0,0 +1,207 @@
+use std::path::Path;
+
+use cgmath::{InnerSpace, Zero};
+use log::warn;
+use thiserror::Error;
+use tobj::{LoadError, LoadOptions, Material, Model};
+
+use crate::assets::gltf_loader::{GltfError, GltfScene};
+use crate::assets::mesh::Mesh;
+use crate::assets::vertex::Vertex;
+use crate::utils::math::{Vector3, Vector4};
+
+//Used when the OBJ has neither vertex colors nor a material diffuse
+const DEFAULT_COLOR: Vector4 = Vector4::new(0.5, 0.5, 0.5, 1.0);
+
+#[derive(Debug, Error)]
+pub enum MeshLoadError {
+    #[error("Failed to load OBJ '{path}': {source}.")]
+    Obj { path: String, source: LoadError },
+    #[error("Failed to load glTF '{path}': {source}")]
+    Gltf { path: String, source: GltfError },
+    #[error("'{0}' contains no triangles.")]
+    Empty(String),
+    #[error("Unsupported model format '{0}', expected .obj, .gltf or .glb.")]
+    UnsupportedFormat(String),
+}
+
+//Faces are triangulated and every position/normal pair becomes one vertex
+const OBJ_LOAD_OPTIONS: LoadOptions = LoadOptions {
+    single_index: true,
+    triangulate: true,
+    ignore_points: true,
+    ignore_lines: true,
+};
+
+impl Mesh {
+    //Picks the loader from the file extension, glTF scenes are flattened into one mesh
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MeshLoadError> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+        let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+
+        match extension.as_deref() {
+            Some("obj") => Self::load_obj(path),
+            Some("gltf" | "glb") => {
+                let mesh = GltfScene::load(path)
+                    .map_err(|source| MeshLoadError::Gltf { path: name.clone(), source })?
+                    .flatten();
+
+                match mesh.indices.is_empty() {
+                    true => Err(MeshLoadError::Empty(name)),
+                    false => Ok(mesh),
+                }
+            }
+            _ => Err(MeshLoadError::UnsupportedFormat(name)),
+        }
+    }
+
+    //All models of the file are merged into one mesh. Materials are looked up next to the OBJ,
+    //a missing MTL file only loses the material colors
+    pub fn load_obj(path: impl AsRef<Path>) -> Result<Self, MeshLoadError> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+
+        let (models, materials) = tobj::load_obj(path, &OBJ_LOAD_OPTIONS)
+            .map_err(|source| MeshLoadError::Obj { path: name.clone(), source })?;
+
+        Self::from_obj_models(&name, &models, materials)
+    }
+
+    fn from_obj_models(name: &str, models: &[Model], materials: Result<Vec<Material>, LoadError>) -> Result<Self, MeshLoadError> {
+        let materials = materials.unwrap_or_else(|e| {
+            warn!("OBJ '{}' materials not loaded: {}", name, e);
+            vec![]
+        });
+
+        let mut result = Mesh::default();
+
+        for model in models {
+            let mesh = &model.mesh;
+            let vertex_count = mesh.positions.len() / 3;
+
+            let positions = mesh.positions
+                .chunks_exact(3)
+                .map(|p| Vector3::new(p[0], p[1], p[2]))
+                .collect::<Vec<_>>();
+
+            let normals = if mesh.normals.len() == mesh.positions.len() {
+                mesh.normals
+                    .chunks_exact(3)
+                    .map(|n| Vector3::new(n[0], n[1], n[2]))
+                    .collect()
+            } else {
+                smooth_normals(&positions, &mesh.indices)
+            };
+
+            let material_color = mesh.material_id
+                .and_then(|id| materials.get(id))
+                .and_then(|material| material.diffuse)
+                .map(|[r, g, b]| Vector4::new(r, g, b, 1.0))
+                .unwrap_or(DEFAULT_COLOR);
+
+            let colors = if mesh.vertex_color.len() == mesh.positions.len() {
+                mesh.vertex_color
+                    .chunks_exact(3)
+                    .map(|c| Vector4::new(c[0], c[1], c[2], 1.0))
+                    .collect()
+            } else {
+                vec![material_color; vertex_count]
+            };
+
+            let base_index = result.vertices.len() as u32;
+            result.vertices.extend((0..vertex_count).map(|i| Vertex::new(positions[i], normals[i], colors[i])));
+            result.indices.extend(mesh.indices.iter().map(|i| base_index + i));
+        }
+
+        if result.indices.is_empty() {
+            return Err(MeshLoadError::Empty(name.to_string()));
+        }
+
+        Ok(result)
+    }
+}
+
+//Area weighted average of the normals of the faces sharing each vertex, expects counter-clockwise faces
+pub(crate) fn smooth_normals(positions: &[Vector3], indices: &[u32]) -> Vec<Vector3> {
+    let mut normals = vec![Vector3::zero(); positions.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        //Length is twice the face area
+        let face_normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+
+        normals[a] += face_normal;
+        normals[b] += face_normal;
+        normals[c] += face_normal;
+    }
+
+    normals
+        .into_iter()
+        .map(|n| if n.magnitude2() > 0.0 { n.normalize() } else { n })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //load_obj for an OBJ in memory, `mtl` is used for any mtllib it references
+    fn load_obj_buf(name: &str, obj: &str, mtl: Option<&str>) -> Result<Mesh, MeshLoadError> {
+        let (models, materials) = tobj::load_obj_buf(&mut obj.as_bytes(), &OBJ_LOAD_OPTIONS, |_| match mtl {
+            Some(mtl) => tobj::load_mtl_buf(&mut mtl.as_bytes()),
+            None => Err(LoadError::OpenFileFailed),
+        })
+            .map_err(|source| MeshLoadError::Obj { path: name.to_string(), source })?;
+
+        Mesh::from_obj_models(name, &models, materials)
+    }
+
+    //Unit quad split into two triangles, in the XY plane facing +Z
+    const QUAD: &str = "
+mtllib quad.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+usemtl red
+f 1 2 3 4
+";
+
+    const QUAD_MTL: &str = "
+newmtl red
+Kd 1.0 0.0 0.0
+";
+
+    #[test]
+    fn obj_without_normals_gets_smooth_normals() {
+        let mesh = load_obj_buf("quad", QUAD, Some(QUAD_MTL)).unwrap();
+
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices.len(), 6);
+        assert!(mesh.vertices.iter().all(|v| (v.normal() - Vector3::unit_z()).magnitude() < 1e-6));
+        assert!(mesh.vertices.iter().all(|v| v.color() == Vector4::new(1.0, 0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn obj_keeps_normals_and_vertex_colors() {
+        let obj = "
+v 0 0 0 0 1 0
+v 1 0 0 0 1 0
+v 0 1 0 0 1 0
+vn 0 0 -1
+f 1//1 2//1 3//1
+";
+        //Missing MTL only loses the material colors
+        let mesh = load_obj_buf("triangle", obj, None).unwrap();
+
+        assert_eq!(mesh.vertices.len(), 3);
+        assert!(mesh.vertices.iter().all(|v| v.normal() == -Vector3::unit_z()));
+        assert!(mesh.vertices.iter().all(|v| v.color() == Vector4::new(0.0, 1.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn obj_without_faces_is_rejected() {
+        assert!(matches!(load_obj_buf("points", "v 0 0 0\n", None), Err(MeshLoadError::Empty(_))));
+    }
+}
@@ -0,0 +1,43 @@
+use crate::utils::math::{Vector2, Vector3, Vector4};
+
+//Positions and normals are padded to Vector4s to match the shader side layout
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Vertex {
+    position: Vector4,
+    normal: Vector4,
+    color: Vector4,
+    tex_coord: Vector2,
+}
+
+impl Vertex {
+    pub const fn new(pos: Vector3, normal: Vector3, color: Vector4) -> Self {
+        Self {
+            position: Vector4::new(pos.x, pos.y, pos.z, 1.0),
+            normal: Vector4::new(normal.x, normal.y, normal.z, 0.0),
+            color,
+            tex_coord: Vector2::new(0.0, 0.0),
+        }
+    }
+
+    pub fn with_tex_coord(mut self, tex_coord: Vector2) -> Self {
+        self.tex_coord = tex_coord;
+        self
+    }
+
+    pub fn position(&self) -> Vector3 {
+        self.position.truncate()
+    }
+
+    pub fn normal(&self) -> Vector3 {
+        self.normal.truncate()
+    }
+
+    pub fn color(&self) -> Vector4 {
+        self.color
+    }
+
+    pub fn tex_coord(&self) -> Vector2 {
+        self.tex_coord
+    }
+}
@@ -1,2 +1,3 @@
+#[allow(clippy::module_inception)]
 pub mod camera;
 pub mod orbit_camera;
\ No newline at end of file
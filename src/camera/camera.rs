@@ -8,7 +8,7 @@ pub struct ViewSettings {
     pub fov: f32
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Camera {
     view_settings: ViewSettings,
     transform: Transform
@@ -34,10 +34,21 @@ impl OwnedTransform for Camera {
 }
 
 impl Camera {
+    pub fn new(view_settings: ViewSettings, transform: Transform) -> Self {
+        Self {
+            view_settings,
+            transform
+        }
+    }
+
     pub fn view(&self) -> ViewSettings {
         self.view_settings
     }
 
+    pub fn set_view(&mut self, view_settings: ViewSettings) {
+        self.view_settings = view_settings;
+    }
+
     pub fn view_matrix(&self) -> Matrix4x4 {
         let t = Matrix4x4::from_translation(-self.transform.location());
         let r = self.transform.matrix_r();
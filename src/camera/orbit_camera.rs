@@ -1,30 +1,46 @@
-use crate::camera::camera::Camera;
+use crate::camera::camera::{Camera, ViewSettings};
 use crate::controls::controls::Controls;
 use crate::utils::math::{Vector2, Vector3, Zero};
-use crate::world::game_object::GameObject;
-use crate::world::transform::OwnedTransform;
+use crate::world::transform::Transform;
 use cgmath::InnerSpace;
-use log::debug;
 use winit::event::ElementState;
 use winit::keyboard::KeyCode;
 
+//Moves the Transform of its entity, see World::active_camera
 #[derive(Debug)]
 pub struct OrbitCamera {
     move_speed: f32,
     rotate_speed: f32,
     speed_modifier: f32,
-    camera: Box<Camera>,
+    view_settings: ViewSettings,
     current_input: Vector3,
     current_angular_input: Vector3
 }
 
 impl OrbitCamera {
-    pub fn camera(&self) -> &Camera {
-        self.camera.as_ref()
+    pub fn view(&self) -> ViewSettings {
+        self.view_settings
     }
 
-    pub fn camera_mut(&mut self) -> &mut Camera {
-        self.camera.as_mut()
+    pub fn set_view(&mut self, view_settings: ViewSettings) {
+        self.view_settings = view_settings;
+    }
+
+    pub fn move_speed(&self) -> f32 {
+        self.move_speed
+    }
+
+    pub fn set_move_speed(&mut self, move_speed: f32) {
+        self.move_speed = move_speed;
+    }
+
+    //Degrees per second at full input
+    pub fn rotate_speed(&self) -> f32 {
+        self.rotate_speed
+    }
+
+    pub fn set_rotate_speed(&mut self, rotate_speed: f32) {
+        self.rotate_speed = rotate_speed;
     }
 
     //ToDo: Add more abstract input handling elsewhere
@@ -95,6 +111,28 @@ impl OrbitCamera {
         let (x, y) = (delta.0.clamp(-1.0, 1.0), delta.1.clamp(-1.0, 1.0));
         self.add_angular_input_2d(Vector2::new(y as f32, x as f32));
     }
+
+    //Moves and turns `transform` by the input since the last update
+    pub fn update(&mut self, transform: &mut Transform, delta_time: f32) {
+        if self.current_input.magnitude2() > 0.0 {
+            let mut location = transform.location();
+            location += transform.transform_vector(self.current_input) * self.move_speed * self.speed_modifier * delta_time;
+
+            transform.set_location(location);
+        }
+
+        if self.current_angular_input.magnitude2() > 0.0 {
+            let rotation = self.current_angular_input * self.rotate_speed * delta_time;
+            transform.rotate_vec(rotation);
+
+            self.current_angular_input = Vector3::zero();
+        }
+    }
+
+    //Whether the next update changes the transform
+    pub fn has_input(&self) -> bool {
+        self.current_input.magnitude2() > 0.0 || self.current_angular_input.magnitude2() > 0.0
+    }
 }
 
 impl Default for OrbitCamera {
@@ -103,7 +141,7 @@ impl Default for OrbitCamera {
             move_speed: 10.0,
             rotate_speed: 90.0,
             speed_modifier: 1.0,
-            camera: Box::new(Camera::default()),
+            view_settings: Camera::default().view(),
             current_input: Vector3::zero(),
             current_angular_input: Vector3::zero()
         }
@@ -135,21 +173,3 @@ impl Controls for OrbitCamera {
         self.add_angular_input(input.extend(0.0))
     }
 }
-
-impl GameObject for OrbitCamera {
-    fn update(&mut self, delta_time: f32) {
-        if self.current_input.magnitude2() > 0.0 {
-            let mut location = self.camera.transform().location();
-            location += self.camera.transform().inverse_transform_vector(self.current_input) * self.move_speed * self.speed_modifier * delta_time;
-
-            self.camera_mut().transform_mut().set_location(location);
-        }
-
-        if self.current_angular_input.magnitude2() > 0.0 {
-            let rotation = self.current_angular_input * self.rotate_speed * delta_time;
-            self.camera_mut().transform_mut().rotate_vec(rotation);
-
-            self.current_angular_input = Vector3::zero();
-        }
-    }
-}
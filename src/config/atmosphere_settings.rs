@@ -0,0 +1,370 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Result;
+use cgmath::Zero;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::graphics::vulkan::atmopsheric_scattering::{AtmosphereSampleData, ScatteringMedium};
+use crate::graphics::vulkan::spectral::{rayleigh_coefficient, Spectrum, AIR_DEPOLARIZATION_RATIO};
+use crate::utils::math::{Vector3, Vector4};
+
+pub const ATMOSPHERE_PRESETS_PATH: &str = "./resources/config/atmosphere_presets.json";
+pub const DEFAULT_ATMOSPHERE_PRESET: &str = "earth";
+//Ray march steps per pixel and per transmittance texel, higher counts risk a GPU timeout
+pub const MAX_SAMPLE_COUNT: u32 = 512;
+pub const MAX_SAMPLE_COUNT_LIGHT: u32 = 128;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum AtmosphereSettingsError {
+    #[error("Unknown atmosphere preset '{name}', available: {available}.")]
+    UnknownPreset { name: String, available: String },
+    #[error("{field} must be positive, got {value}.")]
+    NotPositive { field: &'static str, value: f32 },
+    #[error("{field} must be at most {max}, got {value}.")]
+    TooLarge { field: &'static str, value: u32, max: u32 },
+    #[error("{field} must not be negative, got {value:?}.")]
+    Negative { field: &'static str, value: [f32; 3] },
+    #[error("Refractive index must be greater than 1, got {0}.")]
+    RefractiveIndex(f64),
+    #[error("Mie asymmetry must be in (-1, 1), got {0}.")]
+    MieAsymmetry(f32),
+    #[error("Ozone layer centered at {center} lies outside the atmosphere (thickness {thickness}).")]
+    OzoneOutsideAtmosphere { center: f32, thickness: f32 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RayleighSettings {
+    pub scale_height: f32,
+    pub scattering: RayleighScattering,
+}
+
+//Either RGB coefficients in 1/sky unit or the physical properties of the gas they are derived from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum RayleighScattering {
+    Rgb([f32; 3]),
+    Physical(PhysicalRayleigh),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PhysicalRayleigh {
+    pub refractive_index: f64,
+    //Molecules per m^3 at the surface
+    pub molecular_density: f64,
+    #[serde(default = "default_depolarization_ratio")]
+    pub depolarization_ratio: f64,
+    #[serde(default)]
+    pub spectrum: Spectrum,
+    //Converts 1/m to 1/sky unit, Earth's radius of 6371km is 6.371 sky units
+    #[serde(default = "default_meters_per_unit")]
+    pub meters_per_unit: f64,
+    //Scales the result so the largest channel has this value instead, the RGB presets are normalized to 1
+    #[serde(default)]
+    pub normalize_to: Option<f32>,
+}
+
+fn default_depolarization_ratio() -> f64 {
+    AIR_DEPOLARIZATION_RATIO
+}
+
+fn default_meters_per_unit() -> f64 {
+    1e6
+}
+
+impl RayleighScattering {
+    //Coefficients in 1/sky unit
+    pub fn rgb(&self) -> Vector3 {
+        match self {
+            RayleighScattering::Rgb(rgb) => Vector3::from(*rgb),
+            RayleighScattering::Physical(physical) => {
+                let rgb = physical.spectrum.rgb(|wavelength| {
+                    rayleigh_coefficient(physical.refractive_index, physical.molecular_density, physical.depolarization_ratio, wavelength)
+                        * physical.meters_per_unit
+                });
+
+                match physical.normalize_to {
+                    Some(max) => rgb * (max / rgb.x.max(rgb.y).max(rgb.z)),
+                    None => rgb
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MieSettings {
+    pub scale_height: f32,
+    pub scattering: [f32; 3],
+    //Extinction minus scattering, Earth's aerosols absorb about a tenth of what they scatter
+    pub absorption: [f32; 3],
+    //Phase asymmetry, -1 (back scattering) to 1 (forward scattering)
+    pub g: f32,
+}
+
+//Tent profile, absorption only
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct OzoneSettings {
+    pub center: f32,
+    pub width: f32,
+    pub absorption: [f32; 3],
+}
+
+//Planet and medium description in sky units (see unit_scale), light direction is set by the world
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AtmosphereSettings {
+    pub planet_radius: f32,
+    pub atmosphere_thickness: f32,
+    pub unit_scale: f32,
+    pub sample_count: u32,
+    pub sample_count_light: u32,
+    pub light_intensity: f32,
+
+    pub rayleigh: RayleighSettings,
+    #[serde(default)]
+    pub mie: Option<MieSettings>,
+    #[serde(default)]
+    pub ozone: Option<OzoneSettings>,
+}
+
+impl AtmosphereSettings {
+    //Picks a preset by name out of a JSON map of presets and validates it
+    pub fn from_presets_json(json: &str, name: &str) -> Result<Self> {
+        let mut presets = serde_json::from_str::<BTreeMap<String, AtmosphereSettings>>(json)?;
+
+        let settings = presets.remove(name).ok_or_else(|| AtmosphereSettingsError::UnknownPreset {
+            name: name.to_string(),
+            available: presets.keys().cloned().collect::<Vec<_>>().join(", ")
+        })?;
+
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    pub fn validate(&self) -> Result<(), AtmosphereSettingsError> {
+        positive("planet_radius", self.planet_radius)?;
+        positive("atmosphere_thickness", self.atmosphere_thickness)?;
+        positive("unit_scale", self.unit_scale)?;
+        positive("sample_count", self.sample_count as f32)?;
+        positive("sample_count_light", self.sample_count_light as f32)?;
+        at_most("sample_count", self.sample_count, MAX_SAMPLE_COUNT)?;
+        at_most("sample_count_light", self.sample_count_light, MAX_SAMPLE_COUNT_LIGHT)?;
+        positive("light_intensity", self.light_intensity)?;
+
+        positive("rayleigh.scale_height", self.rayleigh.scale_height)?;
+        if let RayleighScattering::Physical(physical) = &self.rayleigh.scattering {
+            if physical.refractive_index.is_nan() || physical.refractive_index <= 1.0 {
+                return Err(AtmosphereSettingsError::RefractiveIndex(physical.refractive_index));
+            }
+
+            positive("rayleigh.molecular_density", physical.molecular_density as f32)?;
+            positive("rayleigh.meters_per_unit", physical.meters_per_unit as f32)?;
+            if let Some(normalize_to) = physical.normalize_to {
+                positive("rayleigh.normalize_to", normalize_to)?;
+            }
+        }
+        non_negative("rayleigh.scattering", self.rayleigh.scattering.rgb().into())?;
+
+        if let Some(mie) = &self.mie {
+            positive("mie.scale_height", mie.scale_height)?;
+            non_negative("mie.scattering", mie.scattering)?;
+            non_negative("mie.absorption", mie.absorption)?;
+
+            //Cornette-Shanks is singular at |g| = 1
+            if mie.g.is_nan() || mie.g.abs() >= 1.0 {
+                return Err(AtmosphereSettingsError::MieAsymmetry(mie.g));
+            }
+        }
+
+        if let Some(ozone) = &self.ozone {
+            positive("ozone.width", ozone.width)?;
+            non_negative("ozone.absorption", ozone.absorption)?;
+
+            if ozone.center.is_nan() || ozone.center < 0.0 || ozone.center > self.atmosphere_thickness {
+                return Err(AtmosphereSettingsError::OzoneOutsideAtmosphere {
+                    center: ozone.center,
+                    thickness: self.atmosphere_thickness
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    //Light direction and intensity are left at zero, see AtmosphereSampleData::with_light
+    pub fn sample_data(&self) -> AtmosphereSampleData {
+        AtmosphereSampleData {
+            planet_pos: Vector4::zero(),
+            planet_radius: self.planet_radius,
+            atmosphere_thickness: self.atmosphere_thickness,
+            sample_count: self.sample_count as f32,
+            sample_count_light: self.sample_count_light as f32,
+            unit_scale: self.unit_scale,
+            light_dir: Vector4::zero(),
+            light_intensity: Vector4::zero(),
+
+            pad: [0.0, 0.0, 0.0]
+        }
+    }
+
+    pub fn medium(&self) -> ScatteringMedium {
+        let mut medium = ScatteringMedium::new(self.rayleigh.scale_height, self.rayleigh.scattering.rgb());
+
+        if let Some(mie) = &self.mie {
+            medium = medium.with_mie(mie.scale_height, Vector3::from(mie.scattering), Vector3::from(mie.absorption), mie.g);
+        }
+
+        if let Some(ozone) = &self.ozone {
+            medium = medium.with_ozone(ozone.center, ozone.width, Vector3::from(ozone.absorption));
+        }
+
+        medium
+    }
+}
+
+//NaN and infinity are rejected as well
+fn positive(field: &'static str, value: f32) -> Result<(), AtmosphereSettingsError> {
+    if !value.is_finite() || value <= 0.0 {
+        return Err(AtmosphereSettingsError::NotPositive { field, value });
+    }
+
+    Ok(())
+}
+
+fn at_most(field: &'static str, value: u32, max: u32) -> Result<(), AtmosphereSettingsError> {
+    if value > max {
+        return Err(AtmosphereSettingsError::TooLarge { field, value, max });
+    }
+
+    Ok(())
+}
+
+fn non_negative(field: &'static str, value: [f32; 3]) -> Result<(), AtmosphereSettingsError> {
+    if value.iter().any(|v| !v.is_finite() || *v < 0.0) {
+        return Err(AtmosphereSettingsError::Negative { field, value });
+    }
+
+    Ok(())
+}
+
+pub async fn load_atmosphere_settings(preset: &str) -> Result<AtmosphereSettings> {
+    let presets_json = tokio::fs::read_to_string(Path::new(ATMOSPHERE_PRESETS_PATH)).await?;
+    AtmosphereSettings::from_presets_json(&presets_json, preset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRESETS: &str = include_str!("../../resources/config/atmosphere_presets.json");
+
+    fn earth() -> AtmosphereSettings {
+        AtmosphereSettings::from_presets_json(PRESETS, "earth").unwrap()
+    }
+
+    #[test]
+    fn bundled_presets_are_valid() {
+        for name in ["earth", "hazy_earth", "mars", "alien"] {
+            AtmosphereSettings::from_presets_json(PRESETS, name).unwrap();
+        }
+    }
+
+    #[test]
+    fn unknown_preset_lists_available() {
+        let error = AtmosphereSettings::from_presets_json(PRESETS, "venus").unwrap_err();
+        let error = error.downcast::<AtmosphereSettingsError>().unwrap();
+
+        match error {
+            AtmosphereSettingsError::UnknownPreset { name, available } => {
+                assert_eq!(name, "venus");
+                assert!(available.contains("earth") && available.contains("mars"));
+            },
+            _ => panic!("Unexpected error {:?}", error)
+        }
+    }
+
+    #[test]
+    fn earth_preset_matches_medium() {
+        let expected = ScatteringMedium::new(0.2, Vector3::new(0.175287, 0.409607, 1.0))
+            .with_mie(0.03, Vector3::new(0.12, 0.12, 0.12), Vector3::new(0.013, 0.013, 0.013), 0.8)
+            .with_ozone(0.025, 0.03, Vector3::new(0.149, 0.431, 0.0195));
+
+        let light_dir = Vector3::new(0.0, 1.0, 0.0);
+        assert_eq!(earth().medium(), expected);
+        assert_eq!(earth().sample_data().with_light(light_dir, Vector3::new(100.0, 100.0, 100.0)), AtmosphereSampleData::earth(light_dir, 100.0));
+    }
+
+    #[test]
+    fn earth_mie_absorption_excludes_scattering() {
+        let mie = earth().mie.unwrap();
+        let extinction = mie.scattering[0] + mie.absorption[0];
+
+        assert!((extinction - 0.133).abs() < 1e-6, "Extinction {}", extinction);
+        assert!((mie.scattering[0] / extinction - 0.9).abs() < 0.01, "Single scattering albedo {}", mie.scattering[0] / extinction);
+    }
+
+    fn physical_air() -> PhysicalRayleigh {
+        serde_json::from_str(r#"{ "refractive_index": 1.0003, "molecular_density": 2.545e25, "normalize_to": 1.0 }"#).unwrap()
+    }
+
+    #[test]
+    fn physical_rayleigh_matches_earth_preset() {
+        let physical = RayleighScattering::Physical(physical_air()).rgb();
+        let preset = earth().rayleigh.scattering.rgb();
+
+        assert!((physical - preset).x.abs() < 1e-4 && (physical - preset).y.abs() < 1e-4 && (physical - preset).z.abs() < 1e-4,
+            "{:?} != {:?}", physical, preset);
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let preset = serde_json::to_value(earth()).unwrap();
+
+        for path in [&[][..], &["mie"][..], &["ozone"][..], &["rayleigh"][..]] {
+            let mut json = preset.clone();
+            let object = path.iter().fold(&mut json, |value, key| &mut value[*key]);
+            object.as_object_mut().unwrap().insert("absorbtion".to_string(), serde_json::json!([0.0, 0.0, 0.0]));
+
+            assert!(serde_json::from_value::<AtmosphereSettings>(json).is_err(), "Typo accepted in {:?}", path);
+        }
+
+        let physical = r#"{ "refractive_index": 1.0003, "molecular_density": 2.545e25, "normalise_to": 1.0 }"#;
+        assert!(serde_json::from_str::<PhysicalRayleigh>(physical).is_err());
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let mut settings = earth();
+        settings.planet_radius = f32::NAN;
+        assert!(matches!(settings.validate(), Err(AtmosphereSettingsError::NotPositive { field: "planet_radius", .. })));
+
+        let mut settings = earth();
+        settings.rayleigh.scattering = RayleighScattering::Rgb([0.2, -0.1, 1.0]);
+        assert!(matches!(settings.validate(), Err(AtmosphereSettingsError::Negative { field: "rayleigh.scattering", .. })));
+
+        let mut settings = earth();
+        settings.rayleigh.scattering = RayleighScattering::Physical(physical_air());
+        if let RayleighScattering::Physical(physical) = &mut settings.rayleigh.scattering {
+            physical.refractive_index = 0.9;
+        }
+        assert_eq!(settings.validate(), Err(AtmosphereSettingsError::RefractiveIndex(0.9)));
+
+        let mut settings = earth();
+        settings.mie.as_mut().unwrap().g = 1.0;
+        assert_eq!(settings.validate(), Err(AtmosphereSettingsError::MieAsymmetry(1.0)));
+
+        let mut settings = earth();
+        settings.ozone.as_mut().unwrap().center = 0.5;
+        assert!(matches!(settings.validate(), Err(AtmosphereSettingsError::OzoneOutsideAtmosphere { .. })));
+
+        let mut settings = earth();
+        settings.sample_count = MAX_SAMPLE_COUNT + 1;
+        assert!(matches!(settings.validate(), Err(AtmosphereSettingsError::TooLarge { field: "sample_count", .. })));
+    }
+}
@@ -1,9 +1,11 @@
 use anyhow::Result;
 use std::collections::HashMap;
 use std::path::Path;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
+use thiserror::Error;
 
 #[derive(Default, Serialize, Deserialize, Debug, Hash, Eq, PartialEq)]
 #[serde(rename_all(serialize="lowercase", deserialize="lowercase"))]
@@ -22,20 +24,249 @@ pub enum LogLevel {
     Error = 4
 }
 
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all(serialize="snake_case", deserialize="snake_case"))]
+pub enum Exposure {
+    Ev100(f32),
+    //Aperture as f-number, shutter speed in seconds
+    Camera { aperture: f32, shutter_speed: f32, iso: f32 },
+    //Eye adaptation from the luminance histogram of the frame, EV100 is clamped to [min_ev100, max_ev100]
+    //and approaches the frame average at adaptation_rate per second
+    Auto {
+        min_ev100: f32,
+        max_ev100: f32,
+        adaptation_rate: f32,
+        #[serde(default)]
+        compensation: f32
+    },
+}
+
+impl Default for Exposure {
+    fn default() -> Self {
+        Exposure::Ev100(0.0)
+    }
+}
+
+impl Exposure {
+    //None for Auto, which is computed on the GPU
+    pub fn ev100(&self) -> Option<f32> {
+        match *self {
+            Exposure::Ev100(ev100) => Some(ev100),
+            Exposure::Camera { aperture, shutter_speed, iso } => Some((aperture * aperture / shutter_speed * 100.0 / iso).log2()),
+            Exposure::Auto { .. } => None
+        }
+    }
+
+    //Scale applied to scene radiance, Lagarde & de Rousiers 2014, "Moving Frostbite to PBR", saturation based sensitivity
+    pub fn multiplier(&self) -> Option<f32> {
+        self.ev100().map(|ev100| 1.0 / (1.2 * ev100.exp2()))
+    }
+}
+
+//Order matches the TONEMAPPER_ defines in post_process_frag.hlsl
+#[derive(Default, Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all(serialize="lowercase", deserialize="lowercase"))]
+#[repr(u32)]
+pub enum Tonemapper {
+    #[default]
+    Aces = 0,
+    Reinhard = 1,
+    Agx = 2,
+    //Clamp only
+    Linear = 3
+}
+
+#[derive(Default, Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct PostProcessConfig {
+    #[serde(default)]
+    pub exposure: Exposure,
+    #[serde(default)]
+    pub tonemapper: Tonemapper,
+}
+
 #[derive(Default, Serialize, Deserialize, Debug, Copy, Clone)]
 pub struct GraphicsConfig {
     pub log_level: LogLevel,
     pub validation_enabled: bool,
+    #[serde(default)]
+    pub post_process: PostProcessConfig,
+}
+
+//Renders the given number of frames offscreen, without creating a window
+#[derive(Default, Serialize, Deserialize, Debug, Clone)]
+pub struct HeadlessConfig {
+    pub width: u32,
+    pub height: u32,
+    pub frames: u32,
+    //Last frame is written here as PNG when set
+    #[serde(default)]
+    pub capture_path: Option<String>,
+}
+
+//Drives the sun from a simulated clock, see world::time_of_day
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimeOfDayConfig {
+    //Degrees, north and east positive
+    pub latitude: f64,
+    pub longitude: f64,
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    //Local time in hours, e.g. 13.5 for 13:30
+    pub hour: f64,
+    //Hours ahead of UTC
+    #[serde(default)]
+    pub utc_offset: f64,
+    //Simulated seconds per real second
+    #[serde(default = "default_time_scale")]
+    pub time_scale: f64,
+}
+
+fn default_time_scale() -> f64 {
+    1.0
+}
+
+impl Default for TimeOfDayConfig {
+    //Greenwich, noon of the 2024 June solstice
+    fn default() -> Self {
+        Self {
+            latitude: 51.4769,
+            longitude: 0.0,
+            year: 2024,
+            month: 6,
+            day: 21,
+            hour: 12.0,
+            utc_offset: 0.0,
+            time_scale: default_time_scale()
+        }
+    }
+}
+
+//Fixed timestep of the World's simulation, rendering blends between ticks
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct SimulationConfig {
+    //Ticks per second
+    #[serde(default = "default_tick_rate")]
+    pub tick_rate: f64,
+    //Frames slower than this many ticks run the simulation slower instead of catching up
+    #[serde(default = "default_max_ticks_per_frame")]
+    pub max_ticks_per_frame: u32,
+}
+
+fn default_tick_rate() -> f64 {
+    60.0
+}
+
+fn default_max_ticks_per_frame() -> u32 {
+    8
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self { tick_rate: default_tick_rate(), max_ticks_per_frame: default_max_ticks_per_frame() }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SimulationConfigError {
+    #[error("simulation.tick_rate must be positive, got {0}.")]
+    NotPositive(f64),
+    //Ticks are counted in whole nanoseconds, faster rates would round to 0
+    #[error("simulation.tick_rate {0} is out of range, ticks must last between a nanosecond and {max} seconds.", max = Duration::MAX.as_secs())]
+    OutOfRange(f64),
+}
+
+impl SimulationConfig {
+    //Length of one tick
+    pub fn tick(&self) -> Result<Duration, SimulationConfigError> {
+        if self.tick_rate.is_nan() || self.tick_rate <= 0.0 {
+            return Err(SimulationConfigError::NotPositive(self.tick_rate));
+        }
+
+        Duration::try_from_secs_f64(1.0 / self.tick_rate)
+            .ok()
+            .filter(|tick| !tick.is_zero())
+            .ok_or(SimulationConfigError::OutOfRange(self.tick_rate))
+    }
+
+    pub fn validate(&self) -> Result<(), SimulationConfigError> {
+        self.tick().map(|_| ())
+    }
 }
 
 #[derive(Default, Serialize, Deserialize, Debug)]
 pub struct Config {
-    pub graphics: HashMap<GraphicsApiType, GraphicsConfig>
+    pub graphics: HashMap<GraphicsApiType, GraphicsConfig>,
+    #[serde(default)]
+    pub headless: Option<HeadlessConfig>,
+    //Name of an entry in atmosphere_presets.json, Earth when unset
+    #[serde(default)]
+    pub atmosphere_preset: Option<String>,
+    //OBJ file replacing the built-in cube, or a glTF scene spawned as entities
+    #[serde(default)]
+    pub model: Option<String>,
+    //Scene file the World is loaded from, see world::scene. F5 saves the World back to it
+    #[serde(default)]
+    pub scene: Option<String>,
+    #[serde(default)]
+    pub simulation: SimulationConfig,
+    //Sun stays where the World puts it when unset
+    #[serde(default)]
+    pub time_of_day: Option<TimeOfDayConfig>
 }
 
 pub async fn load_config() -> Result<Config> {
     let path = Path::new("./resources/config/default_config.json");
 
     let config_json = tokio::fs::read_to_string(path).await?;
-    Ok(serde_json::from_str::<Config>(&config_json)?)
+    let config = serde_json::from_str::<Config>(&config_json)?;
+
+    config.simulation.validate()?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_exposure_matches_sunny_16() {
+        //f/16, 1/100s, ISO 100 is the classic EV 15 daylight exposure
+        let exposure = Exposure::Camera { aperture: 16.0, shutter_speed: 0.01, iso: 100.0 };
+        assert!((exposure.ev100().unwrap() - 14.64).abs() < 0.01);
+
+        assert!((Exposure::Ev100(0.0).multiplier().unwrap() - 1.0 / 1.2).abs() < 1e-6);
+        assert!((Exposure::Ev100(1.0).multiplier().unwrap() * 2.0 - Exposure::Ev100(0.0).multiplier().unwrap()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn post_process_config_parses() {
+        let config = serde_json::from_str::<PostProcessConfig>(r#"{ "exposure": { "camera": { "aperture": 2.8, "shutter_speed": 0.5, "iso": 400 } }, "tonemapper": "agx" }"#).unwrap();
+
+        assert_eq!(config.tonemapper, Tonemapper::Agx);
+        assert_eq!(config.exposure, Exposure::Camera { aperture: 2.8, shutter_speed: 0.5, iso: 400.0 });
+        assert_eq!(serde_json::from_str::<PostProcessConfig>("{}").unwrap(), PostProcessConfig::default());
+    }
+
+    #[test]
+    fn invalid_tick_rates_are_rejected() {
+        let simulation = |tick_rate| SimulationConfig { tick_rate, ..Default::default() };
+
+        assert_eq!(simulation(60.0).validate(), Ok(()));
+        assert_eq!(simulation(1e9).tick(), Ok(Duration::from_nanos(1)));
+        for tick_rate in [0.0, -60.0, f64::NAN, f64::NEG_INFINITY] {
+            assert!(matches!(simulation(tick_rate).validate(), Err(SimulationConfigError::NotPositive(_))), "{}", tick_rate);
+        }
+        for tick_rate in [1e10, f64::INFINITY, 1e-300] {
+            assert_eq!(simulation(tick_rate).validate(), Err(SimulationConfigError::OutOfRange(tick_rate)));
+        }
+    }
+
+    #[test]
+    fn auto_exposure_parses() {
+        let config = serde_json::from_str::<PostProcessConfig>(r#"{ "exposure": { "auto": { "min_ev100": -4.0, "max_ev100": 16.0, "adaptation_rate": 1.5 } } }"#).unwrap();
+
+        assert_eq!(config.exposure, Exposure::Auto { min_ev100: -4.0, max_ev100: 16.0, adaptation_rate: 1.5, compensation: 0.0 });
+        assert_eq!(config.exposure.multiplier(), None);
+    }
 }
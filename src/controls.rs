@@ -1 +1,3 @@
-pub mod controls;
\ No newline at end of file
+#[allow(clippy::module_inception)]
+pub mod controls;
+pub mod atmosphere_controls;
\ No newline at end of file
@@ -0,0 +1,180 @@
+use log::info;
+use winit::event::ElementState;
+use winit::keyboard::KeyCode;
+
+use crate::config::atmosphere_settings::{AtmosphereSettings, MAX_SAMPLE_COUNT, MAX_SAMPLE_COUNT_LIGHT};
+use crate::graphics::vulkan::atmopsheric_scattering::{AtmosphereSampleData, ScatteringMedium};
+use crate::world::directional_light::DirectionalLight;
+
+//Azimuth is lost straight overhead, so elevation stops just short of it
+const SUN_ELEVATION_LIMIT: f32 = 89.0;
+const SUN_ELEVATION_STEP: f32 = 1.0;
+const SUN_AZIMUTH_STEP: f32 = 5.0;
+const SCALE_HEIGHT_STEP: f32 = 0.01;
+const SAMPLE_COUNT_STEP: u32 = 5;
+const LIGHT_INTENSITY_FACTOR: f32 = 1.1;
+
+//Key bindings for tuning the atmosphere and the sun at runtime, values are logged after every change:
+//  Arrow Up/Down      sun elevation
+//  Arrow Left/Right   sun azimuth
+//  ] / [              Rayleigh scale height
+//  = / -              view sample count
+//  . / ,              light sample count
+//  PageUp/PageDown    light intensity
+//  F1                 print the current values
+#[derive(Debug)]
+pub struct AtmosphereControls {
+    settings: AtmosphereSettings
+}
+
+impl AtmosphereControls {
+    pub fn new(settings: AtmosphereSettings) -> Self {
+        Self {
+            settings
+        }
+    }
+
+    pub fn settings(&self) -> &AtmosphereSettings {
+        &self.settings
+    }
+
+    pub fn sample_data(&self) -> AtmosphereSampleData {
+        self.settings.sample_data()
+    }
+
+    pub fn medium(&self) -> ScatteringMedium {
+        self.settings.medium()
+    }
+
+    //The sun is read by the renderer every frame. Returns true when the atmosphere settings changed
+    //and need to be pushed to the renderer
+    pub fn handle_input_key(&mut self, key_code: KeyCode, state: ElementState, sun: &mut DirectionalLight) -> bool {
+        if state != ElementState::Pressed {
+            return false;
+        }
+
+        let settings = &mut self.settings;
+        let (elevation, azimuth) = (sun.elevation(), sun.azimuth());
+
+        let settings_changed = match key_code {
+            KeyCode::ArrowUp => {
+                sun.set_angles((elevation + SUN_ELEVATION_STEP).min(SUN_ELEVATION_LIMIT), azimuth);
+                false
+            },
+            KeyCode::ArrowDown => {
+                sun.set_angles((elevation - SUN_ELEVATION_STEP).max(-SUN_ELEVATION_LIMIT), azimuth);
+                false
+            },
+            KeyCode::ArrowRight => {
+                sun.set_angles(elevation, azimuth + SUN_AZIMUTH_STEP);
+                false
+            },
+            KeyCode::ArrowLeft => {
+                sun.set_angles(elevation, azimuth - SUN_AZIMUTH_STEP);
+                false
+            },
+            KeyCode::PageUp => {
+                sun.set_illuminance(sun.illuminance() * LIGHT_INTENSITY_FACTOR);
+                false
+            },
+            KeyCode::PageDown => {
+                sun.set_illuminance(sun.illuminance() / LIGHT_INTENSITY_FACTOR);
+                false
+            },
+            KeyCode::BracketRight => {
+                settings.rayleigh.scale_height += SCALE_HEIGHT_STEP;
+                true
+            },
+            KeyCode::BracketLeft => {
+                settings.rayleigh.scale_height = (settings.rayleigh.scale_height - SCALE_HEIGHT_STEP).max(SCALE_HEIGHT_STEP);
+                true
+            },
+            KeyCode::Equal => {
+                settings.sample_count = (settings.sample_count + SAMPLE_COUNT_STEP).min(MAX_SAMPLE_COUNT);
+                true
+            },
+            KeyCode::Minus => {
+                settings.sample_count = settings.sample_count.saturating_sub(SAMPLE_COUNT_STEP).max(1);
+                true
+            },
+            KeyCode::Period => {
+                settings.sample_count_light = (settings.sample_count_light + 1).min(MAX_SAMPLE_COUNT_LIGHT);
+                true
+            },
+            KeyCode::Comma => {
+                settings.sample_count_light = settings.sample_count_light.saturating_sub(1).max(1);
+                true
+            },
+            KeyCode::F1 => false,
+            _ => return false
+        };
+
+        self.log(sun);
+        settings_changed
+    }
+
+    pub fn log(&self, sun: &DirectionalLight) {
+        info!("Atmosphere: sun elevation {:.1}, azimuth {:.1}, Rayleigh scale height {:.3}, samples {}/{}, light intensity {:.2}",
+            sun.elevation(), sun.azimuth(), self.settings.rayleigh.scale_height,
+            self.settings.sample_count, self.settings.sample_count_light, sun.illuminance());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRESETS: &str = include_str!("../../resources/config/atmosphere_presets.json");
+
+    fn controls() -> AtmosphereControls {
+        AtmosphereControls::new(AtmosphereSettings::from_presets_json(PRESETS, "earth").unwrap())
+    }
+
+    #[test]
+    fn adjusted_values_stay_valid() {
+        let mut controls = controls();
+        let mut sun = DirectionalLight::new(65.0, 25.0, 100.0);
+
+        for _ in 0..200 {
+            for key_code in [KeyCode::ArrowUp, KeyCode::ArrowLeft, KeyCode::BracketLeft, KeyCode::Minus, KeyCode::Comma, KeyCode::PageDown] {
+                controls.handle_input_key(key_code, ElementState::Pressed, &mut sun);
+            }
+        }
+
+        controls.settings().validate().unwrap();
+        assert!((sun.elevation() - SUN_ELEVATION_LIMIT).abs() < 1e-3);
+        assert!(sun.illuminance() > 0.0);
+        assert_eq!(controls.settings().sample_count, 1);
+        assert_eq!(controls.settings().sample_count_light, 1);
+    }
+
+    #[test]
+    fn sample_counts_stop_at_maximum() {
+        let mut controls = controls();
+        let mut sun = DirectionalLight::new(65.0, 25.0, 100.0);
+
+        for _ in 0..1000 {
+            controls.handle_input_key(KeyCode::Equal, ElementState::Pressed, &mut sun);
+            controls.handle_input_key(KeyCode::Period, ElementState::Pressed, &mut sun);
+        }
+
+        controls.settings().validate().unwrap();
+        assert_eq!(controls.settings().sample_count, MAX_SAMPLE_COUNT);
+        assert_eq!(controls.settings().sample_count_light, MAX_SAMPLE_COUNT_LIGHT);
+    }
+
+    #[test]
+    fn sun_keys_leave_settings_untouched() {
+        let mut controls = controls();
+        let mut sun = DirectionalLight::new(65.0, 25.0, 100.0);
+        let settings = *controls.settings();
+
+        assert!(!controls.handle_input_key(KeyCode::ArrowUp, ElementState::Released, &mut sun));
+        assert!(!controls.handle_input_key(KeyCode::KeyW, ElementState::Pressed, &mut sun));
+        assert!(!controls.handle_input_key(KeyCode::ArrowUp, ElementState::Pressed, &mut sun));
+        assert!(controls.handle_input_key(KeyCode::Equal, ElementState::Pressed, &mut sun));
+
+        assert_eq!(controls.settings().sample_count, settings.sample_count + SAMPLE_COUNT_STEP);
+        assert!((sun.elevation() - 66.0).abs() < 1e-3);
+    }
+}
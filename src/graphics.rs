@@ -1,2 +1,5 @@
-pub(crate) mod rhi;
-pub mod vulkan;
\ No newline at end of file
+pub mod rhi;
+pub mod vulkan;
+pub mod frame_capture;
+#[cfg(test)]
+mod golden;
\ No newline at end of file
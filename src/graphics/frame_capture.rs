@@ -0,0 +1,51 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use anyhow::Result;
+use png::{BitDepth, ColorType, Encoder, SrgbRenderingIntent};
+
+//8-bit RGBA, rows top to bottom with no padding
+#[derive(Debug, Clone)]
+pub struct FrameCapture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl FrameCapture {
+    pub fn from_rgba(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+
+        Self {
+            width,
+            height,
+            pixels
+        }
+    }
+
+    pub fn from_bgra(width: u32, height: u32, mut pixels: Vec<u8>) -> Self {
+        pixels.chunks_exact_mut(4).for_each(|p| p.swap(0, 2));
+        Self::from_rgba(width, height, pixels)
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let writer = BufWriter::new(File::create(path)?);
+
+        let mut encoder = Encoder::new(writer, self.width, self.height);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        //Captured from an sRGB target so values are already gamma encoded
+        encoder.set_source_srgb(SrgbRenderingIntent::Perceptual);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+// Golden-image regression tests for the atmosphere shader.
+//
+// Each scene is rendered offscreen at a fixed size and compared against resources/tests/golden/<scene>.png.
+// On mismatch the actual frame and a diff image are written to target/golden/ next to the failure message.
+//
+// The scene tests need a Vulkan driver and only run with the golden-tests feature. On a GPU-less box
+// scripts/golden_tests.sh runs them on the lavapipe software driver:
+//     VK_ICD_FILENAMES=/usr/share/vulkan/icd.d/lvp_icd.x86_64.json cargo test --features golden-tests golden
+// Set UPDATE_GOLDEN=1 to (re)write the reference images after an intentional change.
+
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use anyhow::{anyhow, Result};
+use png::{BitDepth, ColorType, Decoder};
+
+use crate::config::atmosphere_settings::{AtmosphereSettings, ATMOSPHERE_PRESETS_PATH};
+use crate::config::config::{GraphicsConfig, LogLevel};
+use crate::graphics::frame_capture::FrameCapture;
+use crate::graphics::rhi::RHI;
+use crate::graphics::vulkan::atmopsheric_scattering::ScatteringMedium;
+use crate::graphics::vulkan::vulkan_rhi::RHIVulkan;
+use crate::utils::math::Vector3;
+use crate::world::transform::OwnedTransform;
+use crate::world::world::World;
+
+const REFERENCE_DIR: &str = "./resources/tests/golden";
+const OUTPUT_DIR: &str = "./target/golden";
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 192;
+
+#[derive(Debug, Clone, Copy)]
+struct Tolerance {
+    //Maximum absolute difference allowed per 8-bit channel
+    per_channel: u8,
+    //Fraction of pixels allowed to exceed per_channel
+    mismatched_ratio: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            per_channel: 2,
+            mismatched_ratio: 0.001
+        }
+    }
+}
+
+struct Comparison {
+    mismatched_pixels: usize,
+    max_difference: u8,
+    diff: FrameCapture,
+}
+
+impl Comparison {
+    fn passes(&self, tolerance: Tolerance, pixel_count: usize) -> bool {
+        self.mismatched_pixels as f32 <= tolerance.mismatched_ratio * pixel_count as f32
+    }
+}
+
+struct GoldenScene {
+    name: &'static str,
+    camera_location: Vector3,
+    //Euler degrees
+    camera_rotation: Vector3,
+    //Euler degrees of the sun's transform
+    sun_rotation: Vector3,
+    medium: ScatteringMedium,
+    tolerance: Tolerance,
+}
+
+fn earth_settings() -> Result<AtmosphereSettings> {
+    AtmosphereSettings::from_presets_json(&fs::read_to_string(ATMOSPHERE_PRESETS_PATH)?, "earth")
+}
+
+fn earth_medium() -> ScatteringMedium {
+    earth_settings().unwrap().medium()
+}
+
+fn load_png(path: &Path) -> Result<FrameCapture> {
+    let decoder = Decoder::new(BufReader::new(File::open(path)?));
+    let mut reader = decoder.read_info()?;
+
+    let mut pixels = vec![0u8; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels)?;
+
+    if info.color_type != ColorType::Rgba || info.bit_depth != BitDepth::Eight {
+        return Err(anyhow!("Expected 8-bit RGBA PNG, got {:?} {:?}", info.color_type, info.bit_depth));
+    }
+
+    pixels.truncate(info.buffer_size());
+    Ok(FrameCapture::from_rgba(info.width, info.height, pixels))
+}
+
+//Diff image shows matching pixels as dimmed grayscale of the reference and mismatches in red, scaled by difference
+fn compare(actual: &FrameCapture, reference: &FrameCapture, tolerance: Tolerance) -> Result<Comparison> {
+    if actual.width != reference.width || actual.height != reference.height {
+        return Err(anyhow!("Size mismatch: actual {}x{}, reference {}x{}", actual.width, actual.height, reference.width, reference.height));
+    }
+
+    let mut mismatched_pixels = 0;
+    let mut max_difference = 0;
+    let mut diff = Vec::with_capacity(actual.pixels.len());
+
+    for (a, r) in actual.pixels.chunks_exact(4).zip(reference.pixels.chunks_exact(4)) {
+        let difference = a.iter().zip(r).map(|(a, r)| a.abs_diff(*r)).max().unwrap();
+        max_difference = max_difference.max(difference);
+
+        if difference > tolerance.per_channel {
+            mismatched_pixels += 1;
+            diff.extend_from_slice(&[128u8.saturating_add(difference), 0, 0, 255]);
+        } else {
+            let luminance = ((r[0] as u32 + r[1] as u32 + r[2] as u32) / 12) as u8;
+            diff.extend_from_slice(&[luminance, luminance, luminance, 255]);
+        }
+    }
+
+    Ok(Comparison {
+        mismatched_pixels,
+        max_difference,
+        diff: FrameCapture::from_rgba(actual.width, actual.height, diff)
+    })
+}
+
+fn assert_golden(name: &str, actual: &FrameCapture, tolerance: Tolerance) -> Result<()> {
+    let reference_path = PathBuf::from(REFERENCE_DIR).join(format!("{}.png", name));
+
+    if env::var("UPDATE_GOLDEN").is_ok() {
+        actual.save_png(&reference_path)?;
+        return Ok(());
+    }
+
+    if !reference_path.exists() {
+        return Err(anyhow!("Missing reference image {}. Run with UPDATE_GOLDEN=1 to create it", reference_path.display()));
+    }
+
+    let reference = load_png(&reference_path)?;
+    let comparison = compare(actual, &reference, tolerance)?;
+
+    if comparison.passes(tolerance, (actual.width * actual.height) as usize) {
+        return Ok(());
+    }
+
+    let actual_path = PathBuf::from(OUTPUT_DIR).join(format!("{}_actual.png", name));
+    let diff_path = PathBuf::from(OUTPUT_DIR).join(format!("{}_diff.png", name));
+    actual.save_png(&actual_path)?;
+    comparison.diff.save_png(&diff_path)?;
+
+    Err(anyhow!("{}: {} pixels differ by more than {} (max difference {}). Actual: {}, diff: {}",
+        name, comparison.mismatched_pixels, tolerance.per_channel, comparison.max_difference, actual_path.display(), diff_path.display()))
+}
+
+fn render_scene(scene: &GoldenScene) -> Result<FrameCapture> {
+    let mut world = World::new();
+    let main_camera = world.main_camera_entity();
+    let camera_transform = world.transform_mut(main_camera).unwrap();
+    camera_transform.set_location(scene.camera_location);
+    camera_transform.set_rotation_euler_deg(scene.camera_rotation.x, scene.camera_rotation.y, scene.camera_rotation.z);
+
+    let settings = earth_settings()?;
+    let sun = world.sun_mut();
+    sun.transform_mut().set_rotation_euler_deg(scene.sun_rotation.x, scene.sun_rotation.y, scene.sun_rotation.z);
+    sun.set_illuminance(settings.light_intensity);
+    world.reset_interpolation();
+
+    let config = GraphicsConfig {
+        log_level: LogLevel::Warning,
+        validation_enabled: false,
+        ..Default::default()
+    };
+
+    let mut rhi = RHIVulkan::new_headless(WIDTH, HEIGHT, config, &settings, None)?;
+    rhi.initialize(Arc::new(RwLock::new(world)))?;
+    rhi.set_atmosphere(settings.sample_data(), scene.medium);
+
+    rhi.request_capture()?;
+    rhi.render_offscreen()?;
+    let capture = rhi.take_capture().and_then(|capture| capture.ok_or_else(|| anyhow!("Rendered frame was not captured")));
+
+    rhi.destroy();
+    capture
+}
+
+fn run_scene(scene: GoldenScene) {
+    let capture = render_scene(&scene).unwrap();
+    assert_golden(scene.name, &capture, scene.tolerance).unwrap();
+}
+
+#[test]
+#[cfg_attr(not(feature = "golden-tests"), ignore = "requires a Vulkan driver, enable the golden-tests feature")]
+fn golden_default_view() {
+    run_scene(GoldenScene {
+        name: "default_view",
+        camera_location: Vector3::new(0.0, 0.0, -105.0),
+        camera_rotation: Vector3::new(0.0, 0.0, 0.0),
+        sun_rotation: Vector3::new(-65.0, 25.0, 0.0),
+        medium: earth_medium(),
+        tolerance: Tolerance::default()
+    });
+}
+
+#[test]
+#[cfg_attr(not(feature = "golden-tests"), ignore = "requires a Vulkan driver, enable the golden-tests feature")]
+fn golden_sunset() {
+    run_scene(GoldenScene {
+        name: "sunset",
+        camera_location: Vector3::new(0.0, 0.0, -105.0),
+        camera_rotation: Vector3::new(0.0, 0.0, 0.0),
+        sun_rotation: Vector3::new(-3.0, 90.0, 0.0),
+        medium: earth_medium(),
+        tolerance: Tolerance::default()
+    });
+}
+
+#[test]
+#[cfg_attr(not(feature = "golden-tests"), ignore = "requires a Vulkan driver, enable the golden-tests feature")]
+fn golden_looking_up() {
+    run_scene(GoldenScene {
+        name: "looking_up",
+        camera_location: Vector3::new(0.0, -20.0, -60.0),
+        camera_rotation: Vector3::new(-30.0, 0.0, 0.0),
+        sun_rotation: Vector3::new(-45.0, 0.0, 0.0),
+        medium: earth_medium(),
+        tolerance: Tolerance::default()
+    });
+}
+
+#[test]
+fn compare_identical_frames_pass() {
+    let frame = FrameCapture::from_rgba(2, 1, vec![10, 20, 30, 255, 40, 50, 60, 255]);
+    let comparison = compare(&frame, &frame, Tolerance::default()).unwrap();
+
+    assert_eq!(comparison.mismatched_pixels, 0);
+    assert_eq!(comparison.max_difference, 0);
+    assert!(comparison.passes(Tolerance::default(), 2));
+}
+
+#[test]
+fn compare_reports_pixels_outside_tolerance() {
+    let reference = FrameCapture::from_rgba(2, 1, vec![10, 20, 30, 255, 40, 50, 60, 255]);
+    let actual = FrameCapture::from_rgba(2, 1, vec![11, 20, 30, 255, 40, 90, 60, 255]);
+    let tolerance = Tolerance { per_channel: 2, mismatched_ratio: 0.0 };
+
+    let comparison = compare(&actual, &reference, tolerance).unwrap();
+
+    assert_eq!(comparison.mismatched_pixels, 1);
+    assert_eq!(comparison.max_difference, 40);
+    assert!(!comparison.passes(tolerance, 2));
+    assert_eq!(&comparison.diff.pixels[4..8], &[168, 0, 0, 255]);
+}
+
+#[test]
+fn compare_rejects_size_mismatch() {
+    let reference = FrameCapture::from_rgba(1, 1, vec![0, 0, 0, 255]);
+    let actual = FrameCapture::from_rgba(1, 2, vec![0, 0, 0, 255, 0, 0, 0, 255]);
+
+    assert!(compare(&actual, &reference, Tolerance::default()).is_err());
+}
@@ -3,14 +3,37 @@ use std::sync::{Arc, RwLock};
 use anyhow::Result;
 use winit::window::Window;
 
+use crate::graphics::frame_capture::FrameCapture;
+use crate::assets::mesh::Mesh;
 use crate::world::world::World;
 
+//Refers to a mesh uploaded with RHI::register_mesh
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MeshHandle(pub(crate) u32);
+
+impl MeshHandle {
+    //Registered by the renderer on creation, the configured model or the built-in cube
+    pub const DEFAULT: MeshHandle = MeshHandle(0);
+}
+
+//Render hardware interface, the name follows Unreal's
+#[allow(clippy::upper_case_acronyms)]
 pub trait RHI {
     fn initialize(&mut self, world: Arc<RwLock<World>>) -> Result<()>;
     fn update(&mut self);
     fn render(&mut self, window: &Window) -> Result<()>;
     fn destroy(&mut self);
 
+    //Uploads the mesh, entities draw it by referring to the returned handle
+    fn register_mesh(&mut self, mesh: &Mesh) -> Result<MeshHandle>;
+    //Entities still referring to the handle are no longer drawn
+    fn unregister_mesh(&mut self, handle: MeshHandle) -> Result<()>;
+
+    //Copies the next rendered frame back before it is presented. Fails when the surface can't be read from
+    fn request_capture(&mut self) -> Result<()>;
+    //The frame copied after request_capture, None until it has been rendered
+    fn take_capture(&mut self) -> Result<Option<FrameCapture>>;
+
     fn get_width(&self) -> u32;
     fn get_height(&self) -> u32;
 }
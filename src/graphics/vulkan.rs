@@ -3,9 +3,21 @@ pub mod vulkan_sync_objects;
 pub mod vulkan_utils;
 pub mod vulkan_swapchain;
 mod vulkan_pipeline;
+mod vulkan_atmosphere_lut;
+mod vulkan_post_process;
+mod vulkan_frame_capture;
+mod vulkan_auto_exposure;
+mod vulkan_planet;
+mod vulkan_mesh;
+mod vulkan_texture;
+mod vulkan_mesh_registry;
 mod vertex;
 mod transformation;
 mod push_constants;
 mod view_state;
-mod atmopsheric_scattering;
+pub mod atmopsheric_scattering;
+pub mod spectral;
+//Only the tests compare against it, the renderer runs the shaders
+#[cfg(test)]
+mod scattering_reference;
 mod vulkan_rhi_data;
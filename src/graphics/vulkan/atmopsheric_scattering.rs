@@ -5,7 +5,7 @@ use crate::graphics::vulkan::vertex::{Vector3, Vector4};
 
 #[repr(C)]
 #[pub_fields]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct AtmosphereSampleData {
     planet_pos: Vector4,
     planet_radius: f32,
@@ -21,13 +21,45 @@ pub struct AtmosphereSampleData {
     light_intensity: Vector4
 }
 
+impl AtmosphereSampleData {
+    //Earth-like planet in sky units (see unit_scale), kept for tests. At runtime the atmosphere comes from AtmosphereSettings
+    #[cfg(test)]
+    pub fn earth(light_dir: Vector3, light_intensity: f32) -> Self {
+        Self {
+            planet_pos: Vector4::zero(),
+            planet_radius: 6.3710,
+            atmosphere_thickness: 0.0600,
+            sample_count: 100.0,
+            sample_count_light: 15.0,
+            unit_scale: 0.2,
+            light_dir: Vector4::zero(),
+            light_intensity: Vector4::zero(),
+
+            pad: [0.0, 0.0, 0.0]
+        }.with_light(light_dir, Vector3::new(1.0, 1.0, 1.0) * light_intensity)
+    }
+
+    pub fn with_light(mut self, light_dir: Vector3, light_intensity: Vector3) -> Self {
+        self.light_dir = light_dir.extend(0.0);
+        self.light_intensity = light_intensity.extend(1.0);
+
+        self
+    }
+}
+
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[pub_fields]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ScatteringMedium {
     scale_height_r: f32,
     scale_height_m: f32,
+    //Mie phase asymmetry, -1 (back scattering) to 1 (forward scattering)
+    mie_g: f32,
+    //Ozone tent profile, altitude of the peak and full width (sky units)
+    ozone_center: f32,
+    ozone_width: f32,
 
-    pad: [f32; 2],
+    pad: [f32; 3],
 
     //Rayleigh
     scattering_r: Vector4,
@@ -38,9 +70,13 @@ pub struct ScatteringMedium {
     scattering_m: Vector4,
     absorption_m: Vector4,
     extinction_m: Vector4,
+
+    //Ozone, absorption only
+    absorption_o: Vector4,
 }
 
 impl ScatteringMedium {
+    //Rayleigh only, Mie terms are left at zero
     pub fn new(scale_height_r: f32, scattering_ray: Vector3) -> Self
     {
         let mut result = Self::default();
@@ -51,13 +87,28 @@ impl ScatteringMedium {
         result.absorption_r = Vector4::zero();
         result.extinction_r = result.scattering_r + result.absorption_r;
 
-        //
-        // scatteringM = scatteringMie;
-        // absorptionM = absorptionMie;
-        // extinctionM = scatteringM + absorptionM;
-
         result
     }
+
+    //Absorption is the part of the extinction that is not scattered, i.e. extinction - scattering
+    pub fn with_mie(mut self, scale_height_m: f32, scattering_mie: Vector3, absorption_mie: Vector3, g: f32) -> Self {
+        self.scale_height_m = scale_height_m;
+        self.mie_g = g;
+
+        self.scattering_m = scattering_mie.extend(0.0);
+        self.absorption_m = absorption_mie.extend(0.0);
+        self.extinction_m = self.scattering_m + self.absorption_m;
+
+        self
+    }
+
+    pub fn with_ozone(mut self, center: f32, width: f32, absorption_ozone: Vector3) -> Self {
+        self.ozone_center = center;
+        self.ozone_width = width;
+        self.absorption_o = absorption_ozone.extend(0.0);
+
+        self
+    }
 }
 
 impl Default for ScatteringMedium {
@@ -65,6 +116,9 @@ impl Default for ScatteringMedium {
         Self {
             scale_height_r: 0.0,
             scale_height_m: 0.0,
+            mie_g: 0.0,
+            ozone_center: 0.0,
+            ozone_width: 0.0,
 
             scattering_r: Vector4::zero(),
             absorption_r: Vector4::zero(),
@@ -74,7 +128,9 @@ impl Default for ScatteringMedium {
             absorption_m: Vector4::zero(),
             extinction_m: Vector4::zero(),
 
-            pad: [0.0, 0.0]
+            absorption_o: Vector4::zero(),
+
+            pad: [0.0, 0.0, 0.0]
         }
     }
 }
@@ -1,15 +1,30 @@
+use std::mem::size_of;
+use std::slice;
+
+use crate::assets::material::Material;
 use crate::graphics::vulkan::transformation::Matrix4x4;
+use crate::graphics::vulkan::vertex::Vector4;
 
+//Per draw constants of the scene pipeline, shared by basic_vert.hlsl and basic_frag.hlsl
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct PushConstants {
     model: Matrix4x4,
+    base_color: Vector4,
+    //Metallic, roughness and two unused components
+    metallic_roughness: Vector4,
 }
 
 impl PushConstants {
-    pub fn new(model: Matrix4x4) -> Self {
+    pub fn new(model: Matrix4x4, material: &Material) -> Self {
         Self {
-            model
+            model,
+            base_color: material.base_color,
+            metallic_roughness: Vector4::new(material.metallic, material.roughness, 0.0, 0.0),
         }
     }
-}
\ No newline at end of file
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self as *const Self as *const u8, size_of::<Self>()) }
+    }
+}
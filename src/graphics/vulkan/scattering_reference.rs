@@ -0,0 +1,524 @@
+// CPU port of SingleScattering from sky_frag.hlsl and the LUT compute shaders.
+// Kept line for line with the shaders so it can serve as an oracle when validating shader changes.
+// LUT lookups are replaced by evaluating the LUT function directly.
+
+use cgmath::{ElementWise, InnerSpace};
+
+use crate::graphics::vulkan::atmopsheric_scattering::{AtmosphereSampleData, ScatteringMedium};
+use crate::utils::math::{Vector2, Vector3, Vector4, Zero};
+
+const PI: f32 = std::f32::consts::PI;
+
+//Returns distances along the ray to both intersections, or -1 for both if the ray misses
+pub fn ray_intersect_sphere(ray_origin: Vector3, ray_direction: Vector3, sphere: Vector4) -> Vector2 {
+    let local_position = ray_origin - sphere.truncate();
+    let local_position_sqr = local_position.dot(local_position);
+
+    let a = ray_direction.dot(ray_direction);
+    let b = 2.0 * ray_direction.dot(local_position);
+    let c = local_position_sqr - sphere.w * sphere.w;
+
+    let discriminant = b * b - 4.0 * a * c;
+
+    if discriminant < 0.0 {
+        return Vector2::new(-1.0, -1.0);
+    }
+
+    let sqrt_discriminant = discriminant.sqrt();
+    Vector2::new((-b - sqrt_discriminant) / (2.0 * a), (-b + sqrt_discriminant) / (2.0 * a))
+}
+
+// Theta - angle between light direction and view direction
+pub fn phase_rayleigh(cos_theta: f32) -> f32 {
+    (3.0 / (16.0 * PI)) * (1.0 + cos_theta * cos_theta)
+}
+
+// Cornette-Shanks, g - asymmetry
+pub fn phase_mie(cos_theta: f32, g: f32) -> f32 {
+    let g2 = g * g;
+    let k = (3.0 / (8.0 * PI)) * (1.0 - g2) / (2.0 + g2);
+    k * (1.0 + cos_theta * cos_theta) / (1.0 + g2 - 2.0 * g * cos_theta).powf(1.5)
+}
+
+// Rayleigh in x, Mie in y, Ozone in z
+fn density(h: f32, medium: &ScatteringMedium) -> Vector3 {
+    let density_m = if medium.scale_height_m > 0.0 { (-h / medium.scale_height_m).exp() } else { 0.0 };
+    let density_o = if medium.ozone_width > 0.0 {
+        (1.0 - (h - medium.ozone_center).abs() / (medium.ozone_width * 0.5)).max(0.0)
+    } else {
+        0.0
+    };
+    Vector3::new((-h / medium.scale_height_r).exp(), density_m, density_o)
+}
+
+fn exp3(v: Vector3) -> Vector3 {
+    Vector3::new(v.x.exp(), v.y.exp(), v.z.exp())
+}
+
+// Optical depth as returned by accumulating density
+fn extinction(optical_depth: Vector3, medium: &ScatteringMedium) -> Vector3 {
+    medium.extinction_r.truncate() * optical_depth.x
+        + medium.extinction_m.truncate() * optical_depth.y
+        + medium.absorption_o.truncate() * optical_depth.z
+}
+
+fn scattering(density: Vector3, medium: &ScatteringMedium) -> Vector3 {
+    medium.scattering_r.truncate() * density.x + medium.scattering_m.truncate() * density.y
+}
+
+//Both LUTs are indexed by the cosine of the light zenith angle (u) and altitude (v)
+pub fn lut_uv(h: f32, cos_zenith: f32, sample_data: &AtmosphereSampleData) -> Vector2 {
+    Vector2::new((cos_zenith * 0.5 + 0.5).clamp(0.0, 1.0), (h / sample_data.atmosphere_thickness).clamp(0.0, 1.0))
+}
+
+//Returns (h, cos_zenith)
+pub fn lut_params(uv: Vector2, sample_data: &AtmosphereSampleData) -> (f32, f32) {
+    (uv.y * sample_data.atmosphere_thickness, uv.x * 2.0 - 1.0)
+}
+
+//Stand-in for a transmittance LUT lookup at point x (sky units, planet centered)
+fn sample_transmittance(x: Vector3, light_dir: Vector3, sample_data: &AtmosphereSampleData, medium: &ScatteringMedium) -> Vector3 {
+    let r = x.magnitude();
+    transmittance_to_top(r - sample_data.planet_radius, (x / r).dot(light_dir), sample_data, medium)
+}
+
+//Stand-in for a multi-scattering LUT lookup at point x (sky units, planet centered)
+fn sample_multi_scattering(x: Vector3, light_dir: Vector3, sample_data: &AtmosphereSampleData, medium: &ScatteringMedium) -> Vector3 {
+    let r = x.magnitude();
+    multi_scattering(r - sample_data.planet_radius, (x / r).dot(light_dir), sample_data, medium).0
+}
+
+//atmosphere_transmittance_comp.hlsl
+//Transmittance from altitude h towards the top of the atmosphere, zero if the ray hits the planet
+pub fn transmittance_to_top(h: f32, cos_zenith: f32, sample_data: &AtmosphereSampleData, medium: &ScatteringMedium) -> Vector3 {
+    let x = Vector3::new(0.0, sample_data.planet_radius + h, 0.0);
+    let dir = Vector3::new((1.0 - cos_zenith * cos_zenith).clamp(0.0, 1.0).sqrt(), cos_zenith, 0.0);
+
+    //x is never below the surface, so a downward ray hitting the planet (even at x itself) is blocked
+    let p_planet = ray_intersect_sphere(x, dir, Vector4::new(0.0, 0.0, 0.0, sample_data.planet_radius));
+    if cos_zenith < 0.0 && p_planet.y > 0.0 {
+        return Vector3::zero();
+    }
+
+    let p = ray_intersect_sphere(x, dir, Vector4::new(0.0, 0.0, 0.0, sample_data.planet_radius + sample_data.atmosphere_thickness));
+    let ds = p.y.max(0.0) / sample_data.sample_count_light;
+
+    let mut optical_depth = Vector3::zero();
+    for i in 0..sample_data.sample_count_light as i32 {
+        let x_light = x + dir * (ds * (i as f32 + 0.5));
+        optical_depth += density(x_light.magnitude() - sample_data.planet_radius, medium) * ds;
+    }
+
+    exp3(-extinction(optical_depth, medium))
+}
+
+const SQRT_DIRECTION_COUNT: i32 = 8;
+const STEP_COUNT: i32 = 20;
+
+//atmosphere_multi_scattering_comp.hlsl
+//Returns the multi-scattering contribution (psi) and the transfer factor fms, for unit light illuminance
+pub fn multi_scattering(h: f32, cos_sun_zenith: f32, sample_data: &AtmosphereSampleData, medium: &ScatteringMedium) -> (Vector3, Vector3) {
+    let x = Vector3::new(0.0, sample_data.planet_radius + h, 0.0);
+    let sun_dir = Vector3::new((1.0 - cos_sun_zenith * cos_sun_zenith).clamp(0.0, 1.0).sqrt(), cos_sun_zenith, 0.0);
+
+    let planet = Vector4::new(0.0, 0.0, 0.0, sample_data.planet_radius);
+    let atmosphere = Vector4::new(0.0, 0.0, 0.0, sample_data.planet_radius + sample_data.atmosphere_thickness);
+    let isotropic_phase = 1.0 / (4.0 * PI);
+
+    let mut l2 = Vector3::zero();
+    let mut fms = Vector3::zero();
+
+    for i in 0..SQRT_DIRECTION_COUNT {
+        for j in 0..SQRT_DIRECTION_COUNT {
+            //Uniform directions over the sphere
+            let cos_theta = 1.0 - 2.0 * (i as f32 + 0.5) / SQRT_DIRECTION_COUNT as f32;
+            let sin_theta = (1.0 - cos_theta * cos_theta).clamp(0.0, 1.0).sqrt();
+            let phi = 2.0 * PI * (j as f32 + 0.5) / SQRT_DIRECTION_COUNT as f32;
+            let dir = Vector3::new(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin());
+
+            let p_planet = ray_intersect_sphere(x, dir, planet);
+            let t_max = if p_planet.x > 0.0 { p_planet.x } else { ray_intersect_sphere(x, dir, atmosphere).y };
+            let ds = t_max.max(0.0) / STEP_COUNT as f32;
+
+            let mut optical_depth = Vector3::zero();
+            for k in 0..STEP_COUNT {
+                let xs = x + dir * (ds * (k as f32 + 0.5));
+                let density_s = density(xs.magnitude() - sample_data.planet_radius, medium) * ds;
+                optical_depth += density_s;
+
+                let transmittance = exp3(-extinction(optical_depth, medium));
+                let scattering_s = scattering(density_s, medium);
+                let sun_transmittance = sample_transmittance(xs, sun_dir, sample_data, medium);
+
+                l2 += transmittance.mul_element_wise(scattering_s).mul_element_wise(sun_transmittance) * isotropic_phase;
+                fms += transmittance.mul_element_wise(scattering_s);
+            }
+        }
+    }
+
+    //Integration against the uniform phase function is the average over all directions
+    let direction_count = (SQRT_DIRECTION_COUNT * SQRT_DIRECTION_COUNT) as f32;
+    l2 /= direction_count;
+    fms /= direction_count;
+
+    let psi = Vector3::new(l2.x / (1.0 - fms.x), l2.y / (1.0 - fms.y), l2.z / (1.0 - fms.z));
+    (psi, fms)
+}
+
+//atmosphere_scattering.hlsl
+//Sky-view LUT basis around the camera: (up, forward, right), forward points towards the light
+pub fn sky_view_basis(sky_pos: Vector3, light_dir: Vector3) -> (Vector3, Vector3, Vector3) {
+    let up = sky_pos.normalize();
+
+    let mut forward = light_dir - up * light_dir.dot(up);
+    //Light straight above or below, any horizontal direction works
+    if forward.magnitude2() < 1e-8 {
+        forward = if up.y.abs() < 0.99 { up.cross(Vector3::unit_y()) } else { up.cross(Vector3::unit_x()) };
+    }
+    let forward = forward.normalize();
+    let right = up.cross(forward);
+
+    (up, forward, right)
+}
+
+//Latitude is stored non-linearly to keep detail near the horizon
+pub fn sky_view_uv(view_dir: Vector3, (up, forward, right): (Vector3, Vector3, Vector3)) -> Vector2 {
+    let latitude = view_dir.dot(up).clamp(-1.0, 1.0).asin();
+    let v = 0.5 + 0.5 * latitude.signum() * (latitude.abs() / (0.5 * PI)).sqrt();
+
+    let horizontal = Vector2::new(view_dir.dot(forward), view_dir.dot(right).abs());
+    let longitude = if horizontal.magnitude2() > 0.0 { horizontal.y.atan2(horizontal.x) } else { 0.0 };
+
+    Vector2::new(longitude / PI, v)
+}
+
+pub fn sky_view_dir(uv: Vector2, (up, forward, right): (Vector3, Vector3, Vector3)) -> Vector3 {
+    let vc = uv.y * 2.0 - 1.0;
+    let latitude = vc.signum() * vc * vc * 0.5 * PI;
+    let longitude = uv.x * PI;
+
+    (forward * longitude.cos() + right * longitude.sin()) * latitude.cos() + up * latitude.sin()
+}
+
+//sky_frag.hlsl
+//Radiance reaching view_pos from the direction of world_pos. Both are in world units, as in the shader
+pub fn single_scattering(world_pos: Vector3, view_pos: Vector3, sample_data: &AtmosphereSampleData, medium: &ScatteringMedium) -> Vector3 {
+    let view_dir = (world_pos - view_pos).normalize();
+    let view_pos = (view_pos - sample_data.planet_pos.truncate()) * sample_data.unit_scale;
+    let light_dir = sample_data.light_dir.truncate();
+
+    let atmosphere = Vector4::new(0.0, 0.0, 0.0, sample_data.planet_radius + sample_data.atmosphere_thickness);
+
+    let mut p = ray_intersect_sphere(view_pos, view_dir, atmosphere);
+    if p.x < 0.0 && p.y < 0.0 {
+        return Vector3::zero();
+    }
+
+    let p_planet = ray_intersect_sphere(view_pos, view_dir, Vector4::new(0.0, 0.0, 0.0, sample_data.planet_radius));
+    if p_planet.x > 0.0 {
+        p.y = p_planet.x;
+    }
+
+    p.x = p.x.max(0.0);
+    p.y = p.y.min(9000000.0);
+
+    //Accumulated light
+    let mut a_r = Vector3::zero();
+    let mut a_m = Vector3::zero();
+    let mut a_ms = Vector3::zero();
+
+    let mut optical_depth = Vector3::zero();
+    let mut current_sample = p.x;
+    // Sample size
+    let ds = (p.y - p.x) / sample_data.sample_count;
+
+    for _ in 0..sample_data.sample_count as i32 {
+        //Sample point X
+        let x = view_pos + view_dir * (current_sample + ds * 0.5);
+
+        //Height/Altitude
+        let h = x.magnitude() - sample_data.planet_radius;
+
+        let density_x = density(h, medium) * ds;
+        optical_depth += density_x;
+
+        let view_transmittance = exp3(-extinction(optical_depth, medium));
+
+        //Light transmittance, zero when the planet is in the way
+        let transmittance = view_transmittance.mul_element_wise(sample_transmittance(x, light_dir, sample_data, medium));
+        a_r += transmittance * density_x.x;
+        a_m += transmittance * density_x.y;
+
+        //Multiple scattering, phase is already accounted for
+        a_ms += view_transmittance
+            .mul_element_wise(scattering(density_x, medium))
+            .mul_element_wise(sample_multi_scattering(x, light_dir, sample_data, medium));
+
+        current_sample += ds;
+    }
+
+    let cos_theta = light_dir.dot(view_dir);
+    let phase_r = phase_rayleigh(cos_theta);
+    let phase_m = phase_mie(cos_theta, medium.mie_g);
+    let scattering = medium.scattering_r.truncate().mul_element_wise(a_r) * phase_r
+        + medium.scattering_m.truncate().mul_element_wise(a_m) * phase_m
+        + a_ms;
+
+    sample_data.light_intensity.truncate().mul_element_wise(scattering)
+}
+
+//Convenience for sampling the sky along a direction from a camera position
+pub fn sky_radiance(view_pos: Vector3, view_dir: Vector3, sample_data: &AtmosphereSampleData, medium: &ScatteringMedium) -> Vector3 {
+    single_scattering(view_pos + view_dir, view_pos, sample_data, medium)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, InnerSpace};
+
+    use super::*;
+    use crate::utils::math::{VECTOR3_DOWN, VECTOR3_UP};
+
+    fn earth(light_dir: Vector3) -> (AtmosphereSampleData, ScatteringMedium) {
+        let sample_data = AtmosphereSampleData::earth(light_dir.normalize(), 100.0);
+        let medium = ScatteringMedium::new(0.2, Vector3::new(0.175287, 0.409607, 1.0));
+
+        (sample_data, medium)
+    }
+
+    //Camera standing just above the surface at the planet's north pole, in world units
+    fn surface_camera(sample_data: &AtmosphereSampleData) -> Vector3 {
+        Vector3::new(0.0, (sample_data.planet_radius + 0.0001) / sample_data.unit_scale, 0.0)
+    }
+
+    //Direction with the given elevation above the horizon, towards +Z
+    fn elevated(elevation: f32) -> Vector3 {
+        let (sin, cos) = Deg(elevation).0.to_radians().sin_cos();
+        Vector3::new(0.0, sin, cos)
+    }
+
+    fn luminance(c: Vector3) -> f32 {
+        0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+    }
+
+    #[test]
+    fn ray_sphere_hits_from_outside() {
+        let hits = ray_intersect_sphere(Vector3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0), Vector4::new(0.0, 0.0, 0.0, 1.0));
+
+        assert!((hits.x - 4.0).abs() < 1e-5);
+        assert!((hits.y - 6.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ray_sphere_from_inside_has_negative_near_hit() {
+        let hits = ray_intersect_sphere(Vector3::zero(), Vector3::new(1.0, 0.0, 0.0), Vector4::new(0.0, 0.0, 0.0, 2.0));
+
+        assert!((hits.x + 2.0).abs() < 1e-5);
+        assert!((hits.y - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ray_sphere_miss() {
+        let hits = ray_intersect_sphere(Vector3::new(0.0, 5.0, -5.0), Vector3::new(0.0, 0.0, 1.0), Vector4::new(0.0, 0.0, 0.0, 1.0));
+
+        assert_eq!(hits, Vector2::new(-1.0, -1.0));
+    }
+
+    //Integrate over the sphere: 2 * PI * integral of p(cos) d(cos) over [-1, 1]
+    fn integrate_phase(phase: impl Fn(f32) -> f32) -> f32 {
+        let steps = 20000;
+        (0..steps)
+            .map(|i| {
+                let cos_theta = -1.0 + (i as f32 + 0.5) * 2.0 / steps as f32;
+                phase(cos_theta) * 2.0 / steps as f32
+            })
+            .sum::<f32>() * 2.0 * PI
+    }
+
+    #[test]
+    fn phase_rayleigh_is_normalized() {
+        assert!((integrate_phase(phase_rayleigh) - 1.0).abs() < 1e-3);
+        assert!(phase_rayleigh(1.0) > phase_rayleigh(0.0));
+        assert_eq!(phase_rayleigh(1.0), phase_rayleigh(-1.0));
+    }
+
+    #[test]
+    fn phase_mie_is_normalized_and_forward_peaked() {
+        for g in [0.0, 0.5, 0.76] {
+            assert!((integrate_phase(|c| phase_mie(c, g)) - 1.0).abs() < 1e-2, "g = {}", g);
+        }
+
+        assert!(phase_mie(1.0, 0.76) > 10.0 * phase_mie(-1.0, 0.76));
+        assert!((phase_mie(0.3, 0.0) - phase_mie(-0.3, 0.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mie_adds_halo_around_sun() {
+        let sun = elevated(30.0);
+        let (sample_data, rayleigh) = earth(sun);
+        let hazy = rayleigh.with_mie(0.03, Vector3::new(0.12, 0.12, 0.12), Vector3::new(0.013, 0.013, 0.013), 0.8);
+        let camera = surface_camera(&sample_data);
+
+        let towards_sun = sky_radiance(camera, elevated(31.0), &sample_data, &hazy);
+        let away_from_sun = sky_radiance(camera, elevated(150.0), &sample_data, &hazy);
+        let towards_sun_clear = sky_radiance(camera, elevated(31.0), &sample_data, &rayleigh);
+
+        assert!(luminance(towards_sun) > luminance(towards_sun_clear));
+        assert!(luminance(towards_sun) > 2.0 * luminance(away_from_sun));
+    }
+
+    #[test]
+    fn zenith_sky_is_blue() {
+        let (sample_data, medium) = earth(VECTOR3_UP);
+        let zenith = sky_radiance(surface_camera(&sample_data), VECTOR3_UP, &sample_data, &medium);
+
+        assert!(zenith.z > zenith.y && zenith.y > zenith.x);
+        assert!(zenith.x > 0.0);
+    }
+
+    #[test]
+    fn horizon_is_brighter_and_whiter_than_zenith() {
+        let (sample_data, medium) = earth(VECTOR3_UP);
+        let camera = surface_camera(&sample_data);
+
+        let zenith = sky_radiance(camera, VECTOR3_UP, &sample_data, &medium);
+        let horizon = sky_radiance(camera, elevated(1.0), &sample_data, &medium);
+
+        assert!(luminance(horizon) > luminance(zenith));
+        assert!(horizon.x / horizon.z > zenith.x / zenith.z);
+    }
+
+    #[test]
+    fn sunset_reddens_the_sky() {
+        let (noon_data, medium) = earth(elevated(60.0));
+        let (sunset_data, _) = earth(elevated(2.0));
+        let camera = surface_camera(&noon_data);
+        let view_dir = elevated(10.0);
+
+        let noon = sky_radiance(camera, view_dir, &noon_data, &medium);
+        let sunset = sky_radiance(camera, view_dir, &sunset_data, &medium);
+
+        assert!(sunset.x / sunset.z > noon.x / noon.z);
+    }
+
+    #[test]
+    fn ozone_density_is_tent_shaped() {
+        let medium = ScatteringMedium::default().with_ozone(0.025, 0.03, Vector3::new(0.149, 0.431, 0.0195));
+
+        assert_eq!(density(0.025, &medium).z, 1.0);
+        assert!((density(0.0325, &medium).z - 0.5).abs() < 1e-5);
+        assert!((density(0.0175, &medium).z - 0.5).abs() < 1e-5);
+        assert_eq!(density(0.005, &medium).z, 0.0);
+        assert_eq!(density(0.05, &medium).z, 0.0);
+        assert_eq!(density(0.025, &ScatteringMedium::default()).z, 0.0);
+    }
+
+    #[test]
+    fn ozone_keeps_twilight_zenith_blue() {
+        let (sample_data, clear) = earth(elevated(1.0));
+        let ozone = clear.with_ozone(0.025, 0.03, Vector3::new(0.149, 0.431, 0.0195));
+        let camera = surface_camera(&sample_data);
+
+        let without_ozone = sky_radiance(camera, VECTOR3_UP, &sample_data, &clear);
+        let with_ozone = sky_radiance(camera, VECTOR3_UP, &sample_data, &ozone);
+
+        assert!(with_ozone.z / with_ozone.x > without_ozone.z / without_ozone.x);
+        assert!(with_ozone.z / with_ozone.y > without_ozone.z / without_ozone.y);
+        assert!(luminance(with_ozone) < luminance(without_ozone));
+    }
+
+    #[test]
+    fn sky_view_uv_round_trips() {
+        let (sample_data, _) = earth(VECTOR3_UP);
+        let basis = sky_view_basis(surface_camera(&sample_data) * sample_data.unit_scale, elevated(10.0));
+
+        for dir in [VECTOR3_UP, elevated(0.5), elevated(-30.0), Vector3::new(1.0, 0.2, 0.0).normalize(), Vector3::new(0.3, -0.1, -1.0).normalize()] {
+            let round_trip = sky_view_dir(sky_view_uv(dir, basis), basis);
+            assert!((round_trip - dir).magnitude() < 1e-4, "{:?} -> {:?}", dir, round_trip);
+        }
+    }
+
+    #[test]
+    fn sky_view_is_symmetric_about_light_plane() {
+        let (sample_data, _) = earth(VECTOR3_UP);
+        let basis = sky_view_basis(surface_camera(&sample_data) * sample_data.unit_scale, elevated(10.0));
+
+        let left = sky_view_uv(Vector3::new(-0.6, 0.3, 0.2).normalize(), basis);
+        let right = sky_view_uv(Vector3::new(0.6, 0.3, 0.2).normalize(), basis);
+        assert!((left - right).magnitude() < 1e-6);
+
+        //Horizon sits in the middle of the LUT
+        assert!((sky_view_uv(elevated(0.0), basis).y - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn below_horizon_is_dark() {
+        let (sample_data, medium) = earth(VECTOR3_UP);
+        let camera = surface_camera(&sample_data);
+
+        let ground = sky_radiance(camera, VECTOR3_DOWN, &sample_data, &medium);
+        let zenith = sky_radiance(camera, VECTOR3_UP, &sample_data, &medium);
+
+        assert!(luminance(ground) < luminance(zenith) * 0.01);
+    }
+
+    #[test]
+    fn sun_below_horizon_leaves_faint_multi_scattering() {
+        let (noon_data, medium) = earth(VECTOR3_UP);
+        let (night_data, _) = earth(elevated(-20.0));
+        let camera = surface_camera(&noon_data);
+
+        let noon = sky_radiance(camera, VECTOR3_UP, &noon_data, &medium);
+        let night = sky_radiance(camera, VECTOR3_UP, &night_data, &medium);
+
+        //Only light scattered more than once, from parts of the atmosphere the sun still reaches
+        assert!(luminance(night) < luminance(noon) * 1e-3);
+    }
+
+    #[test]
+    fn lut_uv_round_trips() {
+        let (sample_data, _) = earth(VECTOR3_UP);
+
+        for (h, cos_zenith) in [(0.0, -1.0), (0.015, 0.25), (0.06, 1.0)] {
+            let (h_out, cos_out) = lut_params(lut_uv(h, cos_zenith, &sample_data), &sample_data);
+            assert!((h_out - h).abs() < 1e-6 && (cos_out - cos_zenith).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn transmittance_to_top_falls_towards_horizon() {
+        let (sample_data, medium) = earth(VECTOR3_UP);
+
+        let zenith = transmittance_to_top(0.0, 1.0, &sample_data, &medium);
+        let low = transmittance_to_top(0.0, 0.1, &sample_data, &medium);
+        let blocked = transmittance_to_top(0.0, -0.1, &sample_data, &medium);
+        let top = transmittance_to_top(sample_data.atmosphere_thickness, 1.0, &sample_data, &medium);
+
+        assert!(zenith.x < 1.0 && zenith.z < zenith.x);
+        assert!(low.z < zenith.z);
+        assert_eq!(blocked, Vector3::zero());
+        assert!((top - Vector3::new(1.0, 1.0, 1.0)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn multi_scattering_transfer_is_energy_conserving() {
+        let (sample_data, medium) = earth(VECTOR3_UP);
+
+        let (noon_psi, noon_fms) = multi_scattering(0.0, 1.0, &sample_data, &medium);
+        let (dusk_psi, _) = multi_scattering(0.0, 0.0, &sample_data, &medium);
+
+        assert!(noon_fms.x > 0.0 && noon_fms.z < 1.0);
+        assert!(noon_psi.z > noon_psi.x && noon_psi.x > 0.0);
+        assert!(luminance(dusk_psi) < luminance(noon_psi));
+    }
+
+    #[test]
+    fn view_ray_missing_atmosphere_is_black() {
+        let (sample_data, medium) = earth(VECTOR3_UP);
+        let far_away = Vector3::new(0.0, 0.0, -1000.0);
+
+        let radiance = sky_radiance(far_away, VECTOR3_UP, &sample_data, &medium);
+
+        assert_eq!(radiance, Vector3::zero());
+    }
+}
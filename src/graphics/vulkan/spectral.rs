@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+use crate::utils::math::Vector3;
+
+//Wavelengths in nanometers the RGB channels are evaluated at
+pub const RGB_WAVELENGTHS: [f64; 3] = [680.0, 550.0, 440.0];
+pub const AIR_DEPOLARIZATION_RATIO: f64 = 0.035;
+
+//How a wavelength dependent coefficient is reduced to RGB
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all(serialize="lowercase", deserialize="lowercase"))]
+pub enum Spectrum {
+    //One wavelength (nm) per channel
+    Wavelengths([f64; 3]),
+    //Sampled over [start, end] nm and integrated against the CIE 1931 matching functions
+    Cie { start: f64, end: f64, step: f64 },
+}
+
+impl Default for Spectrum {
+    fn default() -> Self {
+        Spectrum::Wavelengths(RGB_WAVELENGTHS)
+    }
+}
+
+impl Spectrum {
+    //Linear RGB of a coefficient given per wavelength in nm. A constant spectrum stays gray
+    pub fn rgb(&self, coefficient: impl Fn(f64) -> f64) -> Vector3 {
+        match *self {
+            Spectrum::Wavelengths(wavelengths) => {
+                Vector3::new(coefficient(wavelengths[0]) as f32, coefficient(wavelengths[1]) as f32, coefficient(wavelengths[2]) as f32)
+            },
+            Spectrum::Cie { start, end, step } => {
+                let rgb = integrate_cie(start, end, step, &coefficient);
+                let white = integrate_cie(start, end, step, |_| 1.0);
+
+                Vector3::new((rgb[0] / white[0]) as f32, (rgb[1] / white[1]) as f32, (rgb[2] / white[2]) as f32)
+            }
+        }
+    }
+}
+
+//Rayleigh scattering coefficient in 1/m of a gas with the given refractive index and molecular number density (1/m^3)
+//at a wavelength in nm, including the King correction factor for anisotropic molecules
+pub fn rayleigh_coefficient(refractive_index: f64, molecular_density: f64, depolarization_ratio: f64, wavelength: f64) -> f64 {
+    let lambda = wavelength * 1e-9;
+    let n2 = refractive_index * refractive_index - 1.0;
+    let king_factor = (6.0 + 3.0 * depolarization_ratio) / (6.0 - 7.0 * depolarization_ratio);
+
+    8.0 * std::f64::consts::PI.powi(3) * n2 * n2 / (3.0 * molecular_density * lambda.powi(4)) * king_factor
+}
+
+//Wyman et al. 2013, "Simple Analytic Approximations to the CIE XYZ Color Matching Functions", multi-lobe fit
+fn cie_xyz(wavelength: f64) -> [f64; 3] {
+    let g = |mu: f64, sigma_low: f64, sigma_high: f64| {
+        let t = (wavelength - mu) / if wavelength < mu { sigma_low } else { sigma_high };
+        (-0.5 * t * t).exp()
+    };
+
+    [
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8)
+    ]
+}
+
+fn xyz_to_linear_srgb(xyz: [f64; 3]) -> [f64; 3] {
+    [
+        3.2406 * xyz[0] - 1.5372 * xyz[1] - 0.4986 * xyz[2],
+        -0.9689 * xyz[0] + 1.8758 * xyz[1] + 0.0415 * xyz[2],
+        0.0557 * xyz[0] - 0.2040 * xyz[1] + 1.0570 * xyz[2]
+    ]
+}
+
+fn integrate_cie(start: f64, end: f64, step: f64, coefficient: impl Fn(f64) -> f64) -> [f64; 3] {
+    let mut xyz = [0.0; 3];
+
+    let sample_count = ((end - start) / step).floor() as usize + 1;
+    for i in 0..sample_count {
+        let wavelength = start + i as f64 * step;
+        let value = coefficient(wavelength);
+        let matching = cie_xyz(wavelength);
+
+        for (channel, matching) in xyz.iter_mut().zip(matching) {
+            *channel += value * matching * step;
+        }
+    }
+
+    xyz_to_linear_srgb(xyz)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AIR_REFRACTIVE_INDEX: f64 = 1.0003;
+    const AIR_MOLECULAR_DENSITY: f64 = 2.545e25;
+    const VISIBLE: Spectrum = Spectrum::Cie { start: 380.0, end: 780.0, step: 5.0 };
+
+    fn air(wavelength: f64) -> f64 {
+        rayleigh_coefficient(AIR_REFRACTIVE_INDEX, AIR_MOLECULAR_DENSITY, AIR_DEPOLARIZATION_RATIO, wavelength)
+    }
+
+    //Bruneton 2017, "A Qualitative and Quantitative Evaluation of 8 Clear Sky Models", 13.558e-6 at 550nm sea level
+    #[test]
+    fn rayleigh_matches_sea_level_air() {
+        assert!((air(550.0) / 13.558e-6 - 1.0).abs() < 0.01, "{}", air(550.0));
+    }
+
+    //The Earth preset's coefficients are the inverse fourth power law at RGB_WAVELENGTHS, normalized to blue
+    #[test]
+    fn wavelengths_reproduce_earth_preset() {
+        let rgb = Spectrum::default().rgb(air);
+        let normalized = rgb / rgb.z;
+
+        assert!((normalized.x - 0.175287).abs() < 1e-4);
+        assert!((normalized.y - 0.409607).abs() < 1e-4);
+    }
+
+    #[test]
+    fn cie_keeps_constant_spectrum_gray_and_sky_blue() {
+        let gray = VISIBLE.rgb(|_| 0.5);
+        assert!((gray.x - 0.5).abs() < 1e-6 && (gray.y - 0.5).abs() < 1e-6 && (gray.z - 0.5).abs() < 1e-6, "{:?}", gray);
+
+        let sky = VISIBLE.rgb(air);
+        assert!(sky.x < sky.y && sky.y < sky.z, "{:?}", sky);
+    }
+}
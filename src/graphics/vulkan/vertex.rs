@@ -1,9 +1,9 @@
 use std::mem::size_of;
 
-use crate::utils::math::Zero;
 use vulkanalia::vk::{Format, HasBuilder, VertexInputAttributeDescription, VertexInputBindingDescription, VertexInputRate};
 
-pub type Vector2 = cgmath::Vector2<f32>;
+use crate::assets::vertex::Vertex;
+
 pub type Vector3 = cgmath::Vector3<f32>;
 
 //For uniform buffer data prefer over Vector3.
@@ -11,32 +11,8 @@ pub type Vector3 = cgmath::Vector3<f32>;
 //and alignment needs to be manually padded if Vector3 is used
 pub type Vector4 = cgmath::Vector4<f32>;
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug)]
-pub struct Vertex {
-    position: Vector4,
-    normal: Vector4,
-    color: Vector4,
-}
-
+//Vulkan specific
 impl Vertex {
-    pub const fn new(pos: Vector3, normal: Vector3, color: Vector4) -> Self {
-        Self {
-            position: Vector4::new(pos.x, pos.y, pos.z, 1.0),
-            normal: Vector4::new(normal.x, normal.y, normal.z, 0.0),
-            color,
-        }
-    }
-
-    pub fn with_pos(pos: Vector3) -> Self {
-        Vertex::new(pos, Vector3::zero(), Vector4::new(0., 0., 0., 1.))
-    }
-
-    pub fn with_pos_raw(x: f32, y: f32, z: f32) -> Self {
-        Vertex::new(Vector3::new(x, y, z), Vector3::zero(), Vector4::new(0., 0., 0., 1.))
-    }
-
-    //Vulkan specific
     pub fn binding_description() -> VertexInputBindingDescription {
         VertexInputBindingDescription::builder()
             .binding(0)
@@ -64,9 +40,16 @@ impl Vertex {
             .binding(0)
             .location(2)
             .format(Format::R32G32B32A32_SFLOAT)
-            .offset(size_of::<Vector4>() as u32)
+            .offset(2 * size_of::<Vector4>() as u32)
+            .build();
+
+        let tex_coord_attribute = VertexInputAttributeDescription::builder()
+            .binding(0)
+            .location(3)
+            .format(Format::R32G32_SFLOAT)
+            .offset(3 * size_of::<Vector4>() as u32)
             .build();
 
-        Vec::from([position_attribute, normal_attribute, color_attribute])
+        Vec::from([position_attribute, normal_attribute, color_attribute, tex_coord_attribute])
     }
 }
@@ -1,5 +1,6 @@
-use cgmath::Zero;
+use cgmath::{SquareMatrix, Zero};
 
+use crate::graphics::vulkan::transformation::Matrix4x4;
 use crate::graphics::vulkan::vertex::Vector4;
 
 #[repr(C)]
@@ -8,6 +9,8 @@ pub struct ViewState {
     pub world_camera_origin : Vector4,
     pub atmosphere_light_direction : Vector4,
     pub atmosphere_light_illuminance_outer_space : Vector4,
+    //Inverse of projection * view
+    pub clip_to_world : Matrix4x4,
 }
 
 impl Default for ViewState {
@@ -15,7 +18,8 @@ impl Default for ViewState {
         Self {
             world_camera_origin: Vector4::zero(),
             atmosphere_light_direction: Vector4::zero(),
-            atmosphere_light_illuminance_outer_space: Vector4::zero()
+            atmosphere_light_illuminance_outer_space: Vector4::zero(),
+            clip_to_world: Matrix4x4::identity()
         }
     }
 }
@@ -0,0 +1,549 @@
+use std::collections::HashMap;
+use std::fs;
+use std::mem::size_of;
+use std::ptr::copy_nonoverlapping;
+
+use anyhow::{anyhow, Result};
+use vulkanalia::{vk, Device};
+use vulkanalia::vk::{AccessFlags, Buffer, BufferMemoryBarrier, BufferUsageFlags, CommandBuffer, CommandBufferAllocateInfo, CommandBufferBeginInfo, CommandBufferLevel, CommandBufferUsageFlags, CommandPool, CommandPoolCreateFlags, CommandPoolCreateInfo, ComputePipelineCreateInfo, CopyDescriptorSet, DependencyFlags, DescriptorBufferInfo, DescriptorImageInfo, DescriptorPool, DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType, DeviceMemory, DeviceV1_0, Extent3D, Fence, Filter, Format, Handle, HasBuilder, Image, ImageAspectFlags, ImageLayout, ImageMemoryBarrier, ImageSubresourceRange, ImageTiling, ImageUsageFlags, ImageView, MemoryBarrier, MemoryMapFlags, MemoryPropertyFlags, Pipeline, PipelineBindPoint, PipelineCache, PipelineLayout, PipelineLayoutCreateInfo, PipelineShaderStageCreateInfo, PipelineStageFlags, Sampler, SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode, ShaderStageFlags, SubmitInfo, WriteDescriptorSet, QUEUE_FAMILY_IGNORED, WHOLE_SIZE};
+
+use crate::graphics::vulkan::atmopsheric_scattering::{AtmosphereSampleData, ScatteringMedium};
+use crate::graphics::vulkan::vulkan_rhi_data::VulkanRHIData;
+use crate::graphics::vulkan::vulkan_utils::{create_buffer, create_image, create_image_view, create_shader_module, get_memory_type_index, QueueFamilyIndices, RHIDestroy};
+
+pub(crate) const TRANSMITTANCE_LUT_EXTENT: Extent3D = Extent3D { width: 256, height: 64, depth: 1 };
+pub(crate) const MULTI_SCATTERING_LUT_EXTENT: Extent3D = Extent3D { width: 32, height: 32, depth: 1 };
+//Per frame, around the camera
+pub(crate) const SKY_VIEW_LUT_EXTENT: Extent3D = Extent3D { width: 192, height: 108, depth: 1 };
+pub(crate) const AERIAL_PERSPECTIVE_EXTENT: Extent3D = Extent3D { width: 32, height: 32, depth: 32 };
+
+const LUT_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+//Matches numthreads in the LUT compute shaders, 8x8x1
+const LUT_GROUP_SIZE: u32 = 8;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum AtmosphereLut {
+    Transmittance,
+    MultiScattering,
+    SkyView,
+    AerialPerspective,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct LutImage {
+    pub(crate) image: Image,
+    pub(crate) image_memory: DeviceMemory,
+    pub(crate) image_view: ImageView,
+    pub(crate) extent: Extent3D,
+}
+
+impl LutImage {
+    //2D unless extent has depth
+    pub fn create(rhi_data: &VulkanRHIData, extent: Extent3D) -> Result<Self> {
+        if extent.depth == 1 {
+            let (image, image_memory) = create_image(
+                rhi_data,
+                extent.width,
+                extent.height,
+                LUT_FORMAT,
+                ImageTiling::OPTIMAL,
+                ImageUsageFlags::STORAGE | ImageUsageFlags::SAMPLED,
+                MemoryPropertyFlags::DEVICE_LOCAL
+            )?;
+
+            let image_view = create_image_view(rhi_data, image, LUT_FORMAT, ImageAspectFlags::COLOR)?;
+
+            return Ok(Self { image, image_memory, image_view, extent });
+        }
+
+        let logical_device = &rhi_data.logical_device;
+
+        let info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::_3D)
+            .extent(extent)
+            .mip_levels(1)
+            .array_layers(1)
+            .format(LUT_FORMAT)
+            .tiling(ImageTiling::OPTIMAL)
+            .initial_layout(ImageLayout::UNDEFINED)
+            .usage(ImageUsageFlags::STORAGE | ImageUsageFlags::SAMPLED)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(vk::SampleCountFlags::_1);
+
+        let image = unsafe { logical_device.create_image(&info, None) }?;
+
+        let requirements = unsafe { logical_device.get_image_memory_requirements(image) };
+
+        let info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(requirements.size)
+            .memory_type_index(get_memory_type_index(rhi_data, MemoryPropertyFlags::DEVICE_LOCAL, requirements)?);
+
+        let image_memory = unsafe { logical_device.allocate_memory(&info, None) }?;
+
+        unsafe { logical_device.bind_image_memory(image, image_memory, 0) }?;
+
+        let info = vk::ImageViewCreateInfo::builder()
+            .image(image)
+            .view_type(vk::ImageViewType::_3D)
+            .format(LUT_FORMAT)
+            .subresource_range(Self::subresource_range());
+
+        let image_view = unsafe { logical_device.create_image_view(&info, None) }?;
+
+        Ok(Self { image, image_memory, image_view, extent })
+    }
+
+    fn subresource_range() -> ImageSubresourceRange {
+        ImageSubresourceRange::builder()
+            .aspect_mask(ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1)
+            .build()
+    }
+
+    fn barrier(&self, old_layout: ImageLayout, new_layout: ImageLayout, src_access: AccessFlags, dst_access: AccessFlags) -> ImageMemoryBarrier {
+        ImageMemoryBarrier::builder()
+            .old_layout(old_layout)
+            .new_layout(new_layout)
+            .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .image(self.image)
+            .subresource_range(Self::subresource_range())
+            .src_access_mask(src_access)
+            .dst_access_mask(dst_access)
+            .build()
+    }
+
+    //Dispatch size for 8x8x1 thread groups
+    fn group_count(&self) -> (u32, u32, u32) {
+        (self.extent.width.div_ceil(LUT_GROUP_SIZE), self.extent.height.div_ceil(LUT_GROUP_SIZE), self.extent.depth)
+    }
+
+    fn destroy(&self, rhi_data: &VulkanRHIData) {
+        let logical_device = &rhi_data.logical_device;
+
+        unsafe {
+            logical_device.destroy_image_view(self.image_view, None);
+            logical_device.destroy_image(self.image, None);
+            logical_device.free_memory(self.image_memory, None);
+        }
+    }
+}
+
+//Sky-view LUT and aerial perspective volume, regenerated every frame for the current camera.
+//Both stay in GENERAL layout since the same descriptor set writes and samples them
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct AtmosphereViewLuts {
+    pub(crate) sky_view: LutImage,
+    pub(crate) aerial_perspective: LutImage,
+}
+
+impl AtmosphereViewLuts {
+    pub fn create(rhi_data: &VulkanRHIData) -> Result<Self> {
+        Ok(Self {
+            sky_view: LutImage::create(rhi_data, SKY_VIEW_LUT_EXTENT)?,
+            aerial_perspective: LutImage::create(rhi_data, AERIAL_PERSPECTIVE_EXTENT)?,
+        })
+    }
+
+    //Expects the descriptor set the pipelines were created for to be bound
+    pub fn record(&self, logical_device: &Device, command_buffer: CommandBuffer, pipelines: &HashMap<AtmosphereLut, Pipeline>) {
+        //Previous frame contents are discarded
+        let to_general = [self.sky_view, self.aerial_perspective]
+            .map(|lut| lut.barrier(ImageLayout::UNDEFINED, ImageLayout::GENERAL, AccessFlags::SHADER_READ, AccessFlags::SHADER_WRITE));
+
+        let written = [self.sky_view, self.aerial_perspective]
+            .map(|lut| lut.barrier(ImageLayout::GENERAL, ImageLayout::GENERAL, AccessFlags::SHADER_WRITE, AccessFlags::SHADER_READ));
+
+        unsafe {
+            logical_device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::FRAGMENT_SHADER, PipelineStageFlags::COMPUTE_SHADER,
+                                                DependencyFlags::empty(), &[] as &[MemoryBarrier], &[] as &[BufferMemoryBarrier], &to_general);
+
+            for (lut, image) in [(AtmosphereLut::SkyView, self.sky_view), (AtmosphereLut::AerialPerspective, self.aerial_perspective)] {
+                let (x, y, z) = image.group_count();
+                logical_device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::COMPUTE, pipelines[&lut]);
+                logical_device.cmd_dispatch(command_buffer, x, y, z);
+            }
+
+            logical_device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::COMPUTE_SHADER, PipelineStageFlags::FRAGMENT_SHADER,
+                                                DependencyFlags::empty(), &[] as &[MemoryBarrier], &[] as &[BufferMemoryBarrier], &written);
+        }
+    }
+
+    pub fn destroy(&self, rhi_data: &VulkanRHIData) {
+        self.sky_view.destroy(rhi_data);
+        self.aerial_perspective.destroy(rhi_data);
+    }
+}
+
+//Transmittance and multi-scattering LUTs, generated by compute shaders and sampled by the atmosphere fragment shader
+#[derive(Debug, Default)]
+pub(crate) struct AtmosphereLutData {
+    pub(crate) transmittance: LutImage,
+    pub(crate) multi_scattering: LutImage,
+    pub(crate) sampler: Sampler,
+
+    command_pool: CommandPool,
+
+    descriptor_set_layout: DescriptorSetLayout,
+    descriptor_pool: DescriptorPool,
+    /*  Matches the graphics bindings where shared:
+         2 - atmospheric scattering medium
+         3 - atmospheric scattering sample data
+         4 - transmittance LUT (sampled)
+         6 - transmittance LUT (storage)
+         7 - multi-scattering LUT (storage)
+    */
+    descriptor_set: DescriptorSet,
+
+    pipeline_layout: PipelineLayout,
+    pipelines: HashMap<AtmosphereLut, Pipeline>,
+
+    //0 - atmospheric scattering medium, 1 - atmospheric scattering sample data
+    uniform_buffers: [Buffer; 2],
+    uniform_buffers_memory: [DeviceMemory; 2],
+
+    //Parameters the LUTs were last generated with
+    generated_for: Option<(AtmosphereSampleData, ScatteringMedium)>,
+}
+
+impl AtmosphereLutData {
+    //Light direction and intensity are not baked into the LUTs
+    pub fn is_outdated(&self, sample_data: &AtmosphereSampleData, medium: &ScatteringMedium) -> bool {
+        match &self.generated_for {
+            None => true,
+            Some((generated_sample_data, generated_medium)) => {
+                generated_medium != medium
+                    || generated_sample_data.planet_radius != sample_data.planet_radius
+                    || generated_sample_data.atmosphere_thickness != sample_data.atmosphere_thickness
+                    || generated_sample_data.sample_count_light != sample_data.sample_count_light
+            }
+        }
+    }
+
+    pub fn generate(&mut self, rhi_data: &VulkanRHIData, sample_data: &AtmosphereSampleData, medium: &ScatteringMedium) -> Result<()> {
+        let logical_device = &rhi_data.logical_device;
+
+        //LUTs may still be sampled by frames in flight
+        unsafe { logical_device.device_wait_idle() }?;
+
+        unsafe {
+            let memory = logical_device.map_memory(self.uniform_buffers_memory[0], 0, size_of::<ScatteringMedium>() as u64, MemoryMapFlags::empty())?;
+            copy_nonoverlapping(medium, memory.cast(), 1);
+            logical_device.unmap_memory(self.uniform_buffers_memory[0]);
+
+            let memory = logical_device.map_memory(self.uniform_buffers_memory[1], 0, size_of::<AtmosphereSampleData>() as u64, MemoryMapFlags::empty())?;
+            copy_nonoverlapping(sample_data, memory.cast(), 1);
+            logical_device.unmap_memory(self.uniform_buffers_memory[1]);
+        }
+
+        let allocate_info = CommandBufferAllocateInfo::builder()
+            .level(CommandBufferLevel::PRIMARY)
+            .command_pool(self.command_pool)
+            .command_buffer_count(1);
+
+        let command_buffer = unsafe { logical_device.allocate_command_buffers(&allocate_info) }?[0];
+        let begin_info = CommandBufferBeginInfo::builder()
+            .flags(CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+        //Previous contents are discarded
+        let to_general = [self.transmittance, self.multi_scattering]
+            .map(|lut| lut.barrier(ImageLayout::UNDEFINED, ImageLayout::GENERAL, AccessFlags::empty(), AccessFlags::SHADER_WRITE));
+
+        //Multi-scattering pass samples the transmittance LUT
+        let transmittance_written = self.transmittance.barrier(ImageLayout::GENERAL, ImageLayout::GENERAL,
+                                                               AccessFlags::SHADER_WRITE, AccessFlags::SHADER_READ);
+
+        let to_read_only = [self.transmittance, self.multi_scattering]
+            .map(|lut| lut.barrier(ImageLayout::GENERAL, ImageLayout::SHADER_READ_ONLY_OPTIMAL, AccessFlags::SHADER_WRITE, AccessFlags::SHADER_READ));
+
+        unsafe {
+            logical_device.begin_command_buffer(command_buffer, &begin_info)?;
+
+            logical_device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::TOP_OF_PIPE, PipelineStageFlags::COMPUTE_SHADER,
+                                                DependencyFlags::empty(), &[] as &[MemoryBarrier], &[] as &[BufferMemoryBarrier], &to_general);
+
+            logical_device.cmd_bind_descriptor_sets(command_buffer, PipelineBindPoint::COMPUTE, self.pipeline_layout, 0, &[self.descriptor_set], &[]);
+
+            logical_device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::COMPUTE, self.pipelines[&AtmosphereLut::Transmittance]);
+            let (x, y, z) = self.transmittance.group_count();
+            logical_device.cmd_dispatch(command_buffer, x, y, z);
+
+            logical_device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::COMPUTE_SHADER, PipelineStageFlags::COMPUTE_SHADER,
+                                                DependencyFlags::empty(), &[] as &[MemoryBarrier], &[] as &[BufferMemoryBarrier], &[transmittance_written]);
+
+            logical_device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::COMPUTE, self.pipelines[&AtmosphereLut::MultiScattering]);
+            let (x, y, z) = self.multi_scattering.group_count();
+            logical_device.cmd_dispatch(command_buffer, x, y, z);
+
+            logical_device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::COMPUTE_SHADER, PipelineStageFlags::FRAGMENT_SHADER,
+                                                DependencyFlags::empty(), &[] as &[MemoryBarrier], &[] as &[BufferMemoryBarrier], &to_read_only);
+
+            logical_device.end_command_buffer(command_buffer)?;
+
+            let command_buffers = &[command_buffer];
+            let submit_info = SubmitInfo::builder()
+                .command_buffers(command_buffers);
+
+            logical_device.queue_submit(rhi_data.graphics_queue, &[submit_info], Fence::null())?;
+            logical_device.queue_wait_idle(rhi_data.graphics_queue)?;
+
+            logical_device.free_command_buffers(self.command_pool, &[command_buffer]);
+        }
+
+        self.generated_for = Some((*sample_data, *medium));
+
+        Ok(())
+    }
+}
+
+impl RHIDestroy for AtmosphereLutData {
+    fn destroy(&mut self, rhi_data: &VulkanRHIData) {
+        let logical_device = &rhi_data.logical_device;
+
+        unsafe {
+            self.pipelines.values().for_each(|p| logical_device.destroy_pipeline(*p, None));
+            self.pipelines.clear();
+            logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
+
+            logical_device.destroy_descriptor_pool(self.descriptor_pool, None);
+            logical_device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+
+            for i in 0..self.uniform_buffers.len() {
+                logical_device.destroy_buffer(self.uniform_buffers[i], None);
+                logical_device.free_memory(self.uniform_buffers_memory[i], None);
+            }
+
+            logical_device.destroy_sampler(self.sampler, None);
+
+
+            logical_device.destroy_command_pool(self.command_pool, None);
+        }
+
+        self.transmittance.destroy(rhi_data);
+        self.multi_scattering.destroy(rhi_data);
+
+        self.generated_for = None;
+    }
+}
+
+pub struct AtmosphereLutDataBuilder<'a> {
+    value: AtmosphereLutData,
+    rhi_data: &'a VulkanRHIData,
+    shaders: HashMap<AtmosphereLut, &'a str>,
+}
+
+impl<'a> AtmosphereLutDataBuilder<'a> {
+    pub fn new(rhi_data: &'a VulkanRHIData) -> Self {
+        Self {
+            rhi_data,
+            value: AtmosphereLutData::default(),
+            shaders: HashMap::new()
+        }
+    }
+
+    pub fn shader(mut self, lut: AtmosphereLut, shader_path: &'a str) -> Self {
+        self.shaders.insert(lut, shader_path);
+        self
+    }
+
+    //LUT contents are undefined until `generate` is called
+    pub fn build(mut self) -> Result<AtmosphereLutData> {
+        self.value.transmittance = LutImage::create(self.rhi_data, TRANSMITTANCE_LUT_EXTENT)?;
+        self.value.multi_scattering = LutImage::create(self.rhi_data, MULTI_SCATTERING_LUT_EXTENT)?;
+        self.create_sampler()?;
+        self.create_command_pool()?;
+
+        self.create_uniform_buffers()?;
+        self.create_descriptor_set_layout()?;
+        self.create_descriptor_pool()?;
+        self.create_descriptor_set()?;
+        self.create_pipelines()?;
+
+        Ok(self.value)
+    }
+
+    fn create_sampler(&mut self) -> Result<()> {
+        let info = SamplerCreateInfo::builder()
+            .mag_filter(Filter::LINEAR)
+            .min_filter(Filter::LINEAR)
+            .mipmap_mode(SamplerMipmapMode::NEAREST)
+            .address_mode_u(SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(SamplerAddressMode::CLAMP_TO_EDGE)
+            .min_lod(0.0)
+            .max_lod(0.0)
+            ;
+
+        self.value.sampler = unsafe { self.rhi_data.logical_device.create_sampler(&info, None) }?;
+
+        Ok(())
+    }
+
+    //Graphics queue families on the hardware we target support compute as well
+    fn create_command_pool(&mut self) -> Result<()> {
+        let indices = QueueFamilyIndices::get(&self.rhi_data.instance, self.rhi_data.physical_device, self.rhi_data.surface)?;
+
+        let create_info = CommandPoolCreateInfo::builder()
+            .queue_family_index(indices.graphics)
+            .flags(CommandPoolCreateFlags::TRANSIENT)
+            ;
+
+        self.value.command_pool = unsafe { self.rhi_data.logical_device.create_command_pool(&create_info, None) }?;
+
+        Ok(())
+    }
+
+    fn create_uniform_buffers(&mut self) -> Result<()> {
+        let sizes = [size_of::<ScatteringMedium>() as u64, size_of::<AtmosphereSampleData>() as u64];
+
+        for (i, size) in sizes.into_iter().enumerate() {
+            let (buffer, buffer_memory) = create_buffer(self.rhi_data, size, BufferUsageFlags::UNIFORM_BUFFER,
+                                                        MemoryPropertyFlags::HOST_COHERENT | MemoryPropertyFlags::HOST_VISIBLE)?;
+
+            self.value.uniform_buffers[i] = buffer;
+            self.value.uniform_buffers_memory[i] = buffer_memory;
+        }
+
+        Ok(())
+    }
+
+    fn create_descriptor_set_layout(&mut self) -> Result<()> {
+        let binding = |binding: u32, descriptor_type: DescriptorType| {
+            DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(descriptor_type)
+                .descriptor_count(1)
+                .stage_flags(ShaderStageFlags::COMPUTE)
+                .build()
+        };
+
+        let bindings = &[
+            binding(2, DescriptorType::UNIFORM_BUFFER),
+            binding(3, DescriptorType::UNIFORM_BUFFER),
+            binding(4, DescriptorType::COMBINED_IMAGE_SAMPLER),
+            binding(6, DescriptorType::STORAGE_IMAGE),
+            binding(7, DescriptorType::STORAGE_IMAGE),
+        ];
+
+        let info = DescriptorSetLayoutCreateInfo::builder()
+            .bindings(bindings)
+        ;
+
+        self.value.descriptor_set_layout = unsafe { self.rhi_data.logical_device.create_descriptor_set_layout(&info, None) }?;
+        Ok(())
+    }
+
+    fn create_descriptor_pool(&mut self) -> Result<()> {
+        let pool_sizes = &[
+            DescriptorPoolSize::builder()
+                .type_(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(2),
+            DescriptorPoolSize::builder()
+                .type_(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1),
+            DescriptorPoolSize::builder()
+                .type_(DescriptorType::STORAGE_IMAGE)
+                .descriptor_count(2),
+        ];
+
+        let info = DescriptorPoolCreateInfo::builder()
+            .pool_sizes(pool_sizes)
+            .max_sets(1);
+
+        self.value.descriptor_pool = unsafe { self.rhi_data.logical_device.create_descriptor_pool(&info, None) }?;
+
+        Ok(())
+    }
+
+    fn create_descriptor_set(&mut self) -> Result<()> {
+        let layouts = &[self.value.descriptor_set_layout];
+        let info = DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.value.descriptor_pool)
+            .set_layouts(layouts)
+        ;
+
+        let descriptor_set = unsafe { self.rhi_data.logical_device.allocate_descriptor_sets(&info) }?[0];
+        self.value.descriptor_set = descriptor_set;
+
+        let buffer_infos = self.value.uniform_buffers
+            .map(|b| {
+                DescriptorBufferInfo::builder()
+                    .buffer(b)
+                    .offset(0)
+                    .range(WHOLE_SIZE as u64)
+                    .build()
+            });
+
+        //LUTs stay in GENERAL layout for the whole generation
+        let sampled_info = &[DescriptorImageInfo::builder()
+            .sampler(self.value.sampler)
+            .image_view(self.value.transmittance.image_view)
+            .image_layout(ImageLayout::GENERAL)];
+
+        let transmittance_info = &[DescriptorImageInfo::builder()
+            .image_view(self.value.transmittance.image_view)
+            .image_layout(ImageLayout::GENERAL)];
+
+        let multi_scattering_info = &[DescriptorImageInfo::builder()
+            .image_view(self.value.multi_scattering.image_view)
+            .image_layout(ImageLayout::GENERAL)];
+
+        let write = |binding: u32, descriptor_type: DescriptorType| {
+            WriteDescriptorSet::builder()
+                .dst_set(descriptor_set)
+                .dst_binding(binding)
+                .dst_array_element(0)
+                .descriptor_type(descriptor_type)
+        };
+
+        let write_infos = &[
+            write(2, DescriptorType::UNIFORM_BUFFER).buffer_info(&buffer_infos[0..=0]),
+            write(3, DescriptorType::UNIFORM_BUFFER).buffer_info(&buffer_infos[1..=1]),
+            write(4, DescriptorType::COMBINED_IMAGE_SAMPLER).image_info(sampled_info),
+            write(6, DescriptorType::STORAGE_IMAGE).image_info(transmittance_info),
+            write(7, DescriptorType::STORAGE_IMAGE).image_info(multi_scattering_info),
+        ];
+
+        unsafe { self.rhi_data.logical_device.update_descriptor_sets(write_infos, &[] as &[CopyDescriptorSet]) }
+
+        Ok(())
+    }
+
+    fn create_pipelines(&mut self) -> Result<()> {
+        let logical_device = &self.rhi_data.logical_device;
+
+        let layouts = &[self.value.descriptor_set_layout];
+        let layout_info = PipelineLayoutCreateInfo::builder()
+            .set_layouts(layouts)
+        ;
+
+        self.value.pipeline_layout = unsafe { logical_device.create_pipeline_layout(&layout_info, None) }?;
+
+        for lut in [AtmosphereLut::Transmittance, AtmosphereLut::MultiScattering] {
+            let shader_path = self.shaders.get(&lut).ok_or_else(|| anyhow!("Missing shader for {:?} LUT", lut))?;
+            let code = fs::read(shader_path)?;
+
+            let module = create_shader_module(self.rhi_data, &code[..])?;
+            let stage = PipelineShaderStageCreateInfo::builder()
+                .stage(ShaderStageFlags::COMPUTE)
+                .module(module)
+                .name(b"main\0");
+
+            let info = ComputePipelineCreateInfo::builder()
+                .stage(stage)
+                .layout(self.value.pipeline_layout)
+                ;
+
+            let pipeline = unsafe { logical_device.create_compute_pipelines(PipelineCache::null(), &[info], None) };
+
+            unsafe { logical_device.destroy_shader_module(module, None); }
+
+            self.value.pipelines.insert(lut, pipeline?.0[0]);
+        }
+
+        Ok(())
+    }
+}
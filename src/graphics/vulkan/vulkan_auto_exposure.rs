@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use std::fs;
+use std::mem::size_of;
+use std::ptr::write_bytes;
+use std::slice;
+
+use anyhow::{anyhow, Result};
+use vulkanalia::Device;
+use vulkanalia::vk::{AccessFlags, Buffer, BufferMemoryBarrier, BufferUsageFlags, CommandBuffer, ComputePipelineCreateInfo, CopyDescriptorSet, DependencyFlags, DescriptorBufferInfo, DescriptorImageInfo, DescriptorPool, DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType, DeviceMemory, DeviceSize, DeviceV1_0, Extent2D, Filter, Handle, HasBuilder, ImageLayout, ImageMemoryBarrier, MemoryBarrier, MemoryMapFlags, MemoryPropertyFlags, Pipeline, PipelineBindPoint, PipelineCache, PipelineLayout, PipelineLayoutCreateInfo, PipelineShaderStageCreateInfo, PipelineStageFlags, PushConstantRange, Sampler, SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode, ShaderStageFlags, WriteDescriptorSet, QUEUE_FAMILY_IGNORED, WHOLE_SIZE};
+
+use crate::config::config::Exposure;
+use crate::graphics::vulkan::vulkan_rhi_data::VulkanRHIData;
+use crate::graphics::vulkan::vulkan_swapchain::SwapchainData;
+use crate::graphics::vulkan::vulkan_utils::{create_buffer, create_shader_module, RHIDestroy};
+
+//Matches HISTOGRAM_BIN_COUNT in auto_exposure_common.hlsl
+const HISTOGRAM_BIN_COUNT: u64 = 256;
+//Matches numthreads in luminance_histogram_comp.hlsl, 16x16x1
+const HISTOGRAM_GROUP_SIZE: u32 = 16;
+//Matches ExposureState in auto_exposure_common.hlsl
+const EXPOSURE_STATE_SIZE: u64 = 4 * size_of::<f32>() as u64;
+//Average luminance of a frame exposed at EV100 0, log2(12.5 / 100)
+const EV100_ZERO_LOG_LUMINANCE: f32 = -3.0;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum AutoExposurePass {
+    //Luminance histogram of the HDR scene color
+    Histogram,
+    //Average of the histogram, blended with the previous exposure
+    Adaptation,
+}
+
+//Matches AutoExposureConstants in auto_exposure_common.hlsl
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AutoExposureConstants {
+    pub min_log_luminance: f32,
+    pub log_luminance_range: f32,
+    pub delta_time: f32,
+    pub adaptation_rate: f32,
+    pub min_ev100: f32,
+    pub max_ev100: f32,
+    pub compensation: f32,
+    pub pixel_count: u32,
+}
+
+impl AutoExposureConstants {
+    //None unless the exposure is automatic. The histogram covers exactly the allowed EV100 range
+    pub fn new(exposure: &Exposure, delta_time: f32, extent: Extent2D) -> Option<Self> {
+        match *exposure {
+            Exposure::Auto { min_ev100, max_ev100, adaptation_rate, compensation } => Some(Self {
+                min_log_luminance: min_ev100 + EV100_ZERO_LOG_LUMINANCE,
+                log_luminance_range: (max_ev100 - min_ev100).max(f32::EPSILON),
+                delta_time,
+                adaptation_rate,
+                min_ev100,
+                max_ev100,
+                compensation,
+                pixel_count: extent.width * extent.height
+            }),
+            _ => None
+        }
+    }
+}
+
+//Eye adaptation, computes the exposure multiplier read by the post-process pass from the HDR scene color.
+//The exposure state persists across frames, so the passes rely on frames being submitted in order
+#[derive(Debug, Default)]
+pub struct AutoExposureData {
+    histogram_buffer: Buffer,
+    histogram_buffer_memory: DeviceMemory,
+    pub(crate) exposure_buffer: Buffer,
+    exposure_buffer_memory: DeviceMemory,
+
+    sampler: Sampler,
+
+    descriptor_set_layout: DescriptorSetLayout,
+    descriptor_pool: DescriptorPool,
+    /*  Per swapchain image:
+         0 - HDR scene color
+         1 - luminance histogram
+         2 - exposure state
+    */
+    descriptor_sets: Vec<DescriptorSet>,
+
+    pipeline_layout: PipelineLayout,
+    pipelines: HashMap<AutoExposurePass, Pipeline>,
+}
+
+impl AutoExposureData {
+    //Must be recorded after the scene render pass and before the post-process pass
+    pub fn record(&self, logical_device: &Device, command_buffer: CommandBuffer, image_index: usize, extent: Extent2D, constants: AutoExposureConstants) {
+        let constants_bytes = unsafe { slice::from_raw_parts(&constants as *const AutoExposureConstants as *const u8, size_of::<AutoExposureConstants>()) };
+
+        //Previous frame's adaptation and post-process pass are done with both buffers
+        let previous_frame = [self.histogram_buffer, self.exposure_buffer]
+            .map(|b| Self::barrier(b, AccessFlags::SHADER_READ | AccessFlags::SHADER_WRITE, AccessFlags::SHADER_READ | AccessFlags::SHADER_WRITE));
+
+        let histogram_written = [Self::barrier(self.histogram_buffer, AccessFlags::SHADER_WRITE, AccessFlags::SHADER_READ | AccessFlags::SHADER_WRITE)];
+        let exposure_written = [Self::barrier(self.exposure_buffer, AccessFlags::SHADER_WRITE, AccessFlags::SHADER_READ)];
+
+        unsafe {
+            logical_device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::COMPUTE_SHADER | PipelineStageFlags::FRAGMENT_SHADER, PipelineStageFlags::COMPUTE_SHADER,
+                                                DependencyFlags::empty(), &[] as &[MemoryBarrier], &previous_frame, &[] as &[ImageMemoryBarrier]);
+
+            logical_device.cmd_bind_descriptor_sets(command_buffer, PipelineBindPoint::COMPUTE, self.pipeline_layout, 0, &[self.descriptor_sets[image_index]], &[]);
+            logical_device.cmd_push_constants(command_buffer, self.pipeline_layout, ShaderStageFlags::COMPUTE, 0, constants_bytes);
+
+            logical_device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::COMPUTE, self.pipelines[&AutoExposurePass::Histogram]);
+            logical_device.cmd_dispatch(command_buffer, extent.width.div_ceil(HISTOGRAM_GROUP_SIZE), extent.height.div_ceil(HISTOGRAM_GROUP_SIZE), 1);
+
+            logical_device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::COMPUTE_SHADER, PipelineStageFlags::COMPUTE_SHADER,
+                                                DependencyFlags::empty(), &[] as &[MemoryBarrier], &histogram_written, &[] as &[ImageMemoryBarrier]);
+
+            logical_device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::COMPUTE, self.pipelines[&AutoExposurePass::Adaptation]);
+            logical_device.cmd_dispatch(command_buffer, 1, 1, 1);
+
+            logical_device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::COMPUTE_SHADER, PipelineStageFlags::FRAGMENT_SHADER,
+                                                DependencyFlags::empty(), &[] as &[MemoryBarrier], &exposure_written, &[] as &[ImageMemoryBarrier]);
+        }
+    }
+
+    fn barrier(buffer: Buffer, src_access: AccessFlags, dst_access: AccessFlags) -> BufferMemoryBarrier {
+        BufferMemoryBarrier::builder()
+            .src_access_mask(src_access)
+            .dst_access_mask(dst_access)
+            .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .buffer(buffer)
+            .offset(0)
+            .size(WHOLE_SIZE as u64)
+            .build()
+    }
+}
+
+impl RHIDestroy for AutoExposureData {
+    fn destroy(&mut self, rhi_data: &VulkanRHIData) {
+        let logical_device = &rhi_data.logical_device;
+
+        unsafe {
+            self.pipelines.values().for_each(|p| logical_device.destroy_pipeline(*p, None));
+            self.pipelines.clear();
+            logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
+
+            logical_device.destroy_descriptor_pool(self.descriptor_pool, None);
+            logical_device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            self.descriptor_sets.clear();
+
+            logical_device.destroy_sampler(self.sampler, None);
+
+            logical_device.destroy_buffer(self.histogram_buffer, None);
+            logical_device.free_memory(self.histogram_buffer_memory, None);
+            logical_device.destroy_buffer(self.exposure_buffer, None);
+            logical_device.free_memory(self.exposure_buffer_memory, None);
+        }
+    }
+}
+
+pub struct AutoExposureDataBuilder<'a> {
+    value: AutoExposureData,
+    rhi_data: &'a VulkanRHIData,
+    swapchain_data: &'a SwapchainData,
+    shaders: HashMap<AutoExposurePass, &'a str>,
+}
+
+impl<'a> AutoExposureDataBuilder<'a> {
+    pub fn new(rhi_data: &'a VulkanRHIData, swapchain_data: &'a SwapchainData) -> Self {
+        Self {
+            rhi_data,
+            swapchain_data,
+            value: AutoExposureData::default(),
+            shaders: HashMap::new()
+        }
+    }
+
+    pub fn shader(mut self, pass: AutoExposurePass, shader_path: &'a str) -> Self {
+        self.shaders.insert(pass, shader_path);
+        self
+    }
+
+    pub fn build(mut self) -> Result<AutoExposureData> {
+        self.create_buffers()?;
+        self.create_sampler()?;
+        self.create_descriptor_set_layout()?;
+        self.create_descriptor_pool()?;
+        self.create_descriptor_sets()?;
+        self.create_pipelines()?;
+
+        Ok(self.value)
+    }
+
+    //Both start zeroed, an uninitialized exposure state snaps to the first frame's average
+    fn create_buffers(&mut self) -> Result<()> {
+        let (histogram_buffer, histogram_buffer_memory) = self.create_zeroed_buffer(HISTOGRAM_BIN_COUNT * size_of::<u32>() as u64)?;
+        let (exposure_buffer, exposure_buffer_memory) = self.create_zeroed_buffer(EXPOSURE_STATE_SIZE)?;
+
+        self.value.histogram_buffer = histogram_buffer;
+        self.value.histogram_buffer_memory = histogram_buffer_memory;
+        self.value.exposure_buffer = exposure_buffer;
+        self.value.exposure_buffer_memory = exposure_buffer_memory;
+
+        Ok(())
+    }
+
+    fn create_zeroed_buffer(&self, size: DeviceSize) -> Result<(Buffer, DeviceMemory)> {
+        let logical_device = &self.rhi_data.logical_device;
+
+        let (buffer, buffer_memory) = create_buffer(self.rhi_data, size, BufferUsageFlags::STORAGE_BUFFER,
+                                                    MemoryPropertyFlags::HOST_COHERENT | MemoryPropertyFlags::HOST_VISIBLE)?;
+
+        unsafe {
+            let memory = logical_device.map_memory(buffer_memory, 0, size, MemoryMapFlags::empty())?;
+            write_bytes(memory.cast::<u8>(), 0, size as usize);
+            logical_device.unmap_memory(buffer_memory);
+        }
+
+        Ok((buffer, buffer_memory))
+    }
+
+    fn create_sampler(&mut self) -> Result<()> {
+        //Only used for Load, which ignores the sampler state
+        let info = SamplerCreateInfo::builder()
+            .mag_filter(Filter::NEAREST)
+            .min_filter(Filter::NEAREST)
+            .mipmap_mode(SamplerMipmapMode::NEAREST)
+            .address_mode_u(SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(SamplerAddressMode::CLAMP_TO_EDGE)
+            .min_lod(0.0)
+            .max_lod(0.0)
+            ;
+
+        self.value.sampler = unsafe { self.rhi_data.logical_device.create_sampler(&info, None) }?;
+
+        Ok(())
+    }
+
+    fn create_descriptor_set_layout(&mut self) -> Result<()> {
+        let binding = |binding: u32, descriptor_type: DescriptorType| {
+            DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(descriptor_type)
+                .descriptor_count(1)
+                .stage_flags(ShaderStageFlags::COMPUTE)
+                .build()
+        };
+
+        let bindings = &[
+            binding(0, DescriptorType::COMBINED_IMAGE_SAMPLER),
+            binding(1, DescriptorType::STORAGE_BUFFER),
+            binding(2, DescriptorType::STORAGE_BUFFER),
+        ];
+
+        let info = DescriptorSetLayoutCreateInfo::builder()
+            .bindings(bindings)
+            ;
+
+        self.value.descriptor_set_layout = unsafe { self.rhi_data.logical_device.create_descriptor_set_layout(&info, None) }?;
+
+        Ok(())
+    }
+
+    fn create_descriptor_pool(&mut self) -> Result<()> {
+        let image_count = self.swapchain_data.hdr_image_views.len() as u32;
+
+        let pool_sizes = &[
+            DescriptorPoolSize::builder()
+                .type_(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(image_count),
+            DescriptorPoolSize::builder()
+                .type_(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(2 * image_count),
+        ];
+
+        let info = DescriptorPoolCreateInfo::builder()
+            .pool_sizes(pool_sizes)
+            .max_sets(image_count);
+
+        self.value.descriptor_pool = unsafe { self.rhi_data.logical_device.create_descriptor_pool(&info, None) }?;
+
+        Ok(())
+    }
+
+    fn create_descriptor_sets(&mut self) -> Result<()> {
+        let logical_device = &self.rhi_data.logical_device;
+
+        let layouts = vec![self.value.descriptor_set_layout; self.swapchain_data.hdr_image_views.len()];
+        let info = DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.value.descriptor_pool)
+            .set_layouts(&layouts)
+            ;
+
+        self.value.descriptor_sets = unsafe { logical_device.allocate_descriptor_sets(&info) }?;
+
+        let histogram_info = &[DescriptorBufferInfo::builder()
+            .buffer(self.value.histogram_buffer)
+            .offset(0)
+            .range(WHOLE_SIZE as u64)];
+
+        let exposure_info = &[DescriptorBufferInfo::builder()
+            .buffer(self.value.exposure_buffer)
+            .offset(0)
+            .range(WHOLE_SIZE as u64)];
+
+        for (descriptor_set, image_view) in self.value.descriptor_sets.iter().zip(self.swapchain_data.hdr_image_views.iter()) {
+            let image_info = &[DescriptorImageInfo::builder()
+                .sampler(self.value.sampler)
+                .image_view(*image_view)
+                .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)];
+
+            let write = |binding: u32, descriptor_type: DescriptorType| {
+                WriteDescriptorSet::builder()
+                    .dst_set(*descriptor_set)
+                    .dst_binding(binding)
+                    .dst_array_element(0)
+                    .descriptor_type(descriptor_type)
+            };
+
+            let writes = &[
+                write(0, DescriptorType::COMBINED_IMAGE_SAMPLER).image_info(image_info),
+                write(1, DescriptorType::STORAGE_BUFFER).buffer_info(histogram_info),
+                write(2, DescriptorType::STORAGE_BUFFER).buffer_info(exposure_info),
+            ];
+
+            unsafe { logical_device.update_descriptor_sets(writes, &[] as &[CopyDescriptorSet]) };
+        }
+
+        Ok(())
+    }
+
+    fn create_pipelines(&mut self) -> Result<()> {
+        let logical_device = &self.rhi_data.logical_device;
+
+        let push_constant_range = PushConstantRange::builder()
+            .stage_flags(ShaderStageFlags::COMPUTE)
+            .offset(0)
+            .size(size_of::<AutoExposureConstants>() as u32)
+            ;
+
+        let layouts = &[self.value.descriptor_set_layout];
+        let push_constant_ranges = &[push_constant_range];
+        let layout_info = PipelineLayoutCreateInfo::builder()
+            .set_layouts(layouts)
+            .push_constant_ranges(push_constant_ranges)
+            ;
+
+        self.value.pipeline_layout = unsafe { logical_device.create_pipeline_layout(&layout_info, None) }?;
+
+        for pass in [AutoExposurePass::Histogram, AutoExposurePass::Adaptation] {
+            let shader_path = self.shaders.get(&pass).ok_or_else(|| anyhow!("Missing shader for {:?} auto exposure pass", pass))?;
+            let code = fs::read(shader_path)?;
+
+            let module = create_shader_module(self.rhi_data, &code[..])?;
+            let stage = PipelineShaderStageCreateInfo::builder()
+                .stage(ShaderStageFlags::COMPUTE)
+                .module(module)
+                .name(b"main\0");
+
+            let info = ComputePipelineCreateInfo::builder()
+                .stage(stage)
+                .layout(self.value.pipeline_layout)
+                ;
+
+            let pipeline = unsafe { logical_device.create_compute_pipelines(PipelineCache::null(), &[info], None) };
+
+            unsafe { logical_device.destroy_shader_module(module, None); }
+
+            self.value.pipelines.insert(pass, pipeline?.0[0]);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_covers_ev100_range() {
+        let exposure = Exposure::Auto { min_ev100: -2.0, max_ev100: 14.0, adaptation_rate: 1.0, compensation: 0.0 };
+        let extent = Extent2D { width: 64, height: 32 };
+
+        let constants = AutoExposureConstants::new(&exposure, 0.016, extent).unwrap();
+
+        //EV100 = log2(L * 100 / 12.5) at both ends
+        let ev100 = |log_luminance: f32| log_luminance + (100.0f32 / 12.5).log2();
+        assert!((ev100(constants.min_log_luminance) - -2.0).abs() < 1e-5);
+        assert!((ev100(constants.min_log_luminance + constants.log_luminance_range) - 14.0).abs() < 1e-5);
+        assert_eq!(constants.pixel_count, 64 * 32);
+
+        assert_eq!(AutoExposureConstants::new(&Exposure::Ev100(10.0), 0.016, extent), None);
+    }
+}
@@ -0,0 +1,118 @@
+use std::ptr::copy_nonoverlapping;
+
+use anyhow::{anyhow, Result};
+use vulkanalia::vk::{AccessFlags, Buffer, BufferImageCopy, BufferMemoryBarrier, BufferUsageFlags, CommandBuffer, DependencyFlags, DeviceMemory, DeviceV1_0, Extent2D, Extent3D, Format, HasBuilder, Image, ImageAspectFlags, ImageLayout, ImageMemoryBarrier, ImageSubresourceLayers, ImageSubresourceRange, MemoryBarrier, MemoryMapFlags, MemoryPropertyFlags, Offset3D, PipelineStageFlags, QUEUE_FAMILY_IGNORED};
+
+use crate::graphics::frame_capture::FrameCapture;
+use crate::graphics::vulkan::vulkan_rhi_data::VulkanRHIData;
+use crate::graphics::vulkan::vulkan_utils::{create_buffer, RHIDestroy};
+
+//Copy of a frame's final color target in host visible memory. It is recorded into the frame's own command buffer,
+//after the post-process pass and before the image is presented
+pub(crate) struct FrameCaptureData {
+    buffer: Buffer,
+    memory: DeviceMemory,
+    extent: Extent2D,
+    format: Format,
+}
+
+impl FrameCaptureData {
+    //`layout` is the one the post-process pass leaves the image in, it is restored after the copy
+    pub fn record(rhi_data: &VulkanRHIData, command_buffer: CommandBuffer, image: Image, layout: ImageLayout, extent: Extent2D, format: Format) -> Result<Self> {
+        let logical_device = &rhi_data.logical_device;
+
+        let size = (extent.width * extent.height * 4) as u64;
+        let (buffer, memory) = create_buffer(rhi_data, size, BufferUsageFlags::TRANSFER_DST,
+                                             MemoryPropertyFlags::HOST_COHERENT | MemoryPropertyFlags::HOST_VISIBLE)?;
+
+        let subresource_range = ImageSubresourceRange::builder()
+            .aspect_mask(ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1);
+
+        let to_transfer = ImageMemoryBarrier::builder()
+            .old_layout(layout)
+            .new_layout(ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range)
+            .src_access_mask(AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_access_mask(AccessFlags::TRANSFER_READ);
+
+        //Presentation waits on the frame's semaphore, so no access has to be made visible to it
+        let from_transfer = ImageMemoryBarrier::builder()
+            .old_layout(ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .new_layout(layout)
+            .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range)
+            .src_access_mask(AccessFlags::TRANSFER_READ)
+            .dst_access_mask(AccessFlags::empty());
+
+        let to_host = BufferMemoryBarrier::builder()
+            .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .buffer(buffer)
+            .offset(0)
+            .size(size)
+            .src_access_mask(AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(AccessFlags::HOST_READ);
+
+        let subresource = ImageSubresourceLayers::builder()
+            .aspect_mask(ImageAspectFlags::COLOR)
+            .mip_level(0)
+            .base_array_layer(0)
+            .layer_count(1);
+
+        let region = BufferImageCopy::builder()
+            .buffer_offset(0)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(subresource)
+            .image_offset(Offset3D::default())
+            .image_extent(Extent3D { width: extent.width, height: extent.height, depth: 1 });
+
+        unsafe {
+            logical_device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT, PipelineStageFlags::TRANSFER,
+                                                DependencyFlags::empty(), &[] as &[MemoryBarrier], &[] as &[BufferMemoryBarrier], &[to_transfer]);
+
+            logical_device.cmd_copy_image_to_buffer(command_buffer, image, ImageLayout::TRANSFER_SRC_OPTIMAL, buffer, &[region]);
+
+            logical_device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::TRANSFER, PipelineStageFlags::HOST | PipelineStageFlags::BOTTOM_OF_PIPE,
+                                                DependencyFlags::empty(), &[] as &[MemoryBarrier], &[to_host], &[from_transfer]);
+        }
+
+        Ok(Self { buffer, memory, extent, format })
+    }
+
+    //The frame it was recorded into must have finished rendering
+    pub fn read(&self, rhi_data: &VulkanRHIData) -> Result<FrameCapture> {
+        let logical_device = &rhi_data.logical_device;
+        let mut pixels = vec![0u8; (self.extent.width * self.extent.height * 4) as usize];
+
+        unsafe {
+            let memory = logical_device.map_memory(self.memory, 0, pixels.len() as u64, MemoryMapFlags::empty())?;
+            copy_nonoverlapping(memory.cast::<u8>(), pixels.as_mut_ptr(), pixels.len());
+            logical_device.unmap_memory(self.memory);
+        }
+
+        match self.format {
+            Format::B8G8R8A8_SRGB | Format::B8G8R8A8_UNORM => Ok(FrameCapture::from_bgra(self.extent.width, self.extent.height, pixels)),
+            Format::R8G8B8A8_SRGB | Format::R8G8B8A8_UNORM => Ok(FrameCapture::from_rgba(self.extent.width, self.extent.height, pixels)),
+            format => Err(anyhow!("Frame capture is not supported for {:?}", format))
+        }
+    }
+}
+
+impl RHIDestroy for FrameCaptureData {
+    fn destroy(&mut self, rhi_data: &VulkanRHIData) {
+        unsafe {
+            rhi_data.logical_device.destroy_buffer(self.buffer, None);
+            rhi_data.logical_device.free_memory(self.memory, None);
+        }
+    }
+}
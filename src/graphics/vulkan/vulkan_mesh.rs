@@ -0,0 +1,92 @@
+use anyhow::{bail, Result};
+use vulkanalia::Device;
+use vulkanalia::vk::{Buffer, BufferUsageFlags, CommandBuffer, CommandPool, DescriptorSet, DescriptorSetLayout, DeviceMemory, DeviceV1_0, IndexType, PipelineBindPoint, PipelineLayout, Sampler, ShaderStageFlags};
+
+use crate::assets::material::Material;
+use crate::assets::mesh::Mesh;
+use crate::graphics::vulkan::push_constants::PushConstants;
+use crate::graphics::vulkan::transformation::Matrix4x4;
+use crate::graphics::vulkan::vulkan_rhi_data::VulkanRHIData;
+use crate::graphics::vulkan::vulkan_texture::GpuTexture;
+use crate::graphics::vulkan::vulkan_utils::{create_device_local_buffer, RHIDestroy};
+
+//Device-local vertex and index buffers of a Mesh, with its material
+#[derive(Debug, Default)]
+pub struct GpuMesh {
+    vertex_buffer: Buffer,
+    vertex_buffer_memory: DeviceMemory,
+    index_buffer: Buffer,
+    index_buffer_memory: DeviceMemory,
+    index_count: u32,
+    //Without the texture, which is uploaded to base_color_texture
+    material: Material,
+    base_color_texture: Option<GpuTexture>,
+}
+
+impl GpuMesh {
+    //Blocks until the upload is done. Vulkan has no empty buffers, so meshes without triangles are rejected.
+    //The base color texture is only uploaded when a layout and sampler for it are given
+    pub fn upload(rhi_data: &VulkanRHIData, command_pool: CommandPool, mesh: &Mesh, texture_set: Option<(DescriptorSetLayout, Sampler)>) -> Result<Self> {
+        if mesh.vertices.is_empty() || mesh.indices.is_empty() {
+            bail!("Mesh has no triangles");
+        }
+
+        let (vertex_buffer, vertex_buffer_memory) = create_device_local_buffer(rhi_data, command_pool, &mesh.vertices, BufferUsageFlags::VERTEX_BUFFER)?;
+        let (index_buffer, index_buffer_memory) = create_device_local_buffer(rhi_data, command_pool, &mesh.indices, BufferUsageFlags::INDEX_BUFFER)?;
+
+        let mut result = Self {
+            vertex_buffer,
+            vertex_buffer_memory,
+            index_buffer,
+            index_buffer_memory,
+            index_count: mesh.indices.len() as u32,
+            material: Material { base_color_texture: None, ..mesh.material.clone() },
+            base_color_texture: None,
+        };
+
+        if let (Some(texture), Some((layout, sampler))) = (&mesh.material.base_color_texture, texture_set) {
+            match GpuTexture::upload(rhi_data, command_pool, texture, layout, sampler) {
+                Ok(texture) => result.base_color_texture = Some(texture),
+                Err(e) => {
+                    result.destroy(rhi_data);
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    //Expects a pipeline using the Vertex layout and PushConstants to be bound. `default_texture` is bound as set 1
+    //when the mesh has no texture of its own
+    pub fn record_draw(&self, logical_device: &Device, command_buffer: CommandBuffer, pipeline_layout: PipelineLayout, model: Matrix4x4, default_texture: DescriptorSet) {
+        let texture = self.base_color_texture.as_ref().map_or(default_texture, |texture| texture.descriptor_set);
+        let constants = PushConstants::new(model, &self.material);
+
+        unsafe {
+            logical_device.cmd_bind_descriptor_sets(command_buffer, PipelineBindPoint::GRAPHICS, pipeline_layout, 1, &[texture], &[]);
+            logical_device.cmd_push_constants(command_buffer, pipeline_layout, ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT, 0, constants.as_bytes());
+
+            logical_device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer], &[0]);
+            logical_device.cmd_bind_index_buffer(command_buffer, self.index_buffer, 0, IndexType::UINT32);
+            logical_device.cmd_draw_indexed(command_buffer, self.index_count, 1, 0, 0, 0);
+        }
+    }
+}
+
+impl RHIDestroy for GpuMesh {
+    fn destroy(&mut self, rhi_data: &VulkanRHIData) {
+        let logical_device = &rhi_data.logical_device;
+
+        unsafe {
+            logical_device.destroy_buffer(self.vertex_buffer, None);
+            logical_device.free_memory(self.vertex_buffer_memory, None);
+            logical_device.destroy_buffer(self.index_buffer, None);
+            logical_device.free_memory(self.index_buffer_memory, None);
+        }
+
+        if let Some(mut texture) = self.base_color_texture.take() {
+            texture.destroy(rhi_data);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use vulkanalia::vk::{CommandPool, CommandPoolCreateFlags, CommandPoolCreateInfo, DescriptorSet, DescriptorSetLayout, DeviceV1_0, HasBuilder, Sampler};
+
+use crate::assets::material::Texture;
+use crate::assets::mesh::Mesh;
+use crate::graphics::rhi::MeshHandle;
+use crate::graphics::vulkan::vulkan_mesh::GpuMesh;
+use crate::graphics::vulkan::vulkan_rhi_data::VulkanRHIData;
+use crate::graphics::vulkan::vulkan_texture::{create_texture_sampler, create_texture_set_layout, GpuTexture};
+use crate::graphics::vulkan::vulkan_utils::{QueueFamilyIndices, RHIDestroy};
+
+//Uploaded meshes by handle. Independent of the swapchain, so it is kept when the swapchain is recreated
+#[derive(Debug, Default)]
+pub struct MeshRegistry {
+    //Transient pool for the staging copies
+    command_pool: CommandPool,
+    //Set 1 of the scene pipeline, the base color texture
+    texture_set_layout: DescriptorSetLayout,
+    texture_sampler: Sampler,
+    //Bound for meshes without a texture
+    default_texture: GpuTexture,
+    meshes: HashMap<MeshHandle, GpuMesh>,
+    next_handle: u32,
+}
+
+impl MeshRegistry {
+    pub fn create(rhi_data: &VulkanRHIData) -> Result<Self> {
+        let indices = QueueFamilyIndices::get(&rhi_data.instance, rhi_data.physical_device, rhi_data.surface)?;
+
+        let create_info = CommandPoolCreateInfo::builder()
+            .queue_family_index(indices.graphics)
+            .flags(CommandPoolCreateFlags::TRANSIENT)
+            ;
+
+        let command_pool = unsafe { rhi_data.logical_device.create_command_pool(&create_info, None) }?;
+        let texture_set_layout = create_texture_set_layout(rhi_data)?;
+        let texture_sampler = create_texture_sampler(rhi_data)?;
+        let default_texture = GpuTexture::upload(rhi_data, command_pool, &Texture::white(), texture_set_layout, texture_sampler)?;
+
+        Ok(Self {
+            command_pool,
+            texture_set_layout,
+            texture_sampler,
+            default_texture,
+            ..Default::default()
+        })
+    }
+
+    pub fn texture_set_layout(&self) -> DescriptorSetLayout {
+        self.texture_set_layout
+    }
+
+    pub fn default_texture(&self) -> DescriptorSet {
+        self.default_texture.descriptor_set
+    }
+
+    //Handles are never reused, the first one is MeshHandle::DEFAULT
+    pub fn register(&mut self, rhi_data: &VulkanRHIData, mesh: &Mesh) -> Result<MeshHandle> {
+        let gpu_mesh = GpuMesh::upload(rhi_data, self.command_pool, mesh, Some((self.texture_set_layout, self.texture_sampler)))?;
+
+        let handle = MeshHandle(self.next_handle);
+        self.next_handle += 1;
+        self.meshes.insert(handle, gpu_mesh);
+
+        Ok(handle)
+    }
+
+    pub fn get(&self, handle: MeshHandle) -> Option<&GpuMesh> {
+        self.meshes.get(&handle)
+    }
+
+    //Expects the mesh not to be in use by the GPU anymore
+    pub fn remove(&mut self, rhi_data: &VulkanRHIData, handle: MeshHandle) -> bool {
+        match self.meshes.remove(&handle) {
+            Some(mut gpu_mesh) => {
+                gpu_mesh.destroy(rhi_data);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl RHIDestroy for MeshRegistry {
+    fn destroy(&mut self, rhi_data: &VulkanRHIData) {
+        self.meshes.values_mut().for_each(|mesh| mesh.destroy(rhi_data));
+        self.meshes.clear();
+        self.default_texture.destroy(rhi_data);
+
+        unsafe {
+            rhi_data.logical_device.destroy_sampler(self.texture_sampler, None);
+            rhi_data.logical_device.destroy_descriptor_set_layout(self.texture_set_layout, None);
+            rhi_data.logical_device.destroy_command_pool(self.command_pool, None);
+        }
+    }
+}
@@ -2,29 +2,29 @@ use std::cmp::max;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fs;
-use std::intrinsics::copy_nonoverlapping;
 use std::mem::size_of;
 
-use anyhow::anyhow;
-use anyhow::Result;
-use vulkanalia::bytecode::Bytecode;
-use vulkanalia::vk::{AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentReference, AttachmentStoreOp, BlendFactor, BlendOp, Buffer, BufferCopy, BufferCreateInfo, BufferUsageFlags, ColorComponentFlags, CommandBuffer, CommandBufferAllocateInfo, CommandBufferBeginInfo, CommandBufferLevel, CommandBufferUsageFlags, CommandPool, CommandPoolCreateFlags, CommandPoolCreateInfo, CompareOp, CopyDescriptorSet, CullModeFlags, DescriptorBufferInfo, DescriptorPool, DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType, DeviceMemory, DeviceSize, DeviceV1_0, Fence, Format, Framebuffer, FramebufferCreateInfo, FrontFace, GraphicsPipelineCreateInfo, Handle, HasBuilder, ImageLayout, InstanceV1_0, LogicOp, MemoryAllocateInfo, MemoryMapFlags, MemoryPropertyFlags, MemoryRequirements, Offset2D, Pipeline, PipelineBindPoint, PipelineCache, PipelineColorBlendAttachmentState, PipelineColorBlendStateCreateInfo, PipelineDepthStencilStateCreateInfo, PipelineInputAssemblyStateCreateInfo, PipelineLayout, PipelineLayoutCreateInfo, PipelineMultisampleStateCreateInfo, PipelineRasterizationStateCreateInfo, PipelineShaderStageCreateInfo, PipelineStageFlags, PipelineVertexInputStateCreateInfo, PipelineViewportStateCreateInfo, PolygonMode, PrimitiveTopology, PushConstantRange, Rect2D, RenderPass, RenderPassCreateInfo, SampleCountFlags, ShaderModule, ShaderModuleCreateInfo, ShaderStageFlags, SharingMode, SubmitInfo, SubpassDependency, SubpassDescription, Viewport, WriteDescriptorSet, SUBPASS_EXTERNAL, WHOLE_SIZE};
+use anyhow::{anyhow, Result};
+use vulkanalia::vk::{AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentReference, AttachmentStoreOp, BlendFactor, BlendOp, Buffer, BufferUsageFlags, ColorComponentFlags, CommandBuffer, CommandBufferAllocateInfo, CommandBufferLevel, CommandPool, CommandPoolCreateFlags, CommandPoolCreateInfo, CompareOp, ComputePipelineCreateInfo, CopyDescriptorSet, CullModeFlags, DescriptorBufferInfo, DescriptorImageInfo, DescriptorPool, DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType, DeviceMemory, DeviceSize, DeviceV1_0, Format, Framebuffer, FramebufferCreateInfo, FrontFace, GraphicsPipelineCreateInfo, Handle, HasBuilder, ImageLayout, LogicOp, MemoryPropertyFlags, Offset2D, Pipeline, PipelineBindPoint, PipelineCache, PipelineColorBlendAttachmentState, PipelineColorBlendStateCreateInfo, PipelineDepthStencilStateCreateInfo, PipelineInputAssemblyStateCreateInfo, PipelineLayout, PipelineLayoutCreateInfo, PipelineMultisampleStateCreateInfo, PipelineRasterizationStateCreateInfo, PipelineShaderStageCreateInfo, PipelineStageFlags, PipelineVertexInputStateCreateInfo, PipelineViewportStateCreateInfo, PolygonMode, PrimitiveTopology, PushConstantRange, Rect2D, RenderPass, RenderPassCreateInfo, SampleCountFlags, ShaderModule, ShaderStageFlags, SubpassDependency, SubpassDescription, Viewport, WriteDescriptorSet, SUBPASS_EXTERNAL, WHOLE_SIZE};
 use vulkanalia::Device;
 
 use crate::graphics::vulkan::atmopsheric_scattering::{AtmosphereSampleData, ScatteringMedium};
 use crate::graphics::vulkan::push_constants::PushConstants;
+use crate::graphics::vulkan::vulkan_atmosphere_lut::{AtmosphereLut, AtmosphereLutData, AtmosphereViewLuts};
 use crate::graphics::vulkan::transformation::Transformation;
-use crate::graphics::vulkan::vertex::Vertex;
+use crate::assets::vertex::Vertex;
 use crate::graphics::vulkan::view_state::ViewState;
 use crate::graphics::vulkan::vulkan_rhi_data::VulkanRHIData;
-use crate::graphics::vulkan::vulkan_swapchain::SwapchainData;
-use crate::graphics::vulkan::vulkan_utils::{QueueFamilyIndices, RHIDestroy, INDICES, VERTICES};
+use crate::graphics::vulkan::vulkan_swapchain::{SwapchainData, HDR_FORMAT};
+use crate::graphics::vulkan::vulkan_utils::{create_buffer, create_shader_module, QueueFamilyIndices, RHIDestroy};
 
 #[derive(Debug, Default)]
 pub struct PipelineData {
     pub(crate) pipeline_layout: PipelineLayout,
     pub(crate) render_pass: RenderPass,
     pub(crate) pipeline: Pipeline,
+    //Atmosphere behind the scene, see PipelineDataBuilder::create_sky_pipeline
+    pub(crate) sky_pipeline: Pipeline,
     pub(crate) framebuffers: Vec<Framebuffer>,
 
     pub(crate) global_command_pool: CommandPool,
@@ -33,12 +33,6 @@ pub struct PipelineData {
     pub(crate) primary_command_buffers: Vec<CommandBuffer>,
     pub(crate) secondary_command_buffers: Vec<Vec<CommandBuffer>>,
 
-    pub(crate) vertex_buffer: Buffer,
-    pub(crate) vertex_buffer_memory: DeviceMemory,
-
-    pub(crate) index_buffer: Buffer,
-    pub(crate) index_buffer_memory: DeviceMemory,
-
     pub(crate) descriptor_set_layout: DescriptorSetLayout,
 
     /*  Fixed size:
@@ -46,10 +40,22 @@ pub struct PipelineData {
          1 - viewState
          2 - atmospheric scattering medium
          3 - atmospheric scattering sample data
+        Followed by the atmosphere LUTs (see AtmosphereLutData):
+         4 - transmittance LUT
+         5 - multi-scattering LUT
+        And the per frame atmosphere LUTs, sampled and written by the compute pipelines:
+         6 - sky-view LUT
+         7 - aerial perspective volume
+         8 - sky-view LUT (storage)
+         9 - aerial perspective volume (storage)
     */
     pub(crate) uniform_buffers: Vec<[Buffer; 4]>,
     pub(crate) uniform_buffers_memory: Vec<[DeviceMemory; 4]>,
 
+    //Per swapchain image, share the descriptor sets and pipeline layout above
+    pub(crate) atmosphere_view_luts: Vec<AtmosphereViewLuts>,
+    pub(crate) compute_pipelines: HashMap<AtmosphereLut, Pipeline>,
+
     pub(crate) descriptor_pool: DescriptorPool,
     pub(crate) descriptor_sets: Vec<DescriptorSet>,
 }
@@ -70,8 +76,16 @@ impl PipelineData {
             command_buffers.append(&mut new_buffers);
         }
 
-        let command_buffer = command_buffers[buffer_index];
-        command_buffer
+        command_buffers[buffer_index]
+    }
+
+    //Must be recorded outside of a render pass
+    pub fn record_atmosphere_view_luts(&self, logical_device: &Device, command_buffer: CommandBuffer, image_index: usize) {
+        unsafe {
+            logical_device.cmd_bind_descriptor_sets(command_buffer, PipelineBindPoint::COMPUTE, self.pipeline_layout, 0, &[self.descriptor_sets[image_index]], &[]);
+        }
+
+        self.atmosphere_view_luts[image_index].record(logical_device, command_buffer, &self.compute_pipelines);
     }
 }
 
@@ -94,12 +108,10 @@ impl RHIDestroy for PipelineData {
            logical_device.destroy_render_pass(self.render_pass, None);
            logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
            logical_device.destroy_pipeline(self.pipeline, None);
+           logical_device.destroy_pipeline(self.sky_pipeline, None);
 
-           logical_device.destroy_buffer(self.index_buffer, None);
-           logical_device.free_memory(self.index_buffer_memory, None);
-
-           logical_device.destroy_buffer(self.vertex_buffer, None);
-           logical_device.free_memory(self.vertex_buffer_memory, None);
+           self.compute_pipelines.values().for_each(|p| logical_device.destroy_pipeline(*p, None));
+           self.compute_pipelines.clear();
 
            logical_device.destroy_descriptor_pool(self.descriptor_pool, None);
 
@@ -115,6 +127,9 @@ impl RHIDestroy for PipelineData {
            self.uniform_buffers.clear();
            self.uniform_buffers_memory.clear();
        }
+
+       self.atmosphere_view_luts.iter().for_each(|luts| luts.destroy(rhi_data));
+       self.atmosphere_view_luts.clear();
    }
 }
 
@@ -123,7 +138,11 @@ pub struct PipelineDataBuilder<'a> {
     value: PipelineData,
     rhi_data: &'a VulkanRHIData,
     swapchain_data: &'a SwapchainData,
+    atmosphere_luts: Option<&'a AtmosphereLutData>,
+    texture_set_layout: Option<DescriptorSetLayout>,
     shaders: HashMap<ShaderStageFlags, &'a str>,
+    sky_shaders: HashMap<ShaderStageFlags, &'a str>,
+    compute_shaders: HashMap<AtmosphereLut, &'a str>,
 }
 
 impl<'a> PipelineDataBuilder<'a> {
@@ -131,8 +150,12 @@ impl<'a> PipelineDataBuilder<'a> {
         Self {
             rhi_data,
             swapchain_data,
+            atmosphere_luts: None,
+            texture_set_layout: None,
             value: PipelineData::default(),
-            shaders: HashMap::new()
+            shaders: HashMap::new(),
+            sky_shaders: HashMap::new(),
+            compute_shaders: HashMap::new()
         }
     }
 
@@ -141,15 +164,38 @@ impl<'a> PipelineDataBuilder<'a> {
         self
     }
 
+    pub fn sky_shader(mut self, stage: ShaderStageFlags, shader_path: &'a str) -> Self {
+        self.sky_shaders.insert(stage, shader_path);
+        self
+    }
+
+    //Per frame atmosphere LUT
+    pub fn compute_shader(mut self, lut: AtmosphereLut, shader_path: &'a str) -> Self {
+        self.compute_shaders.insert(lut, shader_path);
+        self
+    }
+
+    pub fn atmosphere_luts(mut self, atmosphere_luts: &'a AtmosphereLutData) -> Self {
+        self.atmosphere_luts = Some(atmosphere_luts);
+        self
+    }
+
+    //Set 1, bound per mesh. Owned by the caller
+    pub fn texture_set_layout(mut self, layout: DescriptorSetLayout) -> Self {
+        self.texture_set_layout = Some(layout);
+        self
+    }
+
     pub fn build(mut self) -> Result<PipelineData> {
         self.create_descriptor_set_layout()?;
         self.create_pipeline()?;
+        self.create_sky_pipeline()?;
+        self.create_compute_pipelines()?;
         self.create_framebuffers()?;
         self.create_command_pools()?;
 
-        self.create_vertex_buffer()?;
-        self.create_index_buffer()?;
         self.create_uniform_buffers()?;
+        self.create_atmosphere_view_luts()?;
         self.create_descriptor_pool()?;
         self.create_descriptor_sets()?;
         self.create_command_buffers()?;
@@ -163,13 +209,24 @@ impl<'a> PipelineDataBuilder<'a> {
 
         let logical_device = &self.rhi_data.logical_device;
 
-        let vert = fs::read(self.shaders[&ShaderStageFlags::VERTEX])?;
+        let texture_set_layout = self.texture_set_layout.ok_or_else(|| anyhow!("Missing texture set layout"))?;
 
-        let vert_module = self.create_shader_module(&vert[..])?;
-        let vert_stage = PipelineShaderStageCreateInfo::builder()
-            .stage(ShaderStageFlags::VERTEX)
-            .module(vert_module)
-            .name(b"main\0");
+        let push_constant_range = PushConstantRange::builder()
+            .stage_flags(ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(size_of::<PushConstants>() as u32)
+        ;
+
+        let layouts = &[self.value.descriptor_set_layout, texture_set_layout];
+        let push_constant_ranges = &[push_constant_range];
+        let layout_info = PipelineLayoutCreateInfo::builder()
+            .set_layouts(layouts)
+            .push_constant_ranges(push_constant_ranges)
+        ;
+
+        self.value.pipeline_layout = unsafe { logical_device.create_pipeline_layout(&layout_info, None) }?;
+
+        self.create_render_pass()?;
 
         //Create vertex input
         let binding_descriptions = &[Vertex::binding_description()];
@@ -179,7 +236,51 @@ impl<'a> PipelineDataBuilder<'a> {
             .vertex_attribute_descriptions(&attribute_descriptions)
             ;
 
-        let frag = fs::read(self.shaders[&ShaderStageFlags::FRAGMENT])?;
+        let depth_stencil_state = PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(true)
+            .depth_write_enable(true)
+            .depth_compare_op(CompareOp::LESS)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false);
+
+        self.value.pipeline = self.create_graphics_pipeline(&self.shaders, &vertex_input_state, &depth_stencil_state)?;
+
+        Ok(())
+    }
+
+    //Fullscreen triangle at the far plane, drawn after the scene so only uncovered pixels are shaded
+    fn create_sky_pipeline(&mut self) -> Result<()> {
+        assert!(self.sky_shaders.contains_key(&ShaderStageFlags::VERTEX));
+        assert!(self.sky_shaders.contains_key(&ShaderStageFlags::FRAGMENT));
+
+        //Vertices come from SV_VertexID
+        let vertex_input_state = PipelineVertexInputStateCreateInfo::builder();
+
+        let depth_stencil_state = PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(true)
+            .depth_write_enable(false)
+            .depth_compare_op(CompareOp::LESS_OR_EQUAL)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false);
+
+        self.value.sky_pipeline = self.create_graphics_pipeline(&self.sky_shaders, &vertex_input_state, &depth_stencil_state)?;
+
+        Ok(())
+    }
+
+    //Uses the shared pipeline layout and render pass
+    fn create_graphics_pipeline(&self, shaders: &HashMap<ShaderStageFlags, &str>, vertex_input_state: &PipelineVertexInputStateCreateInfo, depth_stencil_state: &PipelineDepthStencilStateCreateInfo) -> Result<Pipeline> {
+        let logical_device = &self.rhi_data.logical_device;
+
+        let vert = fs::read(shaders[&ShaderStageFlags::VERTEX])?;
+
+        let vert_module = self.create_shader_module(&vert[..])?;
+        let vert_stage = PipelineShaderStageCreateInfo::builder()
+            .stage(ShaderStageFlags::VERTEX)
+            .module(vert_module)
+            .name(b"main\0");
+
+        let frag = fs::read(shaders[&ShaderStageFlags::FRAGMENT])?;
 
         let frag_module = self.create_shader_module(&frag[..])?;
         let frag_stage = PipelineShaderStageCreateInfo::builder()
@@ -227,13 +328,6 @@ impl<'a> PipelineDataBuilder<'a> {
             .rasterization_samples(SampleCountFlags::_1)
             ;
 
-        let depth_stencil_state = PipelineDepthStencilStateCreateInfo::builder()
-            .depth_test_enable(true)
-            .depth_write_enable(true)
-            .depth_compare_op(CompareOp::LESS)
-            .depth_bounds_test_enable(false)
-            .stencil_test_enable(false);
-
         let color_blend_attachment = PipelineColorBlendAttachmentState::builder()
             .color_write_mask(ColorComponentFlags::all())
             .blend_enable(false)
@@ -253,33 +347,16 @@ impl<'a> PipelineDataBuilder<'a> {
             .blend_constants([0.0, 0.0, 0.0, 0.0])
             ;
 
-        let vert_push_constant_range = PushConstantRange::builder()
-            .stage_flags(ShaderStageFlags::VERTEX)
-            .offset(0)
-            .size(size_of::<PushConstants>() as u32)
-        ;
-
-        let layouts = &[self.value.descriptor_set_layout];
-        let push_constant_ranges = &[vert_push_constant_range];
-        let layout_info = PipelineLayoutCreateInfo::builder()
-            .set_layouts(layouts)
-            .push_constant_ranges(push_constant_ranges)
-        ;
-
-        self.value.pipeline_layout = unsafe { logical_device.create_pipeline_layout(&layout_info, None) }?;
-
-        self.create_render_pass()?;
-
         let stages = &[vert_stage, frag_stage];
         let pipeline_info = GraphicsPipelineCreateInfo::builder()
             .stages(stages)
-            .vertex_input_state(&vertex_input_state)
+            .vertex_input_state(vertex_input_state)
             .input_assembly_state(&input_assembly_state)
             .viewport_state(&viewport_state)
             .rasterization_state(&rasterization_state)
             .multisample_state(&multisample_state)
             .color_blend_state(&color_blend_state)
-            .depth_stencil_state(&depth_stencil_state)
+            .depth_stencil_state(depth_stencil_state)
             .layout(self.value.pipeline_layout)
             .render_pass(self.value.render_pass)
             .subpass(0)
@@ -287,38 +364,59 @@ impl<'a> PipelineDataBuilder<'a> {
             .base_pipeline_index(-1)
             ;
 
-        self.value.pipeline = unsafe { logical_device.create_graphics_pipelines(PipelineCache::null(), &[pipeline_info], None) }?.0[0];
+        let pipeline = unsafe { logical_device.create_graphics_pipelines(PipelineCache::null(), &[pipeline_info], None) };
 
         unsafe {
             logical_device.destroy_shader_module(vert_module, None);
             logical_device.destroy_shader_module(frag_module, None);
         }
 
-        Ok(())
+        Ok(pipeline?.0[0])
     }
 
-    fn create_shader_module(&self, bytecode: &[u8]) -> Result<ShaderModule> {
-        let bytecode = Bytecode::new(bytecode)?;
+    fn create_compute_pipelines(&mut self) -> Result<()> {
+        let logical_device = &self.rhi_data.logical_device;
 
-        let shader_info = ShaderModuleCreateInfo::builder()
-            .code_size(bytecode.code_size())
-            .code(bytecode.code())
-            ;
+        for lut in [AtmosphereLut::SkyView, AtmosphereLut::AerialPerspective] {
+            let shader_path = self.compute_shaders.get(&lut).ok_or_else(|| anyhow!("Missing shader for {:?} LUT", lut))?;
+            let code = fs::read(shader_path)?;
+
+            let module = self.create_shader_module(&code[..])?;
+            let stage = PipelineShaderStageCreateInfo::builder()
+                .stage(ShaderStageFlags::COMPUTE)
+                .module(module)
+                .name(b"main\0");
+
+            let info = ComputePipelineCreateInfo::builder()
+                .stage(stage)
+                .layout(self.value.pipeline_layout)
+                ;
+
+            let pipeline = unsafe { logical_device.create_compute_pipelines(PipelineCache::null(), &[info], None) };
+
+            unsafe { logical_device.destroy_shader_module(module, None); }
 
-        Ok(unsafe { self.rhi_data.logical_device.create_shader_module(&shader_info, None) }?)
+            self.value.compute_pipelines.insert(lut, pipeline?.0[0]);
+        }
+
+        Ok(())
+    }
+
+    fn create_shader_module(&self, bytecode: &[u8]) -> Result<ShaderModule> {
+        create_shader_module(self.rhi_data, bytecode)
     }
 
     fn create_render_pass(&mut self) -> Result<()> {
-        let swapchain_data = self.swapchain_data;
+        //Scene is rendered in HDR and resolved to the swapchain by PostProcessData
         let color_attachment = AttachmentDescription::builder()
-            .format(swapchain_data.swapchain_format)
+            .format(HDR_FORMAT)
             .samples(SampleCountFlags::_1)
             .load_op(AttachmentLoadOp::CLEAR)
             .store_op(AttachmentStoreOp::STORE)
             .stencil_load_op(AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(AttachmentStoreOp::DONT_CARE)
             .initial_layout(ImageLayout::UNDEFINED)
-            .final_layout(ImageLayout::PRESENT_SRC_KHR)
+            .final_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
             ;
 
         let color_attachment_ref = AttachmentReference::builder()
@@ -356,9 +454,19 @@ impl<'a> PipelineDataBuilder<'a> {
             .dst_access_mask(AccessFlags::COLOR_ATTACHMENT_WRITE | AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
             ;
 
+        //Auto exposure and post-processing sample the color target right after
+        let post_process_dependency = SubpassDependency::builder()
+            .src_subpass(0)
+            .dst_subpass(SUBPASS_EXTERNAL)
+            .src_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .src_access_mask(AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_stage_mask(PipelineStageFlags::COMPUTE_SHADER | PipelineStageFlags::FRAGMENT_SHADER)
+            .dst_access_mask(AccessFlags::SHADER_READ)
+            ;
+
         let attachments = &[color_attachment, depth_stencil_attachment];
         let subpasses = &[subpass];
-        let dependencies = &[dependency];
+        let dependencies = &[dependency, post_process_dependency];
         let render_pass_info = RenderPassCreateInfo::builder()
             .attachments(attachments)
             .subpasses(subpasses)
@@ -373,7 +481,7 @@ impl<'a> PipelineDataBuilder<'a> {
     fn create_framebuffers(&mut self) -> Result<()> {
         let swapchain_data = self.swapchain_data;
 
-        let framebuffers = swapchain_data.swapchain_image_views
+        let framebuffers = swapchain_data.hdr_image_views
             .iter()
             .map(|iv| {
                 let attachments = &[*iv, swapchain_data.depth_image_view];
@@ -435,134 +543,8 @@ impl<'a> PipelineDataBuilder<'a> {
         Ok(())
     }
 
-    //Vertex buffer
-    fn create_vertex_buffer(&mut self) -> Result<()> {
-        let logical_device = &self.rhi_data.logical_device;
-
-        let size = (size_of::<Vertex>() * VERTICES.len()) as u64;
-        let (staging_buffer, staging_buffer_memory) = self.create_buffer(size, BufferUsageFlags::TRANSFER_SRC, MemoryPropertyFlags::HOST_COHERENT | MemoryPropertyFlags::HOST_VISIBLE)?;
-
-        let app_memory = unsafe { logical_device.map_memory(staging_buffer_memory, 0, size, MemoryMapFlags::empty()) }?;
-
-        unsafe {
-            copy_nonoverlapping(VERTICES.as_ptr(), app_memory.cast(), VERTICES.len());
-            logical_device.unmap_memory(staging_buffer_memory);
-        }
-
-        let (buffer, buffer_memory) = self.create_buffer(size, BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::VERTEX_BUFFER, MemoryPropertyFlags::DEVICE_LOCAL)?;
-
-        self.value.vertex_buffer = buffer;
-        self.value.vertex_buffer_memory = buffer_memory;
-
-        self.copy_buffer(staging_buffer, self.value.vertex_buffer, size)?;
-
-        unsafe {
-            logical_device.destroy_buffer(staging_buffer, None);
-            logical_device.free_memory(staging_buffer_memory, None);
-        }
-
-        Ok(())
-    }
-
-    fn get_memory_type_index(&self, properties: MemoryPropertyFlags, requirements: MemoryRequirements) -> Result<u32> {
-        let instance = &self.rhi_data.instance;
-        let physical_device = self.rhi_data.physical_device;
-
-        let memory = unsafe { instance.get_physical_device_memory_properties(physical_device) };
-
-        (0..memory.memory_type_count)
-            .find(|i| {
-                let suitable = (requirements.memory_type_bits & (1u32 << i)) != 0;
-                let memory_type = memory.memory_types[*i as usize];
-                suitable && memory_type.property_flags.contains(properties)
-            })
-            .ok_or_else(|| anyhow!("Failed to find suitable memory type"))
-    }
-
     fn create_buffer(&self, size: DeviceSize, usage: BufferUsageFlags, properties: MemoryPropertyFlags) -> Result<(Buffer, DeviceMemory)> {
-        let logical_device = &self.rhi_data.logical_device;
-
-        let buffer_info = BufferCreateInfo::builder()
-            .size(size)
-            .usage(usage)
-            .sharing_mode(SharingMode::EXCLUSIVE)
-        ;
-
-        let buffer = unsafe { logical_device.create_buffer(&buffer_info, None) }?;
-        let requirements = unsafe { logical_device.get_buffer_memory_requirements(buffer) };
-
-        let memory_info = MemoryAllocateInfo::builder()
-            .allocation_size(requirements.size)
-            .memory_type_index(self.get_memory_type_index(properties, requirements)?);
-
-        let buffer_memory = unsafe { logical_device.allocate_memory(&memory_info, None) }?;
-
-        unsafe { logical_device.bind_buffer_memory(buffer, buffer_memory, 0) }?;
-
-        Ok((buffer, buffer_memory))
-    }
-
-    fn copy_buffer(&self, src: Buffer, dst: Buffer, size: DeviceSize) -> Result<()> {
-        let logical_device = &self.rhi_data.logical_device;
-
-        let info = CommandBufferAllocateInfo::builder()
-            .level(CommandBufferLevel::PRIMARY)
-            .command_pool(self.value.global_command_pool)
-            .command_buffer_count(1)
-        ;
-
-        let command_buffer = unsafe { logical_device.allocate_command_buffers(&info) }?[0];
-        let begin_info = CommandBufferBeginInfo::builder()
-            .flags(CommandBufferUsageFlags::ONE_TIME_SUBMIT);
-
-        unsafe { logical_device.begin_command_buffer(command_buffer, &begin_info) }?;
-
-            let regions = BufferCopy::builder().size(size);
-        unsafe { logical_device.cmd_copy_buffer(command_buffer, src, dst, &[regions]); }
-
-        unsafe { logical_device.end_command_buffer(command_buffer) }?;
-
-            let command_buffers = &[command_buffer];
-            let info = SubmitInfo::builder()
-                .command_buffers(command_buffers)
-            ;
-
-        unsafe { logical_device.queue_submit(self.rhi_data.graphics_queue, &[info], Fence::null()) }?;
-        unsafe { logical_device.queue_wait_idle(self.rhi_data.graphics_queue)}?;
-
-        unsafe { logical_device.free_command_buffers(self.value.global_command_pool, &[command_buffer]); }
-
-        Ok(())
-    }
-
-    //Index buffer
-    //ToDo: Unify with Vertex buffer creation
-    fn create_index_buffer(&mut self) -> Result<()> {
-        let logical_device = &self.rhi_data.logical_device;
-
-        let size = (size_of::<u16>() * INDICES.len()) as u64;
-        let (staging_buffer, staging_buffer_memory) = self.create_buffer(size, BufferUsageFlags::TRANSFER_SRC, MemoryPropertyFlags::HOST_COHERENT | MemoryPropertyFlags::HOST_VISIBLE)?;
-
-        let app_memory = unsafe { logical_device.map_memory(staging_buffer_memory, 0, size, MemoryMapFlags::empty()) }?;
-
-        unsafe {
-            copy_nonoverlapping(INDICES.as_ptr(), app_memory.cast(), INDICES.len());
-            logical_device.unmap_memory(staging_buffer_memory);
-        }
-
-        let (buffer, buffer_memory) = self.create_buffer(size, BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::INDEX_BUFFER, MemoryPropertyFlags::DEVICE_LOCAL)?;
-
-        self.value.index_buffer = buffer;
-        self.value.index_buffer_memory = buffer_memory;
-
-        self.copy_buffer(staging_buffer, self.value.index_buffer, size)?;
-
-        unsafe {
-            logical_device.destroy_buffer(staging_buffer, None);
-            logical_device.free_memory(staging_buffer_memory, None);
-        }
-
-        Ok(())
+        create_buffer(self.rhi_data, size, usage, properties)
     }
 
     //Uniform Buffers
@@ -571,27 +553,64 @@ impl<'a> PipelineDataBuilder<'a> {
             .binding(0)
             .descriptor_type(DescriptorType::UNIFORM_BUFFER)
             .descriptor_count(1)
-            .stage_flags(ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT);
+            .stage_flags(ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT | ShaderStageFlags::COMPUTE);
 
         let ubo1_binding = DescriptorSetLayoutBinding::builder()
             .binding(1)
             .descriptor_type(DescriptorType::UNIFORM_BUFFER)
             .descriptor_count(1)
-            .stage_flags(ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT);
+            .stage_flags(ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT | ShaderStageFlags::COMPUTE);
 
         let ubo2_binding = DescriptorSetLayoutBinding::builder()
             .binding(2)
             .descriptor_type(DescriptorType::UNIFORM_BUFFER)
             .descriptor_count(1)
-            .stage_flags(ShaderStageFlags::FRAGMENT);
+            .stage_flags(ShaderStageFlags::FRAGMENT | ShaderStageFlags::COMPUTE);
 
         let ubo3_binding = DescriptorSetLayoutBinding::builder()
             .binding(3)
             .descriptor_type(DescriptorType::UNIFORM_BUFFER)
             .descriptor_count(1)
+            .stage_flags(ShaderStageFlags::FRAGMENT | ShaderStageFlags::COMPUTE);
+
+        let transmittance_lut_binding = DescriptorSetLayoutBinding::builder()
+            .binding(4)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(ShaderStageFlags::FRAGMENT | ShaderStageFlags::COMPUTE);
+
+        let multi_scattering_lut_binding = DescriptorSetLayoutBinding::builder()
+            .binding(5)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(ShaderStageFlags::FRAGMENT | ShaderStageFlags::COMPUTE);
+
+        let sky_view_lut_binding = DescriptorSetLayoutBinding::builder()
+            .binding(6)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(ShaderStageFlags::FRAGMENT);
+
+        let aerial_perspective_binding = DescriptorSetLayoutBinding::builder()
+            .binding(7)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
             .stage_flags(ShaderStageFlags::FRAGMENT);
 
-        let bindings = &[ubo0_binding, ubo1_binding, ubo2_binding, ubo3_binding];
+        let sky_view_lut_storage_binding = DescriptorSetLayoutBinding::builder()
+            .binding(8)
+            .descriptor_type(DescriptorType::STORAGE_IMAGE)
+            .descriptor_count(1)
+            .stage_flags(ShaderStageFlags::COMPUTE);
+
+        let aerial_perspective_storage_binding = DescriptorSetLayoutBinding::builder()
+            .binding(9)
+            .descriptor_type(DescriptorType::STORAGE_IMAGE)
+            .descriptor_count(1)
+            .stage_flags(ShaderStageFlags::COMPUTE);
+
+        let bindings = &[ubo0_binding, ubo1_binding, ubo2_binding, ubo3_binding, transmittance_lut_binding, multi_scattering_lut_binding,
+            sky_view_lut_binding, aerial_perspective_binding, sky_view_lut_storage_binding, aerial_perspective_storage_binding];
         let info = DescriptorSetLayoutCreateInfo::builder()
             .bindings(bindings)
         ;
@@ -641,13 +660,31 @@ impl<'a> PipelineDataBuilder<'a> {
         Ok(())
     }
 
+    fn create_atmosphere_view_luts(&mut self) -> Result<()> {
+        for _swapchain_image in &self.swapchain_data.swapchain_images {
+            self.value.atmosphere_view_luts.push(AtmosphereViewLuts::create(self.rhi_data)?);
+        }
+
+        Ok(())
+    }
+
     fn create_descriptor_pool(&mut self) -> Result<()> {
         let pool_size = DescriptorPoolSize::builder()
             .type_(DescriptorType::UNIFORM_BUFFER)
             .descriptor_count((self.swapchain_data.swapchain_images.len() * 4) as u32)
         ;
 
-        let pool_sizes = &[pool_size];
+        let sampler_pool_size = DescriptorPoolSize::builder()
+            .type_(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count((self.swapchain_data.swapchain_images.len() * 4) as u32)
+        ;
+
+        let storage_pool_size = DescriptorPoolSize::builder()
+            .type_(DescriptorType::STORAGE_IMAGE)
+            .descriptor_count((self.swapchain_data.swapchain_images.len() * 2) as u32)
+        ;
+
+        let pool_sizes = &[pool_size, sampler_pool_size, storage_pool_size];
         let info = DescriptorPoolCreateInfo::builder()
             .pool_sizes(pool_sizes)
             .max_sets(self.swapchain_data.swapchain_images.len() as u32);
@@ -658,6 +695,8 @@ impl<'a> PipelineDataBuilder<'a> {
     }
 
     fn create_descriptor_sets(&mut self) -> Result<()> {
+        let atmosphere_luts = self.atmosphere_luts.ok_or_else(|| anyhow!("Atmosphere LUTs are required by the atmosphere shader"))?;
+
         let layouts = vec![self.value.descriptor_set_layout; self.swapchain_data.swapchain_images.len()];
         let info = DescriptorSetAllocateInfo::builder()
             .descriptor_pool(self.value.descriptor_pool)
@@ -679,7 +718,7 @@ impl<'a> PipelineDataBuilder<'a> {
                         })
                 .collect::<Vec<_>>();
 
-            let write_infos = buffers.iter()
+            let mut write_infos = buffers.iter()
                 .enumerate()
                 .map(|(i, _)|
                 {
@@ -692,6 +731,50 @@ impl<'a> PipelineDataBuilder<'a> {
                 })
                 .collect::<Vec<_>>();
 
+            let image_infos = [atmosphere_luts.transmittance, atmosphere_luts.multi_scattering]
+                .map(|lut| {
+                    DescriptorImageInfo::builder()
+                        .sampler(atmosphere_luts.sampler)
+                        .image_view(lut.image_view)
+                        .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                });
+
+            for (i, _) in image_infos.iter().enumerate() {
+                write_infos.push(WriteDescriptorSet::builder()
+                    .dst_set(descriptor_set)
+                    .dst_binding((buffers.len() + i) as u32)
+                    .dst_array_element(0)
+                    .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(&image_infos[i..=i]));
+            }
+
+            //Written by compute and sampled by fragment shaders, always in GENERAL layout
+            let view_luts = self.value.atmosphere_view_luts[i];
+            let view_lut_infos = [view_luts.sky_view, view_luts.aerial_perspective]
+                .map(|lut| {
+                    DescriptorImageInfo::builder()
+                        .sampler(atmosphere_luts.sampler)
+                        .image_view(lut.image_view)
+                        .image_layout(ImageLayout::GENERAL)
+                });
+
+            let first_view_lut_binding = buffers.len() + image_infos.len();
+            for (j, _) in view_lut_infos.iter().enumerate() {
+                write_infos.push(WriteDescriptorSet::builder()
+                    .dst_set(descriptor_set)
+                    .dst_binding((first_view_lut_binding + j) as u32)
+                    .dst_array_element(0)
+                    .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(&view_lut_infos[j..=j]));
+
+                write_infos.push(WriteDescriptorSet::builder()
+                    .dst_set(descriptor_set)
+                    .dst_binding((first_view_lut_binding + view_lut_infos.len() + j) as u32)
+                    .dst_array_element(0)
+                    .descriptor_type(DescriptorType::STORAGE_IMAGE)
+                    .image_info(&view_lut_infos[j..=j]));
+            }
+
             unsafe { self.rhi_data.logical_device.update_descriptor_sets(&write_infos, &[] as &[CopyDescriptorSet]) }
         }
 
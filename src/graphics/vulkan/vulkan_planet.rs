@@ -0,0 +1,102 @@
+use anyhow::Result;
+use cgmath::InnerSpace;
+use vulkanalia::Device;
+use vulkanalia::vk::{CommandBuffer, CommandPoolCreateFlags, CommandPoolCreateInfo, DescriptorSet, DeviceV1_0, HasBuilder, PipelineLayout};
+
+use crate::graphics::vulkan::atmopsheric_scattering::AtmosphereSampleData;
+use crate::assets::mesh::Mesh;
+use crate::graphics::vulkan::vulkan_mesh::GpuMesh;
+use crate::graphics::vulkan::transformation::Matrix4x4;
+use crate::graphics::vulkan::vertex::{Vector3, Vector4};
+use crate::graphics::vulkan::vulkan_rhi_data::VulkanRHIData;
+use crate::graphics::vulkan::vulkan_utils::{QueueFamilyIndices, RHIDestroy};
+
+//Icosphere subdivisions of each LOD, coarsest first. The finest has 163842 vertices
+const PLANET_LOD_SUBDIVISIONS: [u32; 6] = [2, 3, 4, 5, 6, 7];
+//Angle between neighbouring icosahedron vertices, halved by every subdivision
+const ICOSAHEDRON_EDGE_ANGLE: f32 = 1.107_148_7;
+//Triangle edges are kept below this fraction of the distance to the horizon
+const LOD_EDGE_TO_HORIZON: f32 = 0.02;
+//Linear, multiplied by the transmitted sunlight in basic_frag.hlsl
+const GROUND_ALBEDO: Vector4 = Vector4::new(0.3, 0.25, 0.2, 1.0);
+
+//Index into PLANET_LOD_SUBDIVISIONS for a camera at the given altitude above a planet of the given radius.
+//Picks the coarsest level whose edges are short compared to the distance to the horizon, close to the
+//surface that is always the finest one
+pub fn planet_lod(altitude: f32, radius: f32) -> usize {
+    let altitude = altitude.max(0.0);
+    let horizon_distance = (altitude * (2.0 * radius + altitude)).sqrt();
+
+    PLANET_LOD_SUBDIVISIONS
+        .iter()
+        .position(|&subdivisions| radius * ICOSAHEDRON_EDGE_ANGLE / 2.0f32.powi(subdivisions as i32) <= horizon_distance * LOD_EDGE_TO_HORIZON)
+        .unwrap_or(PLANET_LOD_SUBDIVISIONS.len() - 1)
+}
+
+//Planet surface under the atmosphere, sized and placed by AtmosphereSampleData.
+//Independent of the swapchain, so it is kept when the swapchain is recreated
+#[derive(Debug, Default)]
+pub struct PlanetData {
+    lods: Vec<GpuMesh>,
+}
+
+impl PlanetData {
+    pub fn create(rhi_data: &VulkanRHIData) -> Result<Self> {
+        let logical_device = &rhi_data.logical_device;
+        let indices = QueueFamilyIndices::get(&rhi_data.instance, rhi_data.physical_device, rhi_data.surface)?;
+
+        let create_info = CommandPoolCreateInfo::builder()
+            .queue_family_index(indices.graphics)
+            .flags(CommandPoolCreateFlags::TRANSIENT)
+            ;
+
+        let command_pool = unsafe { logical_device.create_command_pool(&create_info, None) }?;
+
+        let lods = PLANET_LOD_SUBDIVISIONS
+            .iter()
+            .map(|&subdivisions| GpuMesh::upload(rhi_data, command_pool, &Mesh::icosphere(subdivisions, GROUND_ALBEDO), None))
+            .collect::<Result<Vec<_>>>();
+
+        unsafe { logical_device.destroy_command_pool(command_pool, None) };
+
+        Ok(Self { lods: lods? })
+    }
+
+    //Planet radius is in sky units, scaled back to world units
+    pub fn model_matrix(sample_data: &AtmosphereSampleData) -> Matrix4x4 {
+        Matrix4x4::from_translation(sample_data.planet_pos.truncate()) * Matrix4x4::from_scale(sample_data.planet_radius / sample_data.unit_scale)
+    }
+
+    //Expects the scene pipeline and descriptor set to be bound. The surface is untextured, `default_texture` is
+    //the white texture bound as set 1
+    pub fn record(&self, logical_device: &Device, command_buffer: CommandBuffer, pipeline_layout: PipelineLayout, default_texture: DescriptorSet, camera_pos: Vector3, sample_data: &AtmosphereSampleData) {
+        let radius = sample_data.planet_radius / sample_data.unit_scale;
+        let altitude = (camera_pos - sample_data.planet_pos.truncate()).magnitude() - radius;
+
+        self.lods[planet_lod(altitude, radius)].record_draw(logical_device, command_buffer, pipeline_layout, Self::model_matrix(sample_data), default_texture);
+    }
+}
+
+impl RHIDestroy for PlanetData {
+    fn destroy(&mut self, rhi_data: &VulkanRHIData) {
+        self.lods.iter_mut().for_each(|lod| lod.destroy(rhi_data));
+        self.lods.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RADIUS: f32 = 6.371;
+
+    #[test]
+    fn lod_gets_finer_towards_the_surface() {
+        assert_eq!(planet_lod(0.0, RADIUS), PLANET_LOD_SUBDIVISIONS.len() - 1);
+        assert_eq!(planet_lod(-0.01, RADIUS), PLANET_LOD_SUBDIVISIONS.len() - 1);
+        assert_eq!(planet_lod(1000.0, RADIUS), 0);
+
+        let lods = [0.0001, 0.01, 0.1, 1.0, 10.0, 100.0].map(|altitude| planet_lod(altitude, RADIUS));
+        assert!(lods.windows(2).all(|w| w[0] >= w[1]), "{:?}", lods);
+    }
+}
@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::fs;
+use std::mem::size_of;
+use std::slice;
+
+use anyhow::{anyhow, Result};
+use vulkanalia::vk::{AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentReference, AttachmentStoreOp, ColorComponentFlags, CommandBuffer, CopyDescriptorSet, CullModeFlags, DescriptorBufferInfo, DescriptorImageInfo, DescriptorPool, DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType, DeviceV1_0, Extent2D, Filter, Framebuffer, FramebufferCreateInfo, FrontFace, GraphicsPipelineCreateInfo, Handle, HasBuilder, ImageLayout, Offset2D, Pipeline, PipelineBindPoint, PipelineCache, PipelineColorBlendAttachmentState, PipelineColorBlendStateCreateInfo, PipelineInputAssemblyStateCreateInfo, PipelineLayout, PipelineLayoutCreateInfo, PipelineMultisampleStateCreateInfo, PipelineRasterizationStateCreateInfo, PipelineShaderStageCreateInfo, PipelineStageFlags, PipelineVertexInputStateCreateInfo, PipelineViewportStateCreateInfo, PolygonMode, PrimitiveTopology, PushConstantRange, Rect2D, RenderPass, RenderPassBeginInfo, RenderPassCreateInfo, SampleCountFlags, Sampler, SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode, ShaderModule, ShaderStageFlags, SubpassContents, SubpassDependency, SubpassDescription, Viewport, WriteDescriptorSet, SUBPASS_EXTERNAL, WHOLE_SIZE};
+use vulkanalia::Device;
+
+use crate::config::config::PostProcessConfig;
+use crate::graphics::vulkan::vulkan_auto_exposure::AutoExposureData;
+use crate::graphics::vulkan::vulkan_rhi_data::VulkanRHIData;
+use crate::graphics::vulkan::vulkan_swapchain::SwapchainData;
+use crate::graphics::vulkan::vulkan_utils::{create_shader_module, RHIDestroy};
+
+//Matches PostProcessConstants in post_process_frag.hlsl
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct PostProcessConstants {
+    pub exposure: f32,
+    pub tonemapper: u32,
+    //Exposure is read from AutoExposureData instead
+    pub auto_exposure: u32,
+}
+
+impl From<&PostProcessConfig> for PostProcessConstants {
+    fn from(config: &PostProcessConfig) -> Self {
+        let multiplier = config.exposure.multiplier();
+
+        Self {
+            exposure: multiplier.unwrap_or(1.0),
+            tonemapper: config.tonemapper as u32,
+            auto_exposure: multiplier.is_none() as u32
+        }
+    }
+}
+
+//Fullscreen pass resolving the HDR scene color into the swapchain image, see SwapchainData::hdr_images
+#[derive(Debug, Default)]
+pub struct PostProcessData {
+    pub(crate) render_pass: RenderPass,
+    pub(crate) pipeline_layout: PipelineLayout,
+    pub(crate) pipeline: Pipeline,
+    pub(crate) framebuffers: Vec<Framebuffer>,
+
+    sampler: Sampler,
+
+    descriptor_set_layout: DescriptorSetLayout,
+    descriptor_pool: DescriptorPool,
+    /*  Per swapchain image:
+         0 - HDR scene color
+         1 - adapted exposure, see AutoExposureData
+    */
+    descriptor_sets: Vec<DescriptorSet>,
+}
+
+impl PostProcessData {
+    //Must be recorded after the scene render pass
+    pub fn record(&self, logical_device: &Device, command_buffer: CommandBuffer, image_index: usize, extent: Extent2D, constants: PostProcessConstants) {
+        let render_area = Rect2D::builder()
+            .extent(extent)
+            .offset(Offset2D::default())
+            ;
+
+        //Every pixel is written, nothing to clear
+        let render_pass_begin_info = RenderPassBeginInfo::builder()
+            .render_pass(self.render_pass)
+            .framebuffer(self.framebuffers[image_index])
+            .render_area(render_area)
+            ;
+
+        let constants_bytes = unsafe { slice::from_raw_parts(&constants as *const PostProcessConstants as *const u8, size_of::<PostProcessConstants>()) };
+
+        unsafe {
+            logical_device.cmd_begin_render_pass(command_buffer, &render_pass_begin_info, SubpassContents::INLINE);
+            logical_device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::GRAPHICS, self.pipeline);
+            logical_device.cmd_bind_descriptor_sets(command_buffer, PipelineBindPoint::GRAPHICS, self.pipeline_layout, 0, &[self.descriptor_sets[image_index]], &[]);
+            logical_device.cmd_push_constants(command_buffer, self.pipeline_layout, ShaderStageFlags::FRAGMENT, 0, constants_bytes);
+
+            //Fullscreen triangle generated in fullscreen_vert.hlsl
+            logical_device.cmd_draw(command_buffer, 3, 1, 0, 0);
+            logical_device.cmd_end_render_pass(command_buffer);
+        }
+    }
+}
+
+impl RHIDestroy for PostProcessData {
+    fn destroy(&mut self, rhi_data: &VulkanRHIData) {
+        let logical_device = &rhi_data.logical_device;
+
+        unsafe {
+            self.framebuffers
+                .iter()
+                .for_each(|fb| logical_device.destroy_framebuffer(*fb, None));
+            self.framebuffers.clear();
+
+            logical_device.destroy_pipeline(self.pipeline, None);
+            logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
+            logical_device.destroy_render_pass(self.render_pass, None);
+
+            logical_device.destroy_descriptor_pool(self.descriptor_pool, None);
+            logical_device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            self.descriptor_sets.clear();
+
+            logical_device.destroy_sampler(self.sampler, None);
+        }
+    }
+}
+
+pub struct PostProcessDataBuilder<'a> {
+    value: PostProcessData,
+    rhi_data: &'a VulkanRHIData,
+    swapchain_data: &'a SwapchainData,
+    auto_exposure: Option<&'a AutoExposureData>,
+    shaders: HashMap<ShaderStageFlags, &'a str>,
+}
+
+impl<'a> PostProcessDataBuilder<'a> {
+    pub fn new(rhi_data: &'a VulkanRHIData, swapchain_data: &'a SwapchainData) -> Self {
+        Self {
+            rhi_data,
+            swapchain_data,
+            auto_exposure: None,
+            value: PostProcessData::default(),
+            shaders: HashMap::new()
+        }
+    }
+
+    pub fn shader(mut self, stage: ShaderStageFlags, shader_path: &'a str) -> Self {
+        self.shaders.insert(stage, shader_path);
+        self
+    }
+
+    pub fn auto_exposure(mut self, auto_exposure: &'a AutoExposureData) -> Self {
+        self.auto_exposure = Some(auto_exposure);
+        self
+    }
+
+    pub fn build(mut self) -> Result<PostProcessData> {
+        self.create_sampler()?;
+        self.create_descriptor_set_layout()?;
+        self.create_render_pass()?;
+        self.create_pipeline()?;
+        self.create_framebuffers()?;
+        self.create_descriptor_pool()?;
+        self.create_descriptor_sets()?;
+
+        Ok(self.value)
+    }
+
+    fn create_sampler(&mut self) -> Result<()> {
+        //Sampled 1:1, no filtering needed
+        let info = SamplerCreateInfo::builder()
+            .mag_filter(Filter::NEAREST)
+            .min_filter(Filter::NEAREST)
+            .mipmap_mode(SamplerMipmapMode::NEAREST)
+            .address_mode_u(SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(SamplerAddressMode::CLAMP_TO_EDGE)
+            .min_lod(0.0)
+            .max_lod(0.0)
+            ;
+
+        self.value.sampler = unsafe { self.rhi_data.logical_device.create_sampler(&info, None) }?;
+
+        Ok(())
+    }
+
+    fn create_descriptor_set_layout(&mut self) -> Result<()> {
+        let binding = |binding: u32, descriptor_type: DescriptorType| {
+            DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(descriptor_type)
+                .descriptor_count(1)
+                .stage_flags(ShaderStageFlags::FRAGMENT)
+                .build()
+        };
+
+        let bindings = &[
+            binding(0, DescriptorType::COMBINED_IMAGE_SAMPLER),
+            binding(1, DescriptorType::STORAGE_BUFFER),
+        ];
+
+        let info = DescriptorSetLayoutCreateInfo::builder()
+            .bindings(bindings)
+            ;
+
+        self.value.descriptor_set_layout = unsafe { self.rhi_data.logical_device.create_descriptor_set_layout(&info, None) }?;
+
+        Ok(())
+    }
+
+    fn create_render_pass(&mut self) -> Result<()> {
+        let swapchain_data = self.swapchain_data;
+
+        //Offscreen targets are read back rather than presented
+        let color_final_layout = if swapchain_data.is_offscreen() {
+            ImageLayout::TRANSFER_SRC_OPTIMAL
+        } else {
+            ImageLayout::PRESENT_SRC_KHR
+        };
+
+        let color_attachment = AttachmentDescription::builder()
+            .format(swapchain_data.swapchain_format)
+            .samples(SampleCountFlags::_1)
+            .load_op(AttachmentLoadOp::DONT_CARE)
+            .store_op(AttachmentStoreOp::STORE)
+            .stencil_load_op(AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(AttachmentStoreOp::DONT_CARE)
+            .initial_layout(ImageLayout::UNDEFINED)
+            .final_layout(color_final_layout)
+            ;
+
+        let color_attachment_ref = AttachmentReference::builder()
+            .attachment(0)
+            .layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            ;
+
+        let color_attachments = &[color_attachment_ref];
+        let subpass = SubpassDescription::builder()
+            .pipeline_bind_point(PipelineBindPoint::GRAPHICS)
+            .color_attachments(color_attachments)
+            ;
+
+        //Waits for the swapchain image to be acquired and for the scene pass to finish writing the HDR target
+        let dependency = SubpassDependency::builder()
+            .src_subpass(SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .src_access_mask(AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | PipelineStageFlags::FRAGMENT_SHADER)
+            .dst_access_mask(AccessFlags::COLOR_ATTACHMENT_WRITE | AccessFlags::SHADER_READ)
+            ;
+
+        let attachments = &[color_attachment];
+        let subpasses = &[subpass];
+        let dependencies = &[dependency];
+        let render_pass_info = RenderPassCreateInfo::builder()
+            .attachments(attachments)
+            .subpasses(subpasses)
+            .dependencies(dependencies)
+            ;
+
+        self.value.render_pass = unsafe { self.rhi_data.logical_device.create_render_pass(&render_pass_info, None) }?;
+
+        Ok(())
+    }
+
+    fn create_pipeline(&mut self) -> Result<()> {
+        let logical_device = &self.rhi_data.logical_device;
+
+        let vert_path = self.shaders.get(&ShaderStageFlags::VERTEX).ok_or_else(|| anyhow!("Missing post-process vertex shader"))?;
+        let frag_path = self.shaders.get(&ShaderStageFlags::FRAGMENT).ok_or_else(|| anyhow!("Missing post-process fragment shader"))?;
+
+        let vert_module = self.create_shader_module(&fs::read(vert_path)?[..])?;
+        let frag_module = self.create_shader_module(&fs::read(frag_path)?[..])?;
+
+        let vert_stage = PipelineShaderStageCreateInfo::builder()
+            .stage(ShaderStageFlags::VERTEX)
+            .module(vert_module)
+            .name(b"main\0");
+
+        let frag_stage = PipelineShaderStageCreateInfo::builder()
+            .stage(ShaderStageFlags::FRAGMENT)
+            .module(frag_module)
+            .name(b"main\0");
+
+        //Vertices come from SV_VertexID
+        let vertex_input_state = PipelineVertexInputStateCreateInfo::builder();
+
+        let input_assembly_state = PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(PrimitiveTopology::TRIANGLE_LIST)
+            .primitive_restart_enable(false);
+
+        let extent = self.swapchain_data.swapchain_extent;
+        let viewport = Viewport::builder()
+            .x(0.0)
+            .y(0.0)
+            .width(extent.width as f32)
+            .height(extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0)
+            ;
+
+        let scissor = Rect2D::builder()
+            .offset(Offset2D {x: 0, y: 0})
+            .extent(extent)
+            ;
+
+        let viewports = &[viewport];
+        let scissors = &[scissor];
+        let viewport_state = PipelineViewportStateCreateInfo::builder()
+            .viewports(viewports)
+            .scissors(scissors)
+            ;
+
+        let rasterization_state = PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(PolygonMode::FILL)
+            .line_width(1.0)
+            .cull_mode(CullModeFlags::empty())
+            .front_face(FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            ;
+
+        let multisample_state = PipelineMultisampleStateCreateInfo::builder()
+            .sample_shading_enable(false)
+            .rasterization_samples(SampleCountFlags::_1)
+            ;
+
+        let color_blend_attachment = PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(ColorComponentFlags::all())
+            .blend_enable(false)
+            ;
+
+        let attachments = &[color_blend_attachment];
+        let color_blend_state = PipelineColorBlendStateCreateInfo::builder()
+            .logic_op_enable(false)
+            .attachments(attachments)
+            ;
+
+        let push_constant_range = PushConstantRange::builder()
+            .stage_flags(ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(size_of::<PostProcessConstants>() as u32)
+            ;
+
+        let layouts = &[self.value.descriptor_set_layout];
+        let push_constant_ranges = &[push_constant_range];
+        let layout_info = PipelineLayoutCreateInfo::builder()
+            .set_layouts(layouts)
+            .push_constant_ranges(push_constant_ranges)
+            ;
+
+        self.value.pipeline_layout = unsafe { logical_device.create_pipeline_layout(&layout_info, None) }?;
+
+        let stages = &[vert_stage, frag_stage];
+        let pipeline_info = GraphicsPipelineCreateInfo::builder()
+            .stages(stages)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .color_blend_state(&color_blend_state)
+            .layout(self.value.pipeline_layout)
+            .render_pass(self.value.render_pass)
+            .subpass(0)
+            .base_pipeline_handle(Pipeline::null())
+            .base_pipeline_index(-1)
+            ;
+
+        let pipeline = unsafe { logical_device.create_graphics_pipelines(PipelineCache::null(), &[pipeline_info], None) };
+
+        unsafe {
+            logical_device.destroy_shader_module(vert_module, None);
+            logical_device.destroy_shader_module(frag_module, None);
+        }
+
+        self.value.pipeline = pipeline?.0[0];
+
+        Ok(())
+    }
+
+    fn create_shader_module(&self, bytecode: &[u8]) -> Result<ShaderModule> {
+        create_shader_module(self.rhi_data, bytecode)
+    }
+
+    fn create_framebuffers(&mut self) -> Result<()> {
+        let swapchain_data = self.swapchain_data;
+
+        for image_view in &swapchain_data.swapchain_image_views {
+            let attachments = &[*image_view];
+            let create_info = FramebufferCreateInfo::builder()
+                .attachments(attachments)
+                .render_pass(self.value.render_pass)
+                .width(swapchain_data.swapchain_extent.width)
+                .height(swapchain_data.swapchain_extent.height)
+                .layers(1)
+                ;
+
+            let framebuffer = unsafe { self.rhi_data.logical_device.create_framebuffer(&create_info, None) }?;
+            self.value.framebuffers.push(framebuffer);
+        }
+
+        Ok(())
+    }
+
+    fn create_descriptor_pool(&mut self) -> Result<()> {
+        let image_count = self.swapchain_data.hdr_image_views.len() as u32;
+
+        let pool_sizes = &[
+            DescriptorPoolSize::builder()
+                .type_(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(image_count),
+            DescriptorPoolSize::builder()
+                .type_(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(image_count),
+        ];
+
+        let info = DescriptorPoolCreateInfo::builder()
+            .pool_sizes(pool_sizes)
+            .max_sets(image_count);
+
+        self.value.descriptor_pool = unsafe { self.rhi_data.logical_device.create_descriptor_pool(&info, None) }?;
+
+        Ok(())
+    }
+
+    fn create_descriptor_sets(&mut self) -> Result<()> {
+        let logical_device = &self.rhi_data.logical_device;
+        let auto_exposure = self.auto_exposure.ok_or_else(|| anyhow!("Missing auto exposure data"))?;
+
+        let layouts = vec![self.value.descriptor_set_layout; self.swapchain_data.hdr_image_views.len()];
+        let info = DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.value.descriptor_pool)
+            .set_layouts(&layouts)
+            ;
+
+        self.value.descriptor_sets = unsafe { logical_device.allocate_descriptor_sets(&info) }?;
+
+        for (descriptor_set, image_view) in self.value.descriptor_sets.iter().zip(self.swapchain_data.hdr_image_views.iter()) {
+            let image_info = &[DescriptorImageInfo::builder()
+                .sampler(self.value.sampler)
+                .image_view(*image_view)
+                .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)];
+
+            let exposure_info = &[DescriptorBufferInfo::builder()
+                .buffer(auto_exposure.exposure_buffer)
+                .offset(0)
+                .range(WHOLE_SIZE as u64)];
+
+            let write = |binding: u32, descriptor_type: DescriptorType| {
+                WriteDescriptorSet::builder()
+                    .dst_set(*descriptor_set)
+                    .dst_binding(binding)
+                    .dst_array_element(0)
+                    .descriptor_type(descriptor_type)
+            };
+
+            let writes = &[
+                write(0, DescriptorType::COMBINED_IMAGE_SAMPLER).image_info(image_info),
+                write(1, DescriptorType::STORAGE_BUFFER).buffer_info(exposure_info),
+            ];
+
+            unsafe { logical_device.update_descriptor_sets(writes, &[] as &[CopyDescriptorSet]) };
+        }
+
+        Ok(())
+    }
+}
@@ -1,25 +1,40 @@
+use std::collections::HashMap;
 use std::mem::size_of;
 use std::ptr::copy_nonoverlapping;
-use std::slice;
 use std::sync::{Arc, RwLock};
+use std::time::Instant;
 
 use anyhow::{anyhow, Result};
-use cgmath::{vec3, vec4, Deg, Euler, Quaternion, Rotation};
+use log::{info, warn};
+use cgmath::SquareMatrix;
 use vulkanalia::vk;
-use vulkanalia::vk::{ApplicationInfo, ClearColorValue, ClearValue, CommandBuffer, CommandBufferBeginInfo, CommandBufferInheritanceInfo, CommandBufferUsageFlags, CommandPoolResetFlags, DeviceV1_0, ErrorCode, Fence, Handle, HasBuilder, IndexType, KhrSwapchainExtension, MemoryMapFlags, Offset2D, PipelineBindPoint, PipelineStageFlags, PresentInfoKHR, Rect2D, RenderPassBeginInfo, ShaderStageFlags, SubmitInfo, SubpassContents, SuccessCode};
+use vulkanalia::vk::{ApplicationInfo, ClearColorValue, ClearValue, CommandBuffer, CommandBufferBeginInfo, CommandBufferInheritanceInfo, CommandBufferUsageFlags, CommandPoolResetFlags, DeviceV1_0, ErrorCode, Extent2D, Fence, Handle, HasBuilder, ImageLayout, KhrSwapchainExtension, MemoryMapFlags, Offset2D, PipelineBindPoint, PipelineStageFlags, PresentInfoKHR, Rect2D, RenderPassBeginInfo, Semaphore, ShaderStageFlags, SubmitInfo, SubpassContents, SuccessCode};
 use winit::window::Window;
 
+use crate::config::atmosphere_settings::AtmosphereSettings;
 use crate::config::config::GraphicsConfig;
-use crate::graphics::rhi::RHI;
+use crate::graphics::frame_capture::FrameCapture;
+use crate::graphics::rhi::{MeshHandle, RHI};
 use crate::graphics::vulkan::atmopsheric_scattering::{AtmosphereSampleData, ScatteringMedium};
 use crate::graphics::vulkan::transformation::{Matrix4x4, Transformation};
 use crate::graphics::vulkan::view_state::ViewState;
+use crate::graphics::vulkan::vulkan_auto_exposure::{AutoExposureConstants, AutoExposureData, AutoExposureDataBuilder, AutoExposurePass};
+use crate::graphics::vulkan::vulkan_atmosphere_lut::{AtmosphereLut, AtmosphereLutData, AtmosphereLutDataBuilder};
 use crate::graphics::vulkan::vulkan_sync_objects::SyncObjects;
+use crate::graphics::vulkan::vulkan_frame_capture::FrameCaptureData;
+use crate::assets::gltf_loader::GltfScene;
+use crate::assets::mesh::Mesh;
+use crate::assets::mesh_loader::MeshLoadError;
+use crate::graphics::vulkan::vulkan_mesh_registry::MeshRegistry;
 use crate::graphics::vulkan::vulkan_pipeline::{PipelineData, PipelineDataBuilder};
+use crate::graphics::vulkan::vulkan_planet::PlanetData;
+use crate::graphics::vulkan::vulkan_post_process::{PostProcessConstants, PostProcessData, PostProcessDataBuilder};
 use crate::graphics::vulkan::vulkan_rhi_data::{VulkanRHIData, VulkanRHIDataBuilder};
 use crate::graphics::vulkan::vulkan_swapchain::{SwapchainData, SwapchainDataBuilder};
-use crate::graphics::vulkan::vulkan_utils::{perspective_matrix, RHIDestroy, INDICES, PERSPECTIVE_CORRECTION, VALIDATION_ENABLED};
-use crate::utils::math::VECTOR3_FORWARD;
+use crate::graphics::vulkan::vulkan_utils::{perspective_matrix, RHIDestroy, PERSPECTIVE_CORRECTION, VALIDATION_ENABLED};
+use crate::world::components::{MeshAsset, MeshRef, MeshSource};
+use crate::world::hierarchy;
+use crate::world::hierarchy::GlobalTransform;
 use crate::world::transform::OwnedTransform;
 use crate::world::world::World;
 
@@ -29,18 +44,33 @@ pub struct RHIVulkan {
     is_destroyed: bool,
     config: GraphicsConfig,
     frame_index: usize,
+    //Set by request_capture, the next recorded frame copies its final image into frame_capture
+    capture_requested: bool,
+    frame_capture: Option<FrameCaptureData>,
+    //Drives exposure adaptation
+    last_frame_time: Option<Instant>,
     world: Option<Arc<RwLock<World>>>,
 
+    atmosphere_sample_data: AtmosphereSampleData,
+    scattering_medium: ScatteringMedium,
+
     //New Stuff
     data: VulkanRHIData,
     swapchain_data: SwapchainData,
+    atmosphere_luts: AtmosphereLutData,
+    planet_data: PlanetData,
+    mesh_registry: MeshRegistry,
     pipeline_data: PipelineData,
+    auto_exposure_data: AutoExposureData,
+    post_process_data: PostProcessData,
     sync_objects: SyncObjects,
 }
 
 impl RHI for RHIVulkan {
 
     fn initialize(&mut self, world: Arc<RwLock<World>>) -> Result<()> {
+        self.load_mesh_assets(&mut world.write().unwrap())?;
+
         self.world = Some(world);
         Ok(())
     }
@@ -73,24 +103,13 @@ impl RHI for RHIVulkan {
 
         self.sync_objects.set_image_fence(image_index as usize, fence);
 
+        self.update_atmosphere_luts()?;
         self.update_command_buffers(image_index as usize)?;
         self.update_uniform_buffers(image_index as usize)?;
 
         let wait_semaphores = &[self.sync_objects.image_available_semaphores[self.frame_index]];
-        let wait_stages = &[PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-        let command_buffers = &[self.pipeline_data.primary_command_buffers[image_index as usize]];
         let signal_semaphores = &[self.sync_objects.render_finished_semaphores[self.frame_index]];
-        let submit_info = SubmitInfo::builder()
-            .command_buffers(command_buffers)
-            .signal_semaphores(signal_semaphores)
-            .wait_semaphores(wait_semaphores)
-            .wait_dst_stage_mask(wait_stages)
-        ;
-
-        unsafe {
-            self.data.logical_device.reset_fences(&[fence])?;
-            self.data.logical_device.queue_submit(self.data.graphics_queue, &[submit_info], self.sync_objects.in_flight_fences[self.frame_index])?;
-        }
+        self.submit(image_index as usize, wait_semaphores, signal_semaphores)?;
 
         let swapchains = &[self.swapchain_data.swapchain];
         let image_indices = &[image_index];
@@ -119,25 +138,141 @@ impl RHI for RHIVulkan {
 
         unsafe { self.data.logical_device.device_wait_idle() }.unwrap();
 
+        if let Some(mut capture) = self.frame_capture.take() {
+            capture.destroy(&self.data);
+        }
+
         self.sync_objects.destroy(&self.data);
+        self.post_process_data.destroy(&self.data);
+        self.auto_exposure_data.destroy(&self.data);
         self.pipeline_data.destroy(&self.data);
+        self.mesh_registry.destroy(&self.data);
+        self.planet_data.destroy(&self.data);
+        self.atmosphere_luts.destroy(&self.data);
         self.swapchain_data.destroy(&self.data);
         self.data.destroy();
     }
 
+    fn request_capture(&mut self) -> Result<()> {
+        if !self.swapchain_data.supports_capture {
+            return Err(anyhow!("The surface does not support TRANSFER_SRC, frames can not be captured"));
+        }
+
+        //A capture that was never taken is replaced
+        if let Some(mut capture) = self.frame_capture.take() {
+            unsafe { self.data.logical_device.device_wait_idle() }?;
+            capture.destroy(&self.data);
+        }
+
+        self.capture_requested = true;
+        Ok(())
+    }
+
+    fn take_capture(&mut self) -> Result<Option<FrameCapture>> {
+        let Some(mut capture) = self.frame_capture.take() else {
+            return Ok(None);
+        };
+
+        let result = unsafe { self.data.logical_device.device_wait_idle() }
+            .map_err(|e| anyhow!(e))
+            .and_then(|_| capture.read(&self.data));
+
+        capture.destroy(&self.data);
+        result.map(Some)
+    }
+
+    fn register_mesh(&mut self, mesh: &Mesh) -> Result<MeshHandle> {
+        self.mesh_registry.register(&self.data, mesh)
+    }
+
+    fn unregister_mesh(&mut self, handle: MeshHandle) -> Result<()> {
+        //Recorded command buffers may still draw it
+        unsafe { self.data.logical_device.device_wait_idle() }?;
+
+        self.mesh_registry.remove(&self.data, handle);
+        Ok(())
+    }
+
     fn get_width(&self) -> u32 {
-        todo!()
+        self.swapchain_data.swapchain_extent.width
     }
 
     fn get_height(&self) -> u32 {
-        todo!()
+        self.swapchain_data.swapchain_extent.height
     }
 }
 
 impl RHIVulkan {
-    pub fn new(window: &Window, config: GraphicsConfig) -> Self {
-        const MAX_FRAMES_IN_FLIGHT: usize = 2;
+    //`model` is an OBJ file used as MeshHandle::DEFAULT, the built-in cube when None.
+    //glTF models are spawned as entities with their own meshes instead
+    pub fn new(window: &Window, config: GraphicsConfig, atmosphere: &AtmosphereSettings, model: Option<&str>) -> Result<Self> {
+        let rhi_data = Self::rhi_data_builder(config)
+            .build(window)?;
+
+        let swapchain_data = SwapchainDataBuilder::default()
+            .build(window, &rhi_data)?;
 
+        Self::create(config, atmosphere, model, rhi_data, swapchain_data)
+    }
+
+    //Renders into a device-local color + depth target of the given size instead of a swapchain.
+    //Frames are produced with `render_offscreen`
+    pub fn new_headless(width: u32, height: u32, config: GraphicsConfig, atmosphere: &AtmosphereSettings, model: Option<&str>) -> Result<Self> {
+        let rhi_data = Self::rhi_data_builder(config)
+            .build_headless()?;
+
+        let extent = Extent2D::builder()
+            .width(width)
+            .height(height)
+            .build();
+
+        let swapchain_data = SwapchainDataBuilder::default()
+            .build_offscreen(extent, &rhi_data)?;
+
+        Self::create(config, atmosphere, model, rhi_data, swapchain_data)
+    }
+
+    pub fn set_atmosphere(&mut self, sample_data: AtmosphereSampleData, medium: ScatteringMedium) {
+        self.atmosphere_sample_data = sample_data;
+        self.scattering_medium = medium;
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.swapchain_data.is_offscreen()
+    }
+
+    pub fn render_offscreen(&mut self) -> Result<()> {
+        assert!(self.is_headless());
+
+        let fence = self.sync_objects.in_flight_fences[self.frame_index];
+
+        unsafe {
+            self.data.logical_device.wait_for_fences(&[fence], true, u64::MAX)?;
+        }
+
+        //Single color target, nothing to acquire
+        let image_index = 0;
+
+        if !self.sync_objects.images_in_flight[image_index].is_null() {
+            unsafe {
+                self.data.logical_device.wait_for_fences(&[self.sync_objects.images_in_flight[image_index]], true, u64::MAX)?;
+            }
+        }
+
+        self.sync_objects.set_image_fence(image_index, fence);
+
+        self.update_atmosphere_luts()?;
+        self.update_command_buffers(image_index)?;
+        self.update_uniform_buffers(image_index)?;
+
+        self.submit(image_index, &[], &[])?;
+
+        self.frame_index = (self.frame_index + 1) % self.max_frames_in_flight;
+
+        Ok(())
+    }
+
+    fn rhi_data_builder(config: GraphicsConfig) -> VulkanRHIDataBuilder {
         let app_info = ApplicationInfo::builder()
             .application_version(vk::make_version(0, 1, 0))
             .api_version(vk::make_version(1, 0, 0))
@@ -146,35 +281,164 @@ impl RHIVulkan {
             .engine_name(b"Test Engine")
             .build();
 
-        let rhi_data = VulkanRHIDataBuilder::default()
+        VulkanRHIDataBuilder::default()
             .application_info(app_info)
             .config(config)
-            .validation(VALIDATION_ENABLED)
-            .build(window).unwrap();
+            .validation(VALIDATION_ENABLED && config.validation_enabled)
+    }
 
-        let swapchain_data = SwapchainDataBuilder::default()
-            .build(window, &rhi_data)
-            .unwrap();
+    fn create(config: GraphicsConfig, atmosphere: &AtmosphereSettings, model: Option<&str>, rhi_data: VulkanRHIData, swapchain_data: SwapchainData) -> Result<Self> {
+        const MAX_FRAMES_IN_FLIGHT: usize = 2;
 
-        let pipeline_data = PipelineDataBuilder::new(&rhi_data, &swapchain_data)
-            .shader(ShaderStageFlags::VERTEX, "./resources/shaders/compiled/basic_vert.spv")
-            .shader(ShaderStageFlags::FRAGMENT, "./resources/shaders/compiled/atmosphere_frag.spv")
-            .build()
-            .unwrap();
+        let atmosphere_luts = AtmosphereLutDataBuilder::new(&rhi_data)
+            .shader(AtmosphereLut::Transmittance, "./resources/shaders/compiled/atmosphere_transmittance_comp.spv")
+            .shader(AtmosphereLut::MultiScattering, "./resources/shaders/compiled/atmosphere_multi_scattering_comp.spv")
+            .build()?;
+
+        let planet_data = PlanetData::create(&rhi_data)?;
 
+        //A glTF model is spawned as entities of its own by the App
+        let default_mesh = match model {
+            Some(path) if !GltfScene::is_gltf(path) => Mesh::load(path)?,
+            _ => Mesh::cube(),
+        };
+
+        let mut mesh_registry = MeshRegistry::create(&rhi_data)?;
+        mesh_registry.register(&rhi_data, &default_mesh)?;
+
+        let pipeline_data = Self::create_pipeline_data(&rhi_data, &swapchain_data, &atmosphere_luts, &mesh_registry)?;
+        let auto_exposure_data = Self::create_auto_exposure_data(&rhi_data, &swapchain_data)?;
+        let post_process_data = Self::create_post_process_data(&rhi_data, &swapchain_data, &auto_exposure_data)?;
         let sync_objects = SyncObjects::create(&rhi_data.logical_device, &swapchain_data, MAX_FRAMES_IN_FLIGHT);
 
-        Self {
+        //Light is filled in from the World's sun every frame
+        let atmosphere_sample_data = atmosphere.sample_data();
+        let scattering_medium = atmosphere.medium();
+
+        Ok(Self {
             max_frames_in_flight: MAX_FRAMES_IN_FLIGHT,
             is_destroyed: false,
             config,
             frame_index: 0,
+            capture_requested: false,
+            frame_capture: None,
+            last_frame_time: None,
             world: None,
+            atmosphere_sample_data,
+            scattering_medium,
             data: rhi_data,
             swapchain_data,
+            atmosphere_luts,
+            planet_data,
+            mesh_registry,
             pipeline_data,
+            auto_exposure_data,
+            post_process_data,
             sync_objects
+        })
+    }
+
+    //Adds the MeshRef of entities that only have a MeshAsset, e.g. from a scene file. Every file is loaded once
+    fn load_mesh_assets(&mut self, world: &mut World) -> Result<()> {
+        let pending = world.ecs()
+            .query::<MeshAsset>()
+            .filter(|&(entity, _)| !world.ecs().has::<MeshRef>(entity))
+            .map(|(entity, asset)| (entity, asset.clone()))
+            .collect::<Vec<_>>();
+
+        let mut files = HashMap::<String, MeshHandle>::new();
+        let mut gltf_scenes = HashMap::<String, Vec<Option<MeshHandle>>>::new();
+
+        for (entity, asset) in &pending {
+            let handle = match asset.source() {
+                MeshSource::Default => MeshHandle::DEFAULT,
+                MeshSource::File(path) => match files.get(path) {
+                    Some(&handle) => handle,
+                    None => {
+                        let handle = self.register_mesh(&Mesh::load(path)?)?;
+                        files.insert(path.to_string(), handle);
+                        handle
+                    }
+                },
+                MeshSource::GltfMesh(path, mesh) => {
+                    if !gltf_scenes.contains_key(path) {
+                        let scene = GltfScene::load(path).map_err(|source| MeshLoadError::Gltf { path: path.to_string(), source })?;
+                        //Empty meshes can't be uploaded, entities referring to them are not drawn
+                        let handles = scene.meshes
+                            .iter()
+                            .map(|mesh| (!mesh.indices.is_empty()).then(|| self.register_mesh(mesh)).transpose())
+                            .collect::<Result<Vec<_>>>()?;
+
+                        gltf_scenes.insert(path.to_string(), handles);
+                    }
+
+                    match gltf_scenes[path].get(mesh) {
+                        Some(Some(handle)) => *handle,
+                        Some(None) => {
+                            warn!("Mesh {} of '{}' is empty", mesh, path);
+                            continue;
+                        }
+                        None => return Err(anyhow!("'{}' has no mesh {}", path, mesh)),
+                    }
+                }
+            };
+
+            world.ecs_mut().insert(*entity, MeshRef(handle));
         }
+
+        if !pending.is_empty() {
+            info!("Loaded meshes of {} entities from {} files", pending.len(), files.len() + gltf_scenes.len());
+        }
+
+        Ok(())
+    }
+
+    fn create_pipeline_data(rhi_data: &VulkanRHIData, swapchain_data: &SwapchainData, atmosphere_luts: &AtmosphereLutData, mesh_registry: &MeshRegistry) -> Result<PipelineData> {
+        PipelineDataBuilder::new(rhi_data, swapchain_data)
+            .atmosphere_luts(atmosphere_luts)
+            .texture_set_layout(mesh_registry.texture_set_layout())
+            .compute_shader(AtmosphereLut::SkyView, "./resources/shaders/compiled/atmosphere_sky_view_comp.spv")
+            .compute_shader(AtmosphereLut::AerialPerspective, "./resources/shaders/compiled/atmosphere_aerial_perspective_comp.spv")
+            .shader(ShaderStageFlags::VERTEX, "./resources/shaders/compiled/basic_vert.spv")
+            .shader(ShaderStageFlags::FRAGMENT, "./resources/shaders/compiled/basic_frag.spv")
+            .sky_shader(ShaderStageFlags::VERTEX, "./resources/shaders/compiled/fullscreen_vert.spv")
+            .sky_shader(ShaderStageFlags::FRAGMENT, "./resources/shaders/compiled/sky_frag.spv")
+            .build()
+    }
+
+    fn create_auto_exposure_data(rhi_data: &VulkanRHIData, swapchain_data: &SwapchainData) -> Result<AutoExposureData> {
+        AutoExposureDataBuilder::new(rhi_data, swapchain_data)
+            .shader(AutoExposurePass::Histogram, "./resources/shaders/compiled/luminance_histogram_comp.spv")
+            .shader(AutoExposurePass::Adaptation, "./resources/shaders/compiled/exposure_adaptation_comp.spv")
+            .build()
+    }
+
+    fn create_post_process_data(rhi_data: &VulkanRHIData, swapchain_data: &SwapchainData, auto_exposure_data: &AutoExposureData) -> Result<PostProcessData> {
+        PostProcessDataBuilder::new(rhi_data, swapchain_data)
+            .auto_exposure(auto_exposure_data)
+            .shader(ShaderStageFlags::VERTEX, "./resources/shaders/compiled/fullscreen_vert.spv")
+            .shader(ShaderStageFlags::FRAGMENT, "./resources/shaders/compiled/post_process_frag.spv")
+            .build()
+    }
+
+    fn submit(&self, image_index: usize, wait_semaphores: &[Semaphore], signal_semaphores: &[Semaphore]) -> Result<()> {
+        let fence = self.sync_objects.in_flight_fences[self.frame_index];
+
+        let wait_stages = vec![PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT; wait_semaphores.len()];
+        let command_buffers = &[self.pipeline_data.primary_command_buffers[image_index]];
+        let submit_info = SubmitInfo::builder()
+            .command_buffers(command_buffers)
+            .signal_semaphores(signal_semaphores)
+            .wait_semaphores(wait_semaphores)
+            .wait_dst_stage_mask(&wait_stages)
+        ;
+
+        unsafe {
+            self.data.logical_device.reset_fences(&[fence])?;
+            self.data.logical_device.queue_submit(self.data.graphics_queue, &[submit_info], fence)?;
+        }
+
+        Ok(())
     }
 
     fn recreate_swapchain(&mut self, window: &Window) -> Result<()> {
@@ -185,6 +449,8 @@ impl RHIVulkan {
         self.swapchain_data.destroy(&self.data);
 
         //ToDo: Reuse what can be reused (e.g. command buffers)
+        self.post_process_data.destroy(&self.data);
+        self.auto_exposure_data.destroy(&self.data);
         self.pipeline_data.destroy(&self.data);
 
         self.sync_objects.destroy(&self.data);
@@ -192,23 +458,32 @@ impl RHIVulkan {
         self.swapchain_data = SwapchainDataBuilder::default()
             .build(window, &self.data)?;
 
-        self.pipeline_data = PipelineDataBuilder::new(&self.data, &self.swapchain_data)
-            .shader(ShaderStageFlags::VERTEX, "../../../resources/shaders/compiled/basic_vert.spv")
-            .shader(ShaderStageFlags::FRAGMENT, "../../../resources/shaders/compiled/basic_frag.spv")
-            .build()?;
+        self.pipeline_data = Self::create_pipeline_data(&self.data, &self.swapchain_data, &self.atmosphere_luts, &self.mesh_registry)?;
+        self.auto_exposure_data = Self::create_auto_exposure_data(&self.data, &self.swapchain_data)?;
+        self.post_process_data = Self::create_post_process_data(&self.data, &self.swapchain_data, &self.auto_exposure_data)?;
 
         self.sync_objects = SyncObjects::create(&self.data.logical_device, &self.swapchain_data, self.max_frames_in_flight);
 
         Ok(())
     }
 
+    //LUTs only depend on the medium and planet, not on the light direction
+    fn update_atmosphere_luts(&mut self) -> Result<()> {
+        if self.atmosphere_luts.is_outdated(&self.atmosphere_sample_data, &self.scattering_medium) {
+            self.atmosphere_luts.generate(&self.data, &self.atmosphere_sample_data, &self.scattering_medium)?;
+        }
+
+        Ok(())
+    }
+
     //ToDo: Add transforms and move from here
     fn update_uniform_buffers(&self, image_index: usize) -> Result<()> {
         let world = self.world.as_ref().unwrap().read().unwrap();
-        let camera = world.active_camera();
+        let camera = world.interpolated_camera();
         let view = camera.view_matrix();
 
         let camera_pos = camera.transform().location();
+        let atmosphere_sample_data = self.atmosphere_sample_data.with_light(world.sun_direction(), world.sun().radiance());
         let projection = PERSPECTIVE_CORRECTION * perspective_matrix(camera.view().fov,
                                                         self.swapchain_data.swapchain_extent.width as f32,
                                                         self.swapchain_data.swapchain_extent.height as f32,
@@ -229,19 +504,11 @@ impl RHIVulkan {
             self.data.logical_device.unmap_memory(buffer_memory)
         };
 
-        let light_rot = Quaternion::from(Euler {
-            x: Deg(-65.0),
-            y: Deg(25.0),
-            z: Deg(0.0)
-        });
-
-        let light_dir =  light_rot.rotate_vector(VECTOR3_FORWARD).extend(0.0);
-        let light_illuminance_outer_space = vec4(1., 1., 1., 1.) * 100.0;
-
         let view_state = ViewState {
             world_camera_origin: camera_pos.extend(0.0),
-            atmosphere_light_direction: light_dir,
-            atmosphere_light_illuminance_outer_space: light_illuminance_outer_space
+            atmosphere_light_direction: atmosphere_sample_data.light_dir,
+            atmosphere_light_illuminance_outer_space: atmosphere_sample_data.light_intensity,
+            clip_to_world: (projection * view).invert().unwrap_or(Matrix4x4::identity())
         };
 
         unsafe {
@@ -256,10 +523,6 @@ impl RHIVulkan {
             self.data.logical_device.unmap_memory(buffer_memory)
         };
 
-        let unit_scale = 0.2;
-        let scattering_ray = vec3(0.175287, 0.409607, 1.0);
-        let medium = ScatteringMedium::new(0.2, scattering_ray);
-
         unsafe {
             let buffer_memory = self.pipeline_data.uniform_buffers_memory[image_index][2];
             let memory = self.data.logical_device.map_memory(
@@ -268,23 +531,10 @@ impl RHIVulkan {
                 size_of::<ScatteringMedium>() as u64,
                 MemoryMapFlags::empty())?;
 
-            copy_nonoverlapping(&medium, memory.cast(), 1);
+            copy_nonoverlapping(&self.scattering_medium, memory.cast(), 1);
             self.data.logical_device.unmap_memory(buffer_memory)
         };
 
-        let atmospheric_sample_data = AtmosphereSampleData {
-            planet_pos: vec3(0.0, 0.0, 0.0).extend(0.0),
-            planet_radius: 6.3710,
-            atmosphere_thickness: 0.0600,
-            sample_count: 100.0,
-            sample_count_light: 15.0,
-            unit_scale,
-            light_dir,
-            light_intensity: light_illuminance_outer_space,
-
-            pad: [0.0, 0.0, 0.0]
-        };
-
         unsafe {
             let buffer_memory = self.pipeline_data.uniform_buffers_memory[image_index][3];
             let memory = self.data.logical_device.map_memory(
@@ -293,7 +543,7 @@ impl RHIVulkan {
                 size_of::<AtmosphereSampleData>() as u64,
                 MemoryMapFlags::empty())?;
 
-            copy_nonoverlapping(&atmospheric_sample_data, memory.cast(), 1);
+            copy_nonoverlapping(&atmosphere_sample_data, memory.cast(), 1);
             self.data.logical_device.unmap_memory(buffer_memory)
         };
 
@@ -345,6 +595,11 @@ impl RHIVulkan {
 
         unsafe {
             logical_device.begin_command_buffer(command_buffer, &command_buffer_begin_info)?;
+        }
+
+        self.pipeline_data.record_atmosphere_view_luts(logical_device, command_buffer, image_index);
+
+        unsafe {
             logical_device.cmd_begin_render_pass(command_buffer, &render_pass_begin_info, SubpassContents::SECONDARY_COMMAND_BUFFERS);
         }
 
@@ -355,6 +610,34 @@ impl RHIVulkan {
             logical_device.cmd_execute_commands(command_buffer, &[secondary_command_buffer]);
 
             logical_device.cmd_end_render_pass(command_buffer);
+        }
+
+        let frame_time = Instant::now();
+        let delta_time = self.last_frame_time.map_or(0.0, |last| (frame_time - last).as_secs_f32());
+        self.last_frame_time = Some(frame_time);
+
+        let extent = self.swapchain_data.swapchain_extent;
+        if let Some(constants) = AutoExposureConstants::new(&self.config.post_process.exposure, delta_time, extent) {
+            self.auto_exposure_data.record(logical_device, command_buffer, image_index, extent, constants);
+        }
+
+        let constants = PostProcessConstants::from(&self.config.post_process);
+        self.post_process_data.record(logical_device, command_buffer, image_index, self.swapchain_data.swapchain_extent, constants);
+
+        //Same submission as the frame, so the copy happens before the image is presented
+        if self.capture_requested {
+            let layout = if self.is_headless() {
+                ImageLayout::TRANSFER_SRC_OPTIMAL
+            } else {
+                ImageLayout::PRESENT_SRC_KHR
+            };
+
+            let image = self.swapchain_data.swapchain_images[image_index];
+            self.frame_capture = Some(FrameCaptureData::record(&self.data, command_buffer, image, layout, extent, self.swapchain_data.swapchain_format)?);
+            self.capture_requested = false;
+        }
+
+        unsafe {
             logical_device.end_command_buffer(command_buffer)?;
         }
 
@@ -365,10 +648,9 @@ impl RHIVulkan {
     fn update_secondary_command_buffer(&self, command_buffer: CommandBuffer, image_index: usize) -> Result<()> {
         //ToDo:
         let world = self.world.as_ref().unwrap().read().unwrap();
-        let entities = world.get_entities();
 
-        let model = entities[0].transform.matrix();
-        let model_bytes = unsafe { slice::from_raw_parts(&model as *const Matrix4x4 as *const u8, size_of::<Matrix4x4>()) };
+        let camera_pos = world.interpolated_camera().transform().location();
+        let alpha = world.interpolation_alpha();
 
         // let command_buffer = self.get_or_add_secondary_buffer(&image_index, buffer_index);
 
@@ -388,14 +670,25 @@ impl RHIVulkan {
             logical_device.begin_command_buffer(command_buffer, &info)?;
             logical_device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::GRAPHICS, self.pipeline_data.pipeline);
 
-            logical_device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.pipeline_data.vertex_buffer], &[0]);
-            logical_device.cmd_bind_index_buffer(command_buffer, self.pipeline_data.index_buffer, 0, IndexType::UINT16);
-
             logical_device.cmd_bind_descriptor_sets(command_buffer, PipelineBindPoint::GRAPHICS, self.pipeline_data.pipeline_layout, 0, &[self.pipeline_data.descriptor_sets[image_index]], &[]);
+        }
 
-            logical_device.cmd_push_constants(command_buffer, self.pipeline_data.pipeline_layout, ShaderStageFlags::VERTEX, 0, model_bytes);
+        self.planet_data.record(logical_device, command_buffer, self.pipeline_data.pipeline_layout, self.mesh_registry.default_texture(), camera_pos, &self.atmosphere_sample_data);
 
-            logical_device.cmd_draw_indexed(command_buffer, INDICES.len() as u32, 1, 0, 0, 0);
+        //Entities whose mesh was unregistered are skipped. Transforms are blended between the last two ticks
+        for (entity, mesh_ref, _) in world.ecs().query2::<MeshRef, GlobalTransform>() {
+            let Some(mesh) = self.mesh_registry.get(mesh_ref.0) else {
+                continue;
+            };
+
+            let model = hierarchy::interpolated_matrix(world.ecs(), entity, alpha);
+            mesh.record_draw(logical_device, command_buffer, self.pipeline_data.pipeline_layout, model, self.mesh_registry.default_texture());
+        }
+
+        unsafe {
+            //Sky fills whatever the scene left at the far plane
+            logical_device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::GRAPHICS, self.pipeline_data.sky_pipeline);
+            logical_device.cmd_draw(command_buffer, 3, 1, 0, 0);
 
             logical_device.end_command_buffer(command_buffer)?
         }
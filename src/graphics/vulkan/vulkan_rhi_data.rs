@@ -1,18 +1,20 @@
 use crate::config::config::{GraphicsConfig, LogLevel};
 use crate::graphics::vulkan::vulkan_swapchain::SwapchainSupport;
-use crate::graphics::vulkan::vulkan_utils::{debug_callback, CompatibilityError, QueueFamilyIndices, DEVICE_EXTENSIONS, PORTABILITY_MACOS_VERSION, VALIDATION_LAYER};
+use crate::graphics::vulkan::vulkan_utils::{debug_callback, CompatibilityError, QueueFamilyIndices, DEVICE_EXTENSIONS, HEADLESS_DEVICE_EXTENSIONS, PORTABILITY_MACOS_VERSION, VALIDATION_LAYER};
 use anyhow::anyhow;
 use anyhow::Result;
 use log::{info, warn};
 use std::collections::HashSet;
 use vulkanalia::loader::{LibloadingLoader, LIBRARY};
-use vulkanalia::vk::{ApplicationInfo, DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT, DebugUtilsMessengerCreateInfoEXT, DebugUtilsMessengerEXT, DeviceCreateInfo, DeviceQueueCreateInfo, DeviceV1_0, EntryV1_0, ExtDebugUtilsExtension, HasBuilder, InstanceV1_0, KhrSurfaceExtension, PhysicalDevice, PhysicalDeviceFeatures, Queue, SurfaceKHR};
+use vulkanalia::vk::{ApplicationInfo, DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT, DebugUtilsMessengerCreateInfoEXT, DebugUtilsMessengerEXT, DeviceCreateInfo, DeviceQueueCreateInfo, DeviceV1_0, EntryV1_0, ExtDebugUtilsExtension, ExtensionName, Handle, HasBuilder, InstanceV1_0, KhrSurfaceExtension, PhysicalDevice, PhysicalDeviceFeatures, Queue, SurfaceKHR};
 use vulkanalia::window as vk_window;
 use vulkanalia::window::create_surface;
 use vulkanalia::{vk, Device, Entry, Instance};
 use winit::window::Window;
 
 pub struct VulkanRHIData {
+    //Keeps the loaded Vulkan library alive
+    #[allow(dead_code)]
     pub(crate) entry: Entry,
     pub(crate) instance: Instance,
     pub(crate) messenger: DebugUtilsMessengerEXT,
@@ -21,15 +23,24 @@ pub struct VulkanRHIData {
 
     pub(crate) graphics_queue: Queue,
     pub(crate) present_queue: Queue,
+    //Null when running headless
     pub(crate) surface: SurfaceKHR,
 }
 
 impl VulkanRHIData {
+    pub fn is_headless(&self) -> bool {
+        self.surface.is_null()
+    }
+
     pub fn destroy(&self) {
         unsafe {
             self.logical_device.destroy_device(None);
             self.instance.destroy_debug_utils_messenger_ext(self.messenger, None);
-            self.instance.destroy_surface_khr(self.surface, None);
+
+            if !self.is_headless() {
+                self.instance.destroy_surface_khr(self.surface, None);
+            }
+
             self.instance.destroy_instance(None);
         }
     }
@@ -59,8 +70,17 @@ impl VulkanRHIDataBuilder {
     }
 
     pub fn build(self, window: &Window) -> Result<VulkanRHIData> {
+        self.create(Some(window))
+    }
+
+    //No surface is created and presentation support is not required from the device
+    pub fn build_headless(self) -> Result<VulkanRHIData> {
+        self.create(None)
+    }
+
+    fn create(self, window: Option<&Window>) -> Result<VulkanRHIData> {
         let loader = unsafe { LibloadingLoader::new(LIBRARY) }?;
-        let entry = unsafe { Entry::new(loader) }.unwrap();
+        let entry = unsafe { Entry::new(loader) }.map_err(|e| anyhow!("{}", e))?;
         let instance = self.create_instance(window, &entry, &self.config)?;
 
         let mut messenger = DebugUtilsMessengerEXT::default();
@@ -80,7 +100,10 @@ impl VulkanRHIDataBuilder {
             messenger = unsafe { instance.create_debug_utils_messenger_ext(&debug_info, None) }?;
         }
 
-        let surface = unsafe { create_surface(&instance, &window, &window) }?;
+        let surface = match window {
+            Some(window) => unsafe { create_surface(&instance, &window, &window) }?,
+            None => SurfaceKHR::null()
+        };
 
         let physical_device = self.pick_physical_device(&instance, surface)?;
         let logical_device = self.create_logical_device(&instance, physical_device, surface)?;
@@ -102,7 +125,7 @@ impl VulkanRHIDataBuilder {
         })
     }
 
-    fn create_instance(&self, window: &Window, entry: &Entry, config: &GraphicsConfig) -> Result<Instance> {
+    fn create_instance(&self, window: Option<&Window>, entry: &Entry, config: &GraphicsConfig) -> Result<Instance> {
         // let app_info = vk::ApplicationInfo::builder()
         //     .application_version(vk::make_version(0, 1, 0))
         //     .api_version(vk::make_version(1, 0, 0))
@@ -111,7 +134,9 @@ impl VulkanRHIDataBuilder {
         //     .engine_name(b"Test Engine")
         //     .build();
 
-        let mut extensions = vk_window::get_required_instance_extensions(window)
+        let mut extensions = window
+            .map(|w| vk_window::get_required_instance_extensions(w))
+            .unwrap_or(&[])
             .iter()
             .map(|e| e.as_ptr())
             .collect::<Vec<_>>();
@@ -197,8 +222,12 @@ impl VulkanRHIDataBuilder {
     }
 
     fn check_physical_device_compatibility(instance: &Instance, physical_device: PhysicalDevice, surface: SurfaceKHR) -> Result<()> {
-        let _ = QueueFamilyIndices::get(instance, physical_device, surface)?;
-        let _ = Self::check_physical_device_extensions(instance, physical_device)?;
+        QueueFamilyIndices::get(instance, physical_device, surface)?;
+        Self::check_physical_device_extensions(instance, physical_device, Self::device_extensions(surface))?;
+
+        if surface.is_null() {
+            return Ok(());
+        }
 
         let support = SwapchainSupport::get(instance, physical_device, surface)?;
         if support.formats.is_empty() || support.present_modes.is_empty() {
@@ -208,10 +237,18 @@ impl VulkanRHIDataBuilder {
         Ok(())
     }
 
-    fn check_physical_device_extensions(instance: &Instance, physical_device: PhysicalDevice) -> Result<()> {
+    fn device_extensions(surface: SurfaceKHR) -> &'static [ExtensionName] {
+        if surface.is_null() {
+            HEADLESS_DEVICE_EXTENSIONS
+        } else {
+            DEVICE_EXTENSIONS
+        }
+    }
+
+    fn check_physical_device_extensions(instance: &Instance, physical_device: PhysicalDevice, required: &[ExtensionName]) -> Result<()> {
         let extensions = unsafe { instance.enumerate_device_extension_properties(physical_device, None)?.iter().map(|e| e.extension_name).collect::<HashSet<_>>() };
         //Check for graphics commands
-        let is_supported = DEVICE_EXTENSIONS.iter().all(|e| extensions.contains(e));
+        let is_supported = required.iter().all(|e| extensions.contains(e));
         if is_supported {
             Ok(())
         }
@@ -234,7 +271,7 @@ impl VulkanRHIDataBuilder {
             vec![]
         };
 
-        let extensions = DEVICE_EXTENSIONS
+        let extensions = Self::device_extensions(surface)
             .iter()
             .map(|n| n.as_ptr())
             .collect::<Vec<_>>();
@@ -1,23 +1,37 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use std::cmp::min;
 
 use crate::graphics::vulkan::vulkan_rhi_data::VulkanRHIData;
-use crate::graphics::vulkan::vulkan_utils::RHIDestroy;
-use vulkanalia::vk::{ColorSpaceKHR, CompositeAlphaFlagsKHR, DeviceMemory, DeviceV1_0, Extent2D, Format, Handle, HasBuilder, Image, ImageAspectFlags, ImageSubresourceRange, ImageTiling, ImageUsageFlags, ImageView, ImageViewCreateInfo, InstanceV1_0, KhrSurfaceExtension, KhrSwapchainExtension, MemoryPropertyFlags, MemoryRequirements, PhysicalDevice, PresentModeKHR, SharingMode, SurfaceCapabilitiesKHR, SurfaceFormatKHR, SurfaceKHR, SwapchainCreateInfoKHR, SwapchainKHR};
+use crate::graphics::vulkan::vulkan_utils::{create_image, create_image_view, RHIDestroy};
+use vulkanalia::vk::{ColorSpaceKHR, CompositeAlphaFlagsKHR, DeviceMemory, DeviceV1_0, Extent2D, Format, Handle, HasBuilder, Image, ImageAspectFlags, ImageTiling, ImageUsageFlags, ImageView, KhrSurfaceExtension, KhrSwapchainExtension, MemoryPropertyFlags, PhysicalDevice, PresentModeKHR, SharingMode, SurfaceCapabilitiesKHR, SurfaceFormatKHR, SurfaceKHR, SwapchainCreateInfoKHR, SwapchainKHR};
 use vulkanalia::{vk, Device, Instance};
 use winit::window::Window;
 
+pub(crate) const OFFSCREEN_FORMAT: Format = Format::B8G8R8A8_SRGB;
+//Scene color before exposure and tonemapping
+pub(crate) const HDR_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+
+//Holds either a real swapchain or, when rendering offscreen, device-local color images
+//owned by us in its place (swapchain is null in that case)
 #[derive(Debug, Default)]
 pub(crate) struct SwapchainData {
     pub swapchain: SwapchainKHR,
     pub swapchain_images: Vec<Image>,
+    pub swapchain_images_memory: Vec<DeviceMemory>,
     pub swapchain_format: Format,
     pub swapchain_extent: Extent2D,
+    //Images can be copied from for frame captures
+    pub supports_capture: bool,
     pub swapchain_image_views: Vec<ImageView>,
 
     pub depth_image: Image,
     pub depth_image_memory: DeviceMemory,
-    pub depth_image_view: ImageView
+    pub depth_image_view: ImageView,
+
+    //One per swapchain image, rendered by the scene pass and sampled by post-processing
+    pub hdr_images: Vec<Image>,
+    pub hdr_images_memory: Vec<DeviceMemory>,
+    pub hdr_image_views: Vec<ImageView>
 }
 
 impl RHIDestroy for SwapchainData {
@@ -27,17 +41,45 @@ impl RHIDestroy for SwapchainData {
             rhi_data.logical_device.free_memory(self.depth_image_memory, None);
             rhi_data.logical_device.destroy_image(self.depth_image, None);
 
+            self.hdr_image_views
+                .iter()
+                .for_each(|v| rhi_data.logical_device.destroy_image_view(*v, None));
+
+            self.hdr_images
+                .iter()
+                .zip(self.hdr_images_memory.iter())
+                .for_each(|(i, m)| {
+                    rhi_data.logical_device.destroy_image(*i, None);
+                    rhi_data.logical_device.free_memory(*m, None);
+                });
+
             self.swapchain_image_views
                 .iter()
                 .for_each(|v| rhi_data.logical_device.destroy_image_view(*v, None));
 
-            rhi_data.logical_device.destroy_swapchain_khr(self.swapchain, None);
+            if self.is_offscreen() {
+                self.swapchain_images
+                    .iter()
+                    .zip(self.swapchain_images_memory.iter())
+                    .for_each(|(i, m)| {
+                        rhi_data.logical_device.destroy_image(*i, None);
+                        rhi_data.logical_device.free_memory(*m, None);
+                    });
+            } else {
+                rhi_data.logical_device.destroy_swapchain_khr(self.swapchain, None);
+            }
         }
     }
 }
 
+impl SwapchainData {
+    pub fn is_offscreen(&self) -> bool {
+        self.swapchain.is_null()
+    }
+}
+
 #[derive(Default)]
-pub struct SwapchainDataBuilder{
+pub(crate) struct SwapchainDataBuilder{
 }
 
 impl SwapchainDataBuilder {
@@ -52,16 +94,57 @@ impl SwapchainDataBuilder {
         let swapchain_image_views = Self::create_swapchain_image_views(&swapchain_images, &rhi_data.logical_device, surface_format)?;
 
         let (depth_image, depth_image_memory, depth_image_view) = self.create_depth_objects(rhi_data, extent)?;
+        let (hdr_images, hdr_images_memory, hdr_image_views) = self.create_hdr_objects(rhi_data, extent, swapchain_images.len())?;
 
         Ok(SwapchainData {
                 swapchain,
                 swapchain_format: surface_format,
                 swapchain_extent: extent,
+                supports_capture: capabilities.supported_usage_flags.contains(ImageUsageFlags::TRANSFER_SRC),
                 swapchain_images,
+                swapchain_images_memory: Vec::new(),
                 swapchain_image_views,
                 depth_image,
                 depth_image_memory,
-                depth_image_view
+                depth_image_view,
+                hdr_images,
+                hdr_images_memory,
+                hdr_image_views
+            }
+        )
+    }
+
+    pub fn build_offscreen(self, extent: Extent2D, rhi_data: &VulkanRHIData) -> Result<SwapchainData> {
+        let (color_image, color_image_memory) = self.create_image(
+            rhi_data,
+            extent.width,
+            extent.height,
+            OFFSCREEN_FORMAT,
+            ImageTiling::OPTIMAL,
+            ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::TRANSFER_SRC,
+            MemoryPropertyFlags::DEVICE_LOCAL
+        )?;
+
+        let swapchain_images = vec![color_image];
+        let swapchain_image_views = Self::create_swapchain_image_views(&swapchain_images, &rhi_data.logical_device, OFFSCREEN_FORMAT)?;
+
+        let (depth_image, depth_image_memory, depth_image_view) = self.create_depth_objects(rhi_data, extent)?;
+        let (hdr_images, hdr_images_memory, hdr_image_views) = self.create_hdr_objects(rhi_data, extent, swapchain_images.len())?;
+
+        Ok(SwapchainData {
+                swapchain: SwapchainKHR::null(),
+                swapchain_format: OFFSCREEN_FORMAT,
+                swapchain_extent: extent,
+                supports_capture: true,
+                swapchain_images,
+                swapchain_images_memory: vec![color_image_memory],
+                swapchain_image_views,
+                depth_image,
+                depth_image_memory,
+                depth_image_view,
+                hdr_images,
+                hdr_images_memory,
+                hdr_image_views
             }
         )
     }
@@ -75,6 +158,9 @@ impl SwapchainDataBuilder {
         //Sharing mode between graphics and presentation queue. We rely on them being the same one, so we use Exclusive
         let image_sharing_mode = SharingMode::EXCLUSIVE;
 
+        //Transfer source is needed to read frames back for capture, where supported
+        let image_usage = ImageUsageFlags::COLOR_ATTACHMENT | (capabilities.supported_usage_flags & ImageUsageFlags::TRANSFER_SRC);
+
         let info = SwapchainCreateInfoKHR::builder()
             .surface(rhi_data.surface)
             .min_image_count(image_count)
@@ -82,7 +168,7 @@ impl SwapchainDataBuilder {
             .image_color_space(surface_format.color_space)
             .image_extent(extent)
             .image_array_layers(1)
-            .image_usage(ImageUsageFlags::COLOR_ATTACHMENT)
+            .image_usage(image_usage)
             .image_sharing_mode(image_sharing_mode)
             .pre_transform(capabilities.current_transform)
             .composite_alpha(CompositeAlphaFlagsKHR::OPAQUE)
@@ -127,21 +213,19 @@ impl SwapchainDataBuilder {
     }
 
     fn get_swapchain_surface_format(formats: &[SurfaceFormatKHR]) -> SurfaceFormatKHR {
-        formats
+        *formats
             .iter()
             .find(|f| {
                 f.format == Format::B8G8R8A8_SRGB && f.color_space == ColorSpaceKHR::SRGB_NONLINEAR
             })
-            .unwrap_or_else(|| &formats[0])
-            .clone()
+            .unwrap_or(&formats[0])
     }
 
     fn get_swapchain_present_mode(present_modes: &[PresentModeKHR]) -> PresentModeKHR {
-        present_modes
+        *present_modes
             .iter()
             .find(|m| **m == PresentModeKHR::MAILBOX)
             .unwrap_or(&PresentModeKHR::FIFO)
-            .clone()
     }
 
     fn get_swapchain_extent(window: &Window, capabilities: SurfaceCapabilitiesKHR) -> Extent2D {
@@ -178,24 +262,35 @@ impl SwapchainDataBuilder {
         Ok((depth_image, depth_image_memory, image_view))
     }
 
-    fn create_image_view(&self, rhi_data: &VulkanRHIData, image: Image, format: Format, aspects: ImageAspectFlags) -> Result<ImageView> {
-        let subresource_range = ImageSubresourceRange::builder()
-            .aspect_mask(aspects)
-            .base_mip_level(0)
-            .level_count(1)
-            .base_array_layer(0)
-            .layer_count(1);
+    fn create_hdr_objects(&self, rhi_data: &VulkanRHIData, swapchain_extent: Extent2D, count: usize) -> Result<(Vec<Image>, Vec<DeviceMemory>, Vec<ImageView>)> {
+        let mut images = Vec::with_capacity(count);
+        let mut images_memory = Vec::with_capacity(count);
+        let mut image_views = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let (image, image_memory) = self.create_image(
+                rhi_data,
+                swapchain_extent.width,
+                swapchain_extent.height,
+                HDR_FORMAT,
+                ImageTiling::OPTIMAL,
+                ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::SAMPLED,
+                MemoryPropertyFlags::DEVICE_LOCAL
+            )?;
+
+            images.push(image);
+            images_memory.push(image_memory);
+            image_views.push(self.create_image_view(rhi_data, image, HDR_FORMAT, ImageAspectFlags::COLOR)?);
+        }
 
-        let info = ImageViewCreateInfo::builder()
-            .image(image)
-            .view_type(vk::ImageViewType::_2D)
-            .format(format)
-            .subresource_range(subresource_range);
+        Ok((images, images_memory, image_views))
+    }
 
-        let image_view = unsafe { rhi_data.logical_device.create_image_view(&info, None) }?;
-        Ok(image_view)
+    fn create_image_view(&self, rhi_data: &VulkanRHIData, image: Image, format: Format, aspects: ImageAspectFlags) -> Result<ImageView> {
+        create_image_view(rhi_data, image, format, aspects)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn create_image(
         &self,
         rhi_data: &VulkanRHIData,
@@ -206,56 +301,7 @@ impl SwapchainDataBuilder {
         usage: ImageUsageFlags,
         properties: MemoryPropertyFlags,
     ) -> Result<(Image, DeviceMemory)> {
-        // Image
-
-        let info = vk::ImageCreateInfo::builder()
-            .image_type(vk::ImageType::_2D)
-            .extent(vk::Extent3D {
-                width,
-                height,
-                depth: 1,
-            })
-            .mip_levels(1)
-            .array_layers(1)
-            .format(format)
-            .tiling(tiling)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .usage(usage)
-            .sharing_mode(vk::SharingMode::EXCLUSIVE)
-            .samples(vk::SampleCountFlags::_1);
-
-        let logical_device = &rhi_data.logical_device;
-        let image = unsafe { logical_device.create_image(&info, None) }.unwrap();
-
-        // Memory
-
-        let requirements = unsafe { logical_device.get_image_memory_requirements(image) };
-
-        let info = vk::MemoryAllocateInfo::builder()
-            .allocation_size(requirements.size)
-            .memory_type_index(self.get_memory_type_index(rhi_data, properties, requirements)?);
-
-        let image_memory = unsafe { logical_device.allocate_memory(&info, None) }?;
-
-        unsafe { logical_device.bind_image_memory(image, image_memory, 0) }?;
-
-        Ok((image, image_memory))
-    }
-
-    //ToDo: Unify with pipeline
-    fn get_memory_type_index(&self, rhi_data: &VulkanRHIData, properties: MemoryPropertyFlags, requirements: MemoryRequirements) -> Result<u32> {
-        let instance = &rhi_data.instance;
-        let physical_device = rhi_data.physical_device;
-
-        let memory = unsafe { instance.get_physical_device_memory_properties(physical_device) };
-
-        (0..memory.memory_type_count)
-            .find(|i| {
-                let suitable = (requirements.memory_type_bits & (1u32 << i)) != 0;
-                let memory_type = memory.memory_types[*i as usize];
-                suitable && memory_type.property_flags.contains(properties)
-            })
-            .ok_or_else(|| anyhow!("Failed to find suitable memory type"))
+        create_image(rhi_data, width, height, format, tiling, usage, properties)
     }
 }
 
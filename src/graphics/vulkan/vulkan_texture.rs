@@ -0,0 +1,191 @@
+use std::ptr::copy_nonoverlapping;
+
+use anyhow::Result;
+use vulkanalia::vk::{AccessFlags, BorderColor, BufferImageCopy, BufferMemoryBarrier, BufferUsageFlags, CommandPool, CompareOp, CopyDescriptorSet, DependencyFlags, DescriptorImageInfo, DescriptorPool, DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType, DeviceMemory, DeviceV1_0, Extent3D, Filter, Format, HasBuilder, Image, ImageAspectFlags, ImageLayout, ImageMemoryBarrier, ImageSubresourceLayers, ImageSubresourceRange, ImageTiling, ImageUsageFlags, ImageView, MemoryBarrier, MemoryMapFlags, MemoryPropertyFlags, Offset3D, PipelineStageFlags, Sampler, SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode, ShaderStageFlags, WriteDescriptorSet, QUEUE_FAMILY_IGNORED};
+
+use crate::assets::material::Texture;
+use crate::graphics::vulkan::vulkan_rhi_data::VulkanRHIData;
+use crate::graphics::vulkan::vulkan_utils::{create_buffer, create_image, create_image_view, submit_one_time, RHIDestroy};
+
+//Base color textures are stored sRGB encoded, sampling returns linear colors
+const TEXTURE_FORMAT: Format = Format::R8G8B8A8_SRGB;
+
+//Layout of set 1 of the scene pipeline: the base color texture at binding 0
+pub fn create_texture_set_layout(rhi_data: &VulkanRHIData) -> Result<DescriptorSetLayout> {
+    let binding = DescriptorSetLayoutBinding::builder()
+        .binding(0)
+        .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .descriptor_count(1)
+        .stage_flags(ShaderStageFlags::FRAGMENT);
+
+    let bindings = &[binding];
+    let info = DescriptorSetLayoutCreateInfo::builder().bindings(bindings);
+
+    Ok(unsafe { rhi_data.logical_device.create_descriptor_set_layout(&info, None) }?)
+}
+
+//Repeats like glTF's default sampler, the samplers of the file are not imported
+pub fn create_texture_sampler(rhi_data: &VulkanRHIData) -> Result<Sampler> {
+    let info = SamplerCreateInfo::builder()
+        .mag_filter(Filter::LINEAR)
+        .min_filter(Filter::LINEAR)
+        .address_mode_u(SamplerAddressMode::REPEAT)
+        .address_mode_v(SamplerAddressMode::REPEAT)
+        .address_mode_w(SamplerAddressMode::REPEAT)
+        .anisotropy_enable(false)
+        .border_color(BorderColor::INT_OPAQUE_BLACK)
+        .unnormalized_coordinates(false)
+        .compare_enable(false)
+        .compare_op(CompareOp::ALWAYS)
+        .mipmap_mode(SamplerMipmapMode::LINEAR);
+
+    Ok(unsafe { rhi_data.logical_device.create_sampler(&info, None) }?)
+}
+
+//Device-local copy of a Texture with its own descriptor set, see create_texture_set_layout
+#[derive(Debug, Default)]
+pub struct GpuTexture {
+    image: Image,
+    image_memory: DeviceMemory,
+    image_view: ImageView,
+    descriptor_pool: DescriptorPool,
+    pub descriptor_set: DescriptorSet,
+}
+
+impl GpuTexture {
+    //Blocks until the upload is done
+    pub fn upload(rhi_data: &VulkanRHIData, command_pool: CommandPool, texture: &Texture, layout: DescriptorSetLayout, sampler: Sampler) -> Result<Self> {
+        let logical_device = &rhi_data.logical_device;
+
+        let size = texture.pixels.len() as u64;
+        let (staging_buffer, staging_buffer_memory) = create_buffer(rhi_data, size, BufferUsageFlags::TRANSFER_SRC,
+                                                                    MemoryPropertyFlags::HOST_COHERENT | MemoryPropertyFlags::HOST_VISIBLE)?;
+
+        unsafe {
+            let memory = logical_device.map_memory(staging_buffer_memory, 0, size, MemoryMapFlags::empty())?;
+            copy_nonoverlapping(texture.pixels.as_ptr(), memory.cast(), texture.pixels.len());
+            logical_device.unmap_memory(staging_buffer_memory);
+        }
+
+        let (image, image_memory) = create_image(rhi_data, texture.width, texture.height, TEXTURE_FORMAT, ImageTiling::OPTIMAL,
+                                                 ImageUsageFlags::TRANSFER_DST | ImageUsageFlags::SAMPLED, MemoryPropertyFlags::DEVICE_LOCAL)?;
+
+        let subresource_range = ImageSubresourceRange::builder()
+            .aspect_mask(ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1);
+
+        let to_transfer = ImageMemoryBarrier::builder()
+            .old_layout(ImageLayout::UNDEFINED)
+            .new_layout(ImageLayout::TRANSFER_DST_OPTIMAL)
+            .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range)
+            .src_access_mask(AccessFlags::empty())
+            .dst_access_mask(AccessFlags::TRANSFER_WRITE);
+
+        let to_shader = ImageMemoryBarrier::builder()
+            .old_layout(ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range)
+            .src_access_mask(AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(AccessFlags::SHADER_READ);
+
+        let subresource = ImageSubresourceLayers::builder()
+            .aspect_mask(ImageAspectFlags::COLOR)
+            .mip_level(0)
+            .base_array_layer(0)
+            .layer_count(1);
+
+        let region = BufferImageCopy::builder()
+            .buffer_offset(0)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(subresource)
+            .image_offset(Offset3D::default())
+            .image_extent(Extent3D { width: texture.width, height: texture.height, depth: 1 });
+
+        let uploaded = submit_one_time(rhi_data, command_pool, |command_buffer| unsafe {
+            logical_device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::TOP_OF_PIPE, PipelineStageFlags::TRANSFER,
+                                                DependencyFlags::empty(), &[] as &[MemoryBarrier], &[] as &[BufferMemoryBarrier], &[to_transfer]);
+
+            logical_device.cmd_copy_buffer_to_image(command_buffer, staging_buffer, image, ImageLayout::TRANSFER_DST_OPTIMAL, &[region]);
+
+            logical_device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::TRANSFER, PipelineStageFlags::FRAGMENT_SHADER,
+                                                DependencyFlags::empty(), &[] as &[MemoryBarrier], &[] as &[BufferMemoryBarrier], &[to_shader]);
+        });
+
+        unsafe {
+            logical_device.destroy_buffer(staging_buffer, None);
+            logical_device.free_memory(staging_buffer_memory, None);
+        }
+
+        let mut result = Self { image, image_memory, ..Default::default() };
+        if let Err(e) = uploaded.and_then(|_| result.create_descriptor_set(rhi_data, layout, sampler)) {
+            result.destroy(rhi_data);
+            return Err(e);
+        }
+
+        Ok(result)
+    }
+
+    fn create_descriptor_set(&mut self, rhi_data: &VulkanRHIData, layout: DescriptorSetLayout, sampler: Sampler) -> Result<()> {
+        let logical_device = &rhi_data.logical_device;
+
+        self.image_view = create_image_view(rhi_data, self.image, TEXTURE_FORMAT, ImageAspectFlags::COLOR)?;
+
+        let pool_size = DescriptorPoolSize::builder()
+            .type_(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1);
+
+        let pool_sizes = &[pool_size];
+        let pool_info = DescriptorPoolCreateInfo::builder()
+            .pool_sizes(pool_sizes)
+            .max_sets(1);
+
+        self.descriptor_pool = unsafe { logical_device.create_descriptor_pool(&pool_info, None) }?;
+
+        let layouts = &[layout];
+        let allocate_info = DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.descriptor_pool)
+            .set_layouts(layouts);
+
+        self.descriptor_set = unsafe { logical_device.allocate_descriptor_sets(&allocate_info) }?[0];
+
+        let image_info = DescriptorImageInfo::builder()
+            .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(self.image_view)
+            .sampler(sampler);
+
+        let image_infos = &[image_info];
+        let write = WriteDescriptorSet::builder()
+            .dst_set(self.descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(image_infos);
+
+        unsafe { logical_device.update_descriptor_sets(&[write], &[] as &[CopyDescriptorSet]) };
+
+        Ok(())
+    }
+}
+
+impl RHIDestroy for GpuTexture {
+    fn destroy(&mut self, rhi_data: &VulkanRHIData) {
+        let logical_device = &rhi_data.logical_device;
+
+        unsafe {
+            logical_device.destroy_descriptor_pool(self.descriptor_pool, None);
+            logical_device.destroy_image_view(self.image_view, None);
+            logical_device.destroy_image(self.image, None);
+            logical_device.free_memory(self.image_memory, None);
+        }
+    }
+}
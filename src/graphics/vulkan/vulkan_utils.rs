@@ -1,17 +1,18 @@
 use std::ffi::CStr;
+use std::mem::size_of_val;
 use std::os::raw::c_void;
+use std::ptr::copy_nonoverlapping;
 
 use anyhow::anyhow;
-use cgmath::{vec3, vec4, Angle, Deg, Rad};
+use cgmath::{Angle, Deg, Rad};
 use log::{debug, error, trace, warn};
 use thiserror::Error;
-use vulkanalia::vk::{ExtensionName, InstanceV1_0, KhrSurfaceExtension, PhysicalDevice, QueueFlags, SurfaceKHR, KHR_SHADER_NON_SEMANTIC_INFO_EXTENSION, KHR_SWAPCHAIN_EXTENSION};
+use vulkanalia::vk::{Buffer, BufferCopy, BufferCreateInfo, BufferUsageFlags, CommandBufferAllocateInfo, CommandBufferBeginInfo, CommandBufferLevel, CommandBufferUsageFlags, CommandPool, DeviceMemory, DeviceSize, DeviceV1_0, CommandBuffer, ExtensionName, Fence, Format, Handle, HasBuilder, Image, ImageAspectFlags, ImageSubresourceRange, ImageTiling, ImageUsageFlags, ImageView, ImageViewCreateInfo, InstanceV1_0, KhrSurfaceExtension, MemoryAllocateInfo, MemoryMapFlags, MemoryPropertyFlags, MemoryRequirements, PhysicalDevice, QueueFlags, ShaderModule, ShaderModuleCreateInfo, SharingMode, SubmitInfo, SurfaceKHR, KHR_SHADER_NON_SEMANTIC_INFO_EXTENSION, KHR_SWAPCHAIN_EXTENSION};
+use vulkanalia::bytecode::Bytecode;
 use vulkanalia::{vk, Instance, Version};
 
 use crate::graphics::vulkan::transformation::Matrix4x4;
-use crate::graphics::vulkan::vertex::Vertex;
 use crate::graphics::vulkan::vulkan_rhi_data::VulkanRHIData;
-use crate::utils::math::{VECTOR3_BACKWARD, VECTOR3_DOWN, VECTOR3_FORWARD, VECTOR3_LEFT, VECTOR3_RIGHT, VECTOR3_UP};
 
 pub(crate) const PORTABILITY_MACOS_VERSION: Version = Version::new(1, 3, 216);
 
@@ -22,104 +23,12 @@ pub(crate) const VALIDATION_LAYER: vk::ExtensionName =
 
 pub(crate) const DEVICE_EXTENSIONS: &[ExtensionName] = &[KHR_SWAPCHAIN_EXTENSION.name, KHR_SHADER_NON_SEMANTIC_INFO_EXTENSION.name];
 
+pub(crate) const HEADLESS_DEVICE_EXTENSIONS: &[ExtensionName] = &[KHR_SHADER_NON_SEMANTIC_INFO_EXTENSION.name];
+
 #[derive(Debug, Error)]
 #[error("Suitability Error: {0}.")]
 pub struct CompatibilityError(pub &'static str);
 
-//Cube without normals with shared vertices
-// pub static VERTICES: [Vertex; 8] = [
-//     Vertex::new(Vector3::new(-1.0, -1.0, -1.0), Vector3::zero(),  Vector4::new(0.1, 0.1, 0.1, 1.0)), //0
-//     Vertex::new(Vector3::new(1.0, -1.0, -1.0), Vector3::zero(), Vector4::new(1.0, 0.0, 0.0, 1.0)), //1
-//     Vertex::new(Vector3::new(1.0, 1.0, -1.0), Vector3::zero(), Vector4::new(1.0, 1.0, 0.0, 1.0)), //2
-//
-//     Vertex::new(Vector3::new(-1.0, 1.0, -1.0), Vector3::zero(), Vector4::new(0.0, 1.0, 0.0, 1.0)), //3
-//     Vertex::new(Vector3::new(-1.0, -1.0, 1.0), Vector3::zero(), Vector4::new(0.0, 0.0, 1.0, 1.0)), //4
-//     Vertex::new(Vector3::new(1.0, -1.0, 1.0), Vector3::zero(), Vector4::new(1.0, 0.0, 1.0, 1.0)), //5
-//
-//     Vertex::new(Vector3::new(1.0, 1.0, 1.0), Vector3::zero(), Vector4::new(1.0, 1.0, 1.0, 1.0)),  //6
-//     Vertex::new(Vector3::new(-1.0, 1.0, 1.0), Vector3::zero(), Vector4::new(0.0, 1.0, 1.0, 1.0)), //7
-// ];
-//
-//
-// pub static INDICES: &[u16] = &[
-//     0, 1, 3, 3, 1, 2,
-//     1, 5, 2, 2, 5, 6,
-//     5, 4, 6, 6, 4, 7,
-//     4, 0, 7, 7, 0, 3,
-//     3, 2, 7, 7, 2, 6,
-//     4, 5, 0, 0, 5, 1
-// ];
-
-//Cube with per vertex normals
-pub static VERTICES: [Vertex; 24] = [
-    // Front face
-    Vertex::new(vec3(-1.0, -1.0,  1.0), VECTOR3_BACKWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-left
-    Vertex::new(vec3( 1.0, -1.0,  1.0), VECTOR3_BACKWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-right
-    Vertex::new(vec3( 1.0,  1.0,  1.0), VECTOR3_BACKWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-right
-    Vertex::new(vec3(-1.0,  1.0,  1.0), VECTOR3_BACKWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-left
-
-    // Back face
-    Vertex::new(vec3(-1.0, -1.0, -1.0), VECTOR3_FORWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-left
-    Vertex::new(vec3( 1.0, -1.0, -1.0), VECTOR3_FORWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-right
-    Vertex::new(vec3( 1.0,  1.0, -1.0), VECTOR3_FORWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-right
-    Vertex::new(vec3(-1.0,  1.0, -1.0), VECTOR3_FORWARD, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-left
-
-    // Left face
-    Vertex::new(vec3(-1.0,  1.0,  1.0), VECTOR3_LEFT, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-left
-    Vertex::new(vec3(-1.0,  1.0, -1.0), VECTOR3_LEFT, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-right
-    Vertex::new(vec3(-1.0, -1.0, -1.0), VECTOR3_LEFT, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-right
-    Vertex::new(vec3(-1.0, -1.0,  1.0), VECTOR3_LEFT, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-left
-
-    // Right face
-    Vertex::new(vec3( 1.0,  1.0,  1.0), VECTOR3_RIGHT, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-left
-    Vertex::new(vec3( 1.0,  1.0, -1.0), VECTOR3_RIGHT, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-right
-    Vertex::new(vec3( 1.0, -1.0, -1.0), VECTOR3_RIGHT, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-right
-    Vertex::new(vec3( 1.0, -1.0,  1.0), VECTOR3_RIGHT, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-left
-
-    // Top face
-    Vertex::new(vec3(-1.0,  1.0, -1.0), VECTOR3_UP, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-left
-    Vertex::new(vec3( 1.0,  1.0, -1.0), VECTOR3_UP, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-right
-    Vertex::new(vec3( 1.0,  1.0,  1.0), VECTOR3_UP, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-right
-    Vertex::new(vec3(-1.0,  1.0,  1.0), VECTOR3_UP, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-left
-
-    // Bottom face
-    Vertex::new(vec3(-1.0, -1.0, -1.0), VECTOR3_DOWN, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-left
-    Vertex::new(vec3( 1.0, -1.0, -1.0), VECTOR3_DOWN, vec4(0.5, 0.5, 0.5, 1.0)),  // Top-right
-    Vertex::new(vec3( 1.0, -1.0,  1.0), VECTOR3_DOWN, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-right
-    Vertex::new(vec3(-1.0, -1.0,  1.0), VECTOR3_DOWN, vec4(0.5, 0.5, 0.5, 1.0)),  // Bottom-left
-];
-
-
-pub static INDICES: &[u16] = &[
-    // Front face
-    0,  1,  2,  2,  3,  0,
-
-    // Back face
-    4,  5,  6,  6,  7,  4,
-
-    // Left face
-    8,  9,  10, 10, 11, 8,
-
-    // Right face
-    12, 13, 14, 14, 15, 12,
-
-    // Top face
-    16, 17, 18, 18, 19, 16,
-
-    // Bottom face
-    20, 21, 22, 22, 23, 20,
-];
-
-
-//Triangle
-// pub static VERTICES: [Vertex; 3] = [
-//     Vertex::new(Vector3::new(-0.5, -0.5, 0.0), Vector4::new(0.0, 0.0, 1.0, 1.0)),
-//     Vertex::new(Vector3::new(0.0, 0.5, 0.0), Vector4::new(0.0, 1.0, 0.0, 1.0)),
-//     Vertex::new(Vector3::new(0.5, -0.5, 0.0), Vector4::new(1.0, 0.0, 0.0, 1.0)),
-// ];
-//
-// pub static INDICES: &[u16] = &[0, 1, 2];
-
 pub const PERSPECTIVE_CORRECTION: Matrix4x4 = Matrix4x4::new(
     1.0,  0.0,       0.0, 0.0,
     0.0, -1.0,       0.0, 0.0,
@@ -127,7 +36,7 @@ pub const PERSPECTIVE_CORRECTION: Matrix4x4 = Matrix4x4::new(
     0.0,  0.0, 1.0 / 2.0, 1.0,
 );
 
-pub extern "system" fn debug_callback(severity: vk::DebugUtilsMessageSeverityFlagsEXT, message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+pub(crate) extern "system" fn debug_callback(severity: vk::DebugUtilsMessageSeverityFlagsEXT, message_type: vk::DebugUtilsMessageTypeFlagsEXT,
                                   data: *const vk::DebugUtilsMessengerCallbackDataEXT, _: *mut c_void)
 -> vk::Bool32
 {
@@ -156,11 +65,15 @@ pub struct QueueFamilyIndices {
 impl QueueFamilyIndices {
     const QUEUE_FLAGS: QueueFlags = QueueFlags::GRAPHICS;
 
+    //Headless (null surface) only requires a graphics queue
     pub fn get(instance: &Instance, physical_device: PhysicalDevice, surface: SurfaceKHR) -> anyhow::Result<QueueFamilyIndices> {
         let properties = unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
 
         let maybe_index = properties.iter().enumerate()
-            .position(|(i, p)| p.queue_flags.contains(Self::QUEUE_FLAGS) && unsafe { instance.get_physical_device_surface_support_khr(physical_device, i as u32, surface) }.unwrap())
+            .position(|(i, p)| {
+                p.queue_flags.contains(Self::QUEUE_FLAGS)
+                    && (surface.is_null() || unsafe { instance.get_physical_device_surface_support_khr(physical_device, i as u32, surface) }.unwrap())
+            })
             .map(|i| i as u32);
 
         if let Some(i) = maybe_index {
@@ -176,6 +89,178 @@ pub trait RHIDestroy {
     fn destroy(&mut self, rhi_data: &VulkanRHIData);
 }
 
+pub fn get_memory_type_index(rhi_data: &VulkanRHIData, properties: MemoryPropertyFlags, requirements: MemoryRequirements) -> anyhow::Result<u32> {
+    let instance = &rhi_data.instance;
+    let physical_device = rhi_data.physical_device;
+
+    let memory = unsafe { instance.get_physical_device_memory_properties(physical_device) };
+
+    (0..memory.memory_type_count)
+        .find(|i| {
+            let suitable = (requirements.memory_type_bits & (1u32 << i)) != 0;
+            let memory_type = memory.memory_types[*i as usize];
+            suitable && memory_type.property_flags.contains(properties)
+        })
+        .ok_or_else(|| anyhow!("Failed to find suitable memory type"))
+}
+
+pub fn create_buffer(rhi_data: &VulkanRHIData, size: DeviceSize, usage: BufferUsageFlags, properties: MemoryPropertyFlags) -> anyhow::Result<(Buffer, DeviceMemory)> {
+    let logical_device = &rhi_data.logical_device;
+
+    let buffer_info = BufferCreateInfo::builder()
+        .size(size)
+        .usage(usage)
+        .sharing_mode(SharingMode::EXCLUSIVE)
+    ;
+
+    let buffer = unsafe { logical_device.create_buffer(&buffer_info, None) }?;
+    let requirements = unsafe { logical_device.get_buffer_memory_requirements(buffer) };
+
+    let memory_info = MemoryAllocateInfo::builder()
+        .allocation_size(requirements.size)
+        .memory_type_index(get_memory_type_index(rhi_data, properties, requirements)?);
+
+    let buffer_memory = unsafe { logical_device.allocate_memory(&memory_info, None) }?;
+
+    unsafe { logical_device.bind_buffer_memory(buffer, buffer_memory, 0) }?;
+
+    Ok((buffer, buffer_memory))
+}
+
+//Copies data into a new device-local buffer through a staging buffer and waits for the transfer to finish
+pub fn create_device_local_buffer<T: Copy>(rhi_data: &VulkanRHIData, command_pool: CommandPool, data: &[T], usage: BufferUsageFlags) -> anyhow::Result<(Buffer, DeviceMemory)> {
+    let logical_device = &rhi_data.logical_device;
+
+    let size = size_of_val(data) as u64;
+    let (staging_buffer, staging_buffer_memory) = create_buffer(rhi_data, size, BufferUsageFlags::TRANSFER_SRC, MemoryPropertyFlags::HOST_COHERENT | MemoryPropertyFlags::HOST_VISIBLE)?;
+
+    unsafe {
+        let memory = logical_device.map_memory(staging_buffer_memory, 0, size, MemoryMapFlags::empty())?;
+        copy_nonoverlapping(data.as_ptr(), memory.cast(), data.len());
+        logical_device.unmap_memory(staging_buffer_memory);
+    }
+
+    let (buffer, buffer_memory) = create_buffer(rhi_data, size, BufferUsageFlags::TRANSFER_DST | usage, MemoryPropertyFlags::DEVICE_LOCAL)?;
+
+    let regions = BufferCopy::builder().size(size);
+    let result = submit_one_time(rhi_data, command_pool, |command_buffer| unsafe {
+        logical_device.cmd_copy_buffer(command_buffer, staging_buffer, buffer, &[regions]);
+    });
+
+    unsafe {
+        logical_device.destroy_buffer(staging_buffer, None);
+        logical_device.free_memory(staging_buffer_memory, None);
+    }
+
+    result.map(|_| (buffer, buffer_memory))
+}
+
+//Records the commands into a new command buffer, submits it to the graphics queue and waits for it to finish
+pub fn submit_one_time(rhi_data: &VulkanRHIData, command_pool: CommandPool, record: impl FnOnce(CommandBuffer)) -> anyhow::Result<()> {
+    let logical_device = &rhi_data.logical_device;
+
+    let info = CommandBufferAllocateInfo::builder()
+        .level(CommandBufferLevel::PRIMARY)
+        .command_pool(command_pool)
+        .command_buffer_count(1)
+    ;
+
+    let command_buffer = unsafe { logical_device.allocate_command_buffers(&info) }?[0];
+    let begin_info = CommandBufferBeginInfo::builder()
+        .flags(CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+    let command_buffers = &[command_buffer];
+    let submit_info = SubmitInfo::builder()
+        .command_buffers(command_buffers)
+    ;
+
+    let result = unsafe {
+        logical_device.begin_command_buffer(command_buffer, &begin_info)
+            .map(|_| record(command_buffer))
+            .and_then(|_| logical_device.end_command_buffer(command_buffer))
+            .and_then(|_| logical_device.queue_submit(rhi_data.graphics_queue, &[submit_info], Fence::null()))
+            .and_then(|_| logical_device.queue_wait_idle(rhi_data.graphics_queue))
+    };
+
+    unsafe { logical_device.free_command_buffers(command_pool, &[command_buffer]) };
+
+    Ok(result?)
+}
+
+pub fn create_image(
+    rhi_data: &VulkanRHIData,
+    width: u32,
+    height: u32,
+    format: Format,
+    tiling: ImageTiling,
+    usage: ImageUsageFlags,
+    properties: MemoryPropertyFlags,
+) -> anyhow::Result<(Image, DeviceMemory)> {
+    // Image
+
+    let info = vk::ImageCreateInfo::builder()
+        .image_type(vk::ImageType::_2D)
+        .extent(vk::Extent3D {
+            width,
+            height,
+            depth: 1,
+        })
+        .mip_levels(1)
+        .array_layers(1)
+        .format(format)
+        .tiling(tiling)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .usage(usage)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .samples(vk::SampleCountFlags::_1);
+
+    let logical_device = &rhi_data.logical_device;
+    let image = unsafe { logical_device.create_image(&info, None) }?;
+
+    // Memory
+
+    let requirements = unsafe { logical_device.get_image_memory_requirements(image) };
+
+    let info = vk::MemoryAllocateInfo::builder()
+        .allocation_size(requirements.size)
+        .memory_type_index(get_memory_type_index(rhi_data, properties, requirements)?);
+
+    let image_memory = unsafe { logical_device.allocate_memory(&info, None) }?;
+
+    unsafe { logical_device.bind_image_memory(image, image_memory, 0) }?;
+
+    Ok((image, image_memory))
+}
+
+pub fn create_image_view(rhi_data: &VulkanRHIData, image: Image, format: Format, aspects: ImageAspectFlags) -> anyhow::Result<ImageView> {
+    let subresource_range = ImageSubresourceRange::builder()
+        .aspect_mask(aspects)
+        .base_mip_level(0)
+        .level_count(1)
+        .base_array_layer(0)
+        .layer_count(1);
+
+    let info = ImageViewCreateInfo::builder()
+        .image(image)
+        .view_type(vk::ImageViewType::_2D)
+        .format(format)
+        .subresource_range(subresource_range);
+
+    let image_view = unsafe { rhi_data.logical_device.create_image_view(&info, None) }?;
+    Ok(image_view)
+}
+
+pub fn create_shader_module(rhi_data: &VulkanRHIData, bytecode: &[u8]) -> anyhow::Result<ShaderModule> {
+    let bytecode = Bytecode::new(bytecode)?;
+
+    let shader_info = ShaderModuleCreateInfo::builder()
+        .code_size(bytecode.code_size())
+        .code(bytecode.code())
+        ;
+
+    Ok(unsafe { rhi_data.logical_device.create_shader_module(&shader_info, None) }?)
+}
+
 pub fn perspective_matrix(fovy: f32, view_width: f32, view_height: f32, near: f32, far: f32,) -> Matrix4x4 {
     let aspect = view_width / view_height;
 
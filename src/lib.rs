@@ -0,0 +1,8 @@
+pub mod app;
+pub mod graphics;
+pub mod assets;
+pub mod config;
+pub mod world;
+pub mod utils;
+pub mod camera;
+pub mod controls;
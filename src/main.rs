@@ -1,23 +1,34 @@
-use log::debug;
+use std::fmt::Display;
 
-use crate::app::App;
-use crate::config::config::load_config;
+use log::{debug, error};
 
-mod app;
-mod graphics;
-mod config;
-mod world;
-mod utils;
-mod camera;
-mod controls;
+use engine::app::App;
+use engine::config::atmosphere_settings::{load_atmosphere_settings, DEFAULT_ATMOSPHERE_PRESET};
+use engine::config::config::load_config;
+use engine::world::scene::SceneFile;
 
 #[tokio::main()]
 async fn main() {
     pretty_env_logger::init();
 
-    let config = load_config().await.unwrap();
+    let config = load_config().await.unwrap_or_else(|e| exit_with_error(e));
     debug!("{:?}", config);
 
-    let mut app = App::new(config);
-    app.run().unwrap();
+    let preset = config.atmosphere_preset.as_deref().unwrap_or(DEFAULT_ATMOSPHERE_PRESET);
+    let atmosphere = load_atmosphere_settings(preset).await.unwrap_or_else(|e| exit_with_error(e));
+    debug!("Atmosphere '{}': {:?}", preset, atmosphere);
+
+    let scene = config.scene.as_deref().map(SceneFile::load).transpose().unwrap_or_else(|e| exit_with_error(e));
+
+    let mut app = App::new(config, atmosphere, scene).unwrap_or_else(|e| exit_with_error(e));
+    if app.is_headless() {
+        app.run_headless().unwrap();
+    } else {
+        app.run().unwrap();
+    }
+}
+
+fn exit_with_error(error: impl Display) -> ! {
+    error!("{}", error);
+    std::process::exit(1);
 }
@@ -1 +1 @@
-pub(crate) mod math;
\ No newline at end of file
+pub mod math;
\ No newline at end of file
@@ -1,4 +1,14 @@
 pub mod transform;
+#[allow(clippy::module_inception)]
 pub mod world;
 pub mod entity;
-pub mod game_object;
\ No newline at end of file
+pub mod ecs;
+pub mod components;
+pub mod hierarchy;
+pub mod commands;
+pub mod clock;
+pub mod timestep;
+pub mod scene;
+pub mod game_object;
+pub mod directional_light;
+pub mod time_of_day;
\ No newline at end of file
@@ -0,0 +1,42 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+//Time source of the World, only differences between readings are used
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Duration;
+}
+
+//Wall clock time since creation
+pub struct RealClock {
+    start: Instant,
+}
+
+impl Default for RealClock {
+    fn default() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+impl Clock for RealClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+//Only moves when advanced, for deterministic runs such as tests and headless captures. Clones share the time
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    pub fn advance(&self, delta: Duration) {
+        *self.now.lock().unwrap() += delta;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
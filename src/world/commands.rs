@@ -0,0 +1,46 @@
+use std::sync::{Arc, Mutex};
+
+use crate::world::entity::Entity;
+use crate::world::game_object::GameObject;
+use crate::world::world::{Behaviour, World};
+
+pub(crate) enum Command {
+    Spawn(Behaviour),
+    Despawn(Entity),
+    Apply(Box<dyn FnOnce(&mut World) + Send>),
+}
+
+//Queue for changes to the World from places without access to it, e.g. GameObject::update.
+//Clones share the queue, the World applies it after the behaviours are updated
+#[derive(Clone, Default)]
+pub struct Commands {
+    queue: Arc<Mutex<Vec<Command>>>,
+}
+
+impl Commands {
+    //Spawns an entity with the behaviour, see World::spawn_behaviour
+    pub fn spawn(&self, behaviour: impl GameObject + Send + Sync + 'static) {
+        self.push(Command::Spawn(Box::new(behaviour)));
+    }
+
+    //Despawns the entity and its descendants, see World::despawn
+    pub fn despawn(&self, entity: Entity) {
+        self.push(Command::Despawn(entity));
+    }
+
+    pub fn apply(&self, command: impl FnOnce(&mut World) + Send + 'static) {
+        self.push(Command::Apply(Box::new(command)));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.lock().unwrap().is_empty()
+    }
+
+    pub(crate) fn take(&self) -> Vec<Command> {
+        std::mem::take(&mut *self.queue.lock().unwrap())
+    }
+
+    fn push(&self, command: Command) {
+        self.queue.lock().unwrap().push(command);
+    }
+}
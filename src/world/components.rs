@@ -0,0 +1,63 @@
+use crate::graphics::rhi::MeshHandle;
+
+//Components without behaviour of their own. Transform, DirectionalLight and OrbitCamera are components too
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Name(pub String);
+
+//Drawn by the renderer at the entity's Transform
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshRef(pub MeshHandle);
+
+//Where the entity's mesh comes from, kept so scenes can be saved. The renderer loads the file and adds the
+//MeshRef for entities that don't have one yet
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MeshAsset(pub String);
+
+//Left out of saved scene files along with its descendants, e.g. the entities of the configured model which are
+//imported again on every start
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NotSaved;
+
+//Parsed MeshAsset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshSource<'a> {
+    //MeshHandle::DEFAULT, the configured model or the built-in cube
+    Default,
+    //OBJ file, or a whole glTF scene flattened into one mesh
+    File(&'a str),
+    //One mesh of a glTF scene, by index
+    GltfMesh(&'a str, usize),
+}
+
+impl MeshAsset {
+    pub const DEFAULT: &'static str = "default";
+
+    pub fn gltf_mesh(path: &str, mesh: usize) -> Self {
+        Self(format!("{}#{}", path, mesh))
+    }
+
+    pub fn source(&self) -> MeshSource<'_> {
+        if self.0 == Self::DEFAULT {
+            return MeshSource::Default;
+        }
+
+        match self.0.rsplit_once('#').map(|(path, mesh)| (path, mesh.parse::<usize>())) {
+            Some((path, Ok(mesh))) => MeshSource::GltfMesh(path, mesh),
+            _ => MeshSource::File(&self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mesh_assets_parse() {
+        assert_eq!(MeshAsset(MeshAsset::DEFAULT.into()).source(), MeshSource::Default);
+        assert_eq!(MeshAsset("./models/teapot.obj".into()).source(), MeshSource::File("./models/teapot.obj"));
+        assert_eq!(MeshAsset::gltf_mesh("./models/car.glb", 3).source(), MeshSource::GltfMesh("./models/car.glb", 3));
+        assert_eq!(MeshAsset("./models/#1 draft.obj".into()).source(), MeshSource::File("./models/#1 draft.obj"));
+    }
+}
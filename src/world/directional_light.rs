@@ -0,0 +1,109 @@
+use cgmath::{InnerSpace, Rad, Rotation, Rotation3};
+
+use crate::utils::math::{Deg, Quaternion, Vector3, VECTOR3_FORWARD, VECTOR3_ONE};
+use crate::world::transform::{OwnedTransform, Transform};
+
+//Light at infinity, e.g. the sun. Only the rotation of the transform is used, forward points towards the light
+#[derive(Debug)]
+pub struct DirectionalLight {
+    transform: Transform,
+    //Illuminance at the top of the atmosphere
+    illuminance: f32,
+    color: Vector3
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        //Same direction as the former hard-coded Euler { x: -65, y: 25 } light
+        Self::new(55.2, 47.8, 100.0)
+    }
+}
+
+impl OwnedTransform for DirectionalLight {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn transform_mut(&mut self) -> &mut Transform {
+        &mut self.transform
+    }
+}
+
+impl DirectionalLight {
+    //Degrees, elevation above the horizon and azimuth around the up axis, measured from forward towards right.
+    //With forward as north and right as east this is the compass azimuth used by world::time_of_day
+    pub fn new(elevation: f32, azimuth: f32, illuminance: f32) -> Self {
+        let mut light = Self {
+            transform: Transform::identity(),
+            illuminance,
+            color: VECTOR3_ONE
+        };
+
+        light.set_angles(elevation, azimuth);
+        light
+    }
+
+    pub fn direction(&self) -> Vector3 {
+        self.transform.rotation().rotate_vector(VECTOR3_FORWARD).normalize()
+    }
+
+    pub fn set_angles(&mut self, elevation: f32, azimuth: f32) {
+        let yaw = Quaternion::from_angle_y(Deg(azimuth));
+        let pitch = Quaternion::from_angle_x(Deg(-elevation));
+
+        self.transform.set_rotation(yaw * pitch);
+    }
+
+    pub fn elevation(&self) -> f32 {
+        Deg::from(Rad(self.direction().y.clamp(-1.0, 1.0).asin())).0
+    }
+
+    pub fn azimuth(&self) -> f32 {
+        let direction = self.direction();
+        Deg::from(Rad(direction.x.atan2(direction.z))).0.rem_euclid(360.0)
+    }
+
+    pub fn illuminance(&self) -> f32 {
+        self.illuminance
+    }
+
+    pub fn set_illuminance(&mut self, illuminance: f32) {
+        self.illuminance = illuminance;
+    }
+
+    pub fn color(&self) -> Vector3 {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: Vector3) {
+        self.color = color;
+    }
+
+    //Color scaled by illuminance, as consumed by the atmosphere shaders
+    pub fn radiance(&self) -> Vector3 {
+        self.color * self.illuminance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::math::Euler;
+
+    #[test]
+    fn angles_round_trip() {
+        for (elevation, azimuth) in [(65.0, 25.0), (-10.0, 300.0), (0.0, 180.0), (89.0, 90.0)] {
+            let light = DirectionalLight::new(elevation, azimuth, 1.0);
+
+            assert!((light.elevation() - elevation).abs() < 1e-3, "elevation {} != {}", light.elevation(), elevation);
+            assert!((light.azimuth() - azimuth).abs() < 1e-3, "azimuth {} != {}", light.azimuth(), azimuth);
+        }
+    }
+
+    #[test]
+    fn default_matches_former_light() {
+        let former = Quaternion::from(Euler { x: Deg(-65.0), y: Deg(25.0), z: Deg(0.0) }).rotate_vector(VECTOR3_FORWARD);
+
+        assert!((DirectionalLight::default().direction() - former).magnitude() < 1e-3);
+    }
+}
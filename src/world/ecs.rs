@@ -0,0 +1,412 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+use crate::world::entity::Entity;
+use crate::world::hierarchy;
+
+//Anything 'static that can be shared with the render thread can be attached to an entity, including
+//game specific user data
+pub trait Component: Any + Send + Sync {}
+
+impl<T: Any + Send + Sync> Component for T {}
+
+//Entities with this marker are never despawned, e.g. the World's main camera and sun that the renderer relies on.
+//Despawning one of their ancestors detaches them instead
+#[derive(Debug, Clone, Copy)]
+pub struct Persistent;
+
+//Components of one type, packed densely and indexed through the entity index
+struct SparseSet<T> {
+    sparse: Vec<Option<usize>>,
+    dense: Vec<T>,
+    entities: Vec<Entity>,
+    //Set when a component is inserted or borrowed mutably, parallel to dense
+    changed: Vec<bool>,
+}
+
+impl<T> Default for SparseSet<T> {
+    fn default() -> Self {
+        Self { sparse: Vec::new(), dense: Vec::new(), entities: Vec::new(), changed: Vec::new() }
+    }
+}
+
+impl<T> SparseSet<T> {
+    fn dense_index(&self, entity: Entity) -> Option<usize> {
+        self.sparse
+            .get(entity.index() as usize)
+            .copied()
+            .flatten()
+            .filter(|&i| self.entities[i] == entity)
+    }
+
+    fn get(&self, entity: Entity) -> Option<&T> {
+        self.dense_index(entity).map(|i| &self.dense[i])
+    }
+
+    fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        let i = self.dense_index(entity)?;
+        self.changed[i] = true;
+        Some(&mut self.dense[i])
+    }
+
+    //Every component is marked as changed, whether the caller modifies it or not
+    fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.changed.fill(true);
+        self.entities.iter().copied().zip(self.dense.iter_mut())
+    }
+
+    //Returns the replaced component
+    fn insert(&mut self, entity: Entity, component: T) -> Option<T> {
+        if let Some(i) = self.dense_index(entity) {
+            self.changed[i] = true;
+            return Some(std::mem::replace(&mut self.dense[i], component));
+        }
+
+        let index = entity.index() as usize;
+        if self.sparse.len() <= index {
+            self.sparse.resize(index + 1, None);
+        }
+
+        self.sparse[index] = Some(self.dense.len());
+        self.dense.push(component);
+        self.entities.push(entity);
+        self.changed.push(true);
+        None
+    }
+
+    //Swaps the last component into the hole
+    fn remove(&mut self, entity: Entity) -> Option<T> {
+        let i = self.dense_index(entity)?;
+
+        self.sparse[entity.index() as usize] = None;
+        self.entities.swap_remove(i);
+        self.changed.swap_remove(i);
+        let component = self.dense.swap_remove(i);
+
+        if let Some(&moved) = self.entities.get(i) {
+            self.sparse[moved.index() as usize] = Some(i);
+        }
+
+        Some(component)
+    }
+}
+
+//Type erased SparseSet, lets despawn remove components of every type
+trait Storage: Any + Send + Sync {
+    fn remove_entity(&mut self, entity: Entity);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Component> Storage for SparseSet<T> {
+    fn remove_entity(&mut self, entity: Entity) {
+        self.remove(entity);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+//Sparse-set entity component storage. Entity ids are recycled with a new generation, so stale ids stop
+//resolving once their entity is despawned
+#[derive(Default)]
+pub struct Ecs {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+    storages: HashMap<TypeId, Box<dyn Storage>>,
+}
+
+impl Ecs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self) -> Entity {
+        match self.free.pop() {
+            Some(index) => {
+                self.alive[index as usize] = true;
+                Entity::new(index, self.generations[index as usize])
+            }
+            None => {
+                self.generations.push(0);
+                self.alive.push(true);
+                Entity::new(self.generations.len() as u32 - 1, 0)
+            }
+        }
+    }
+
+    //Despawns the entity and its descendants, removing it from its parent's Children. Returns false when it
+    //was already despawned or is Persistent
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) || self.has::<Persistent>(entity) {
+            return false;
+        }
+
+        hierarchy::detach(self, entity);
+
+        let mut stack = vec![entity];
+        while let Some(entity) = stack.pop() {
+            if self.has::<Persistent>(entity) {
+                hierarchy::detach(self, entity);
+                continue;
+            }
+
+            stack.extend(hierarchy::children(self, entity));
+            self.despawn_single(entity);
+        }
+
+        true
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        let index = entity.index() as usize;
+        self.alive.get(index).copied().unwrap_or(false) && self.generations[index] == entity.generation()
+    }
+
+    //Returns the component it replaced. Panics for despawned entities
+    pub fn insert<T: Component>(&mut self, entity: Entity, component: T) -> Option<T> {
+        assert!(self.is_alive(entity), "{:?} is not alive", entity);
+        self.storage_mut::<T>().insert(entity, component)
+    }
+
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        self.storages
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|storage| storage.as_any_mut().downcast_mut::<SparseSet<T>>())
+            .and_then(|set| set.remove(entity))
+    }
+
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.storage::<T>().and_then(|set| set.get(entity))
+    }
+
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.storages
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|storage| storage.as_any_mut().downcast_mut::<SparseSet<T>>())
+            .and_then(|set| set.get_mut(entity))
+    }
+
+    pub fn has<T: Component>(&self, entity: Entity) -> bool {
+        self.get::<T>(entity).is_some()
+    }
+
+    //Entities with a T, in storage order
+    pub fn query<T: Component>(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.storage::<T>()
+            .into_iter()
+            .flat_map(|set| set.entities.iter().copied().zip(set.dense.iter()))
+    }
+
+    //Marks every T as changed
+    pub fn query_mut<T: Component>(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.storages
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|storage| storage.as_any_mut().downcast_mut::<SparseSet<T>>())
+            .into_iter()
+            .flat_map(|set| set.iter_mut())
+    }
+
+    //Entities whose T was inserted or borrowed mutably since the last clear_changed::<T>
+    pub fn changed<T: Component>(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.storage::<T>()
+            .into_iter()
+            .flat_map(|set| set.entities.iter().zip(&set.changed).filter(|(_, &changed)| changed).map(|(&entity, _)| entity))
+    }
+
+    pub fn clear_changed<T: Component>(&mut self) {
+        if let Some(set) = self.storages.get_mut(&TypeId::of::<T>()).and_then(|storage| storage.as_any_mut().downcast_mut::<SparseSet<T>>()) {
+            set.changed.fill(false);
+        }
+    }
+
+    //Entities with both an A and a B, iterating the smaller of the two storages
+    pub fn query2<A: Component, B: Component>(&self) -> Vec<(Entity, &A, &B)> {
+        let (Some(a), Some(b)) = (self.storage::<A>(), self.storage::<B>()) else {
+            return Vec::new();
+        };
+
+        if a.dense.len() <= b.dense.len() {
+            a.entities.iter().zip(a.dense.iter())
+                .filter_map(|(&entity, a)| b.get(entity).map(|b| (entity, a, b)))
+                .collect()
+        } else {
+            b.entities.iter().zip(b.dense.iter())
+                .filter_map(|(&entity, b)| a.get(entity).map(|a| (entity, a, b)))
+                .collect()
+        }
+    }
+
+    fn despawn_single(&mut self, entity: Entity) {
+        self.storages.values_mut().for_each(|storage| storage.remove_entity(entity));
+
+        let index = entity.index() as usize;
+        self.alive[index] = false;
+        self.generations[index] += 1;
+        self.free.push(entity.index());
+    }
+
+    fn storage<T: Component>(&self) -> Option<&SparseSet<T>> {
+        self.storages
+            .get(&TypeId::of::<T>())
+            .and_then(|storage| storage.as_any().downcast_ref::<SparseSet<T>>())
+    }
+
+    fn storage_mut<T: Component>(&mut self) -> &mut SparseSet<T> {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(SparseSet::<T>::default()))
+            .as_any_mut()
+            .downcast_mut::<SparseSet<T>>()
+            .unwrap()
+    }
+}
+
+//Queries only the tests need so far
+#[cfg(test)]
+impl Ecs {
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.alive
+            .iter()
+            .enumerate()
+            .filter(|(_, &alive)| alive)
+            .map(|(index, _)| Entity::new(index as u32, self.generations[index]))
+    }
+
+    pub fn count<T: Component>(&self) -> usize {
+        self.storage::<T>().map_or(0, |storage| storage.dense.len())
+    }
+
+    //Runs `f` for every entity with both an A and a B, A can be modified and is marked as changed. A and B must differ
+    pub fn for_each2_mut<A: Component, B: Component>(&mut self, mut f: impl FnMut(Entity, &mut A, &B)) {
+        assert_ne!(TypeId::of::<A>(), TypeId::of::<B>(), "for_each2_mut needs two different component types");
+
+        //Taken out of the map so B can be borrowed at the same time
+        let Some(mut storage_a) = self.storages.remove(&TypeId::of::<A>()) else {
+            return;
+        };
+
+        if let Some(b) = self.storage::<B>() {
+            let a = storage_a.as_any_mut().downcast_mut::<SparseSet<A>>().unwrap();
+
+            for ((entity, a), changed) in a.entities.iter().copied().zip(a.dense.iter_mut()).zip(a.changed.iter_mut()) {
+                if let Some(b) = b.get(entity) {
+                    *changed = true;
+                    f(entity, a, b);
+                }
+            }
+        }
+
+        self.storages.insert(TypeId::of::<A>(), storage_a);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+
+    #[derive(Debug, PartialEq)]
+    struct Velocity(f32);
+
+    #[test]
+    fn despawned_ids_are_recycled_with_a_new_generation() {
+        let mut ecs = Ecs::new();
+        let a = ecs.spawn();
+        ecs.insert(a, Health(10));
+
+        assert!(ecs.despawn(a));
+        assert!(!ecs.despawn(a));
+
+        let b = ecs.spawn();
+        assert_eq!(a.index(), b.index());
+        assert!(!ecs.is_alive(a) && ecs.is_alive(b));
+        assert_eq!(ecs.get::<Health>(a), None);
+        assert_eq!(ecs.get::<Health>(b), None);
+        assert_eq!(ecs.entities().collect::<Vec<_>>(), vec![b]);
+    }
+
+    #[test]
+    fn persistent_entities_are_kept() {
+        let mut ecs = Ecs::new();
+        let [parent, camera] = [(); 2].map(|_| ecs.spawn());
+        ecs.insert(camera, Persistent);
+        hierarchy::attach(&mut ecs, camera, parent).unwrap();
+
+        assert!(!ecs.despawn(camera));
+        assert!(ecs.despawn(parent));
+        assert!(ecs.is_alive(camera));
+        assert_eq!(hierarchy::parent(&ecs, camera), None);
+    }
+
+    #[test]
+    fn removing_keeps_other_components_reachable() {
+        let mut ecs = Ecs::new();
+        let entities = (0..4).map(|i| {
+            let entity = ecs.spawn();
+            ecs.insert(entity, Health(i));
+            entity
+        }).collect::<Vec<_>>();
+
+        assert_eq!(ecs.remove::<Health>(entities[1]), Some(Health(1)));
+        assert_eq!(ecs.insert(entities[2], Health(20)), Some(Health(2)));
+
+        assert_eq!(ecs.count::<Health>(), 3);
+        assert_eq!(ecs.get::<Health>(entities[3]), Some(&Health(3)));
+        assert_eq!(ecs.get::<Health>(entities[2]), Some(&Health(20)));
+        assert!(!ecs.has::<Health>(entities[1]));
+    }
+
+    #[test]
+    fn queries_join_component_types() {
+        let mut ecs = Ecs::new();
+        let moving = ecs.spawn();
+        ecs.insert(moving, Health(1));
+        ecs.insert(moving, Velocity(2.0));
+        let still = ecs.spawn();
+        ecs.insert(still, Health(3));
+
+        assert_eq!(ecs.query::<Health>().count(), 2);
+        assert_eq!(ecs.query2::<Health, Velocity>(), vec![(moving, &Health(1), &Velocity(2.0))]);
+
+        ecs.query_mut::<Health>().for_each(|(_, health)| health.0 += 1);
+        ecs.for_each2_mut::<Health, Velocity>(|_, health, velocity| health.0 += velocity.0 as u32);
+
+        assert_eq!(ecs.get::<Health>(moving), Some(&Health(4)));
+        assert_eq!(ecs.get::<Health>(still), Some(&Health(4)));
+    }
+
+    #[test]
+    fn mutable_access_marks_changes() {
+        let mut ecs = Ecs::new();
+        let [a, b, c] = [(); 3].map(|_| ecs.spawn());
+        for entity in [a, b, c] {
+            ecs.insert(entity, Health(1));
+        }
+        ecs.insert(c, Velocity(1.0));
+        assert_eq!(ecs.changed::<Health>().count(), 3);
+
+        ecs.clear_changed::<Health>();
+        ecs.get::<Health>(a);
+        assert_eq!(ecs.changed::<Health>().count(), 0);
+
+        ecs.get_mut::<Health>(a);
+        ecs.for_each2_mut::<Health, Velocity>(|_, _, _| ());
+        assert_eq!(ecs.changed::<Health>().collect::<Vec<_>>(), vec![a, c]);
+
+        //Changes follow components moved by removals
+        ecs.remove::<Health>(a);
+        assert_eq!(ecs.changed::<Health>().collect::<Vec<_>>(), vec![c]);
+
+        ecs.query_mut::<Health>().count();
+        assert_eq!(ecs.changed::<Health>().count(), 2);
+    }
+}
@@ -1,11 +1,23 @@
 #![allow(dead_code)]
-#![allow(unused_variables)]
 
-use crate::world::transform::Transform;
-
-//ToDo: Convert to ECS
+//Id of an entity in world::ecs::Ecs, its data lives in components
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Entity {
-    pub id: u32,
-    pub name: String,
-    pub transform: Transform
-}
\ No newline at end of file
+    index: u32,
+    //Bumped when the index is reused, so ids of despawned entities stay invalid
+    generation: u32
+}
+
+impl Entity {
+    pub(crate) fn new(index: u32, generation: u32) -> Self {
+        Self { index, generation }
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
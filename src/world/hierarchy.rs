@@ -0,0 +1,340 @@
+use std::collections::HashSet;
+
+use cgmath::{InnerSpace, SquareMatrix};
+use thiserror::Error;
+
+use crate::utils::math::Matrix4x4;
+use crate::world::ecs::Ecs;
+use crate::world::entity::Entity;
+use crate::world::transform::Transform;
+
+//Parent/child relations between entities. The Transform component is relative to the parent, the renderer
+//draws with the GlobalTransform computed from the chain of parents
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Parent(pub Entity);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Children(pub Vec<Entity>);
+
+//World matrix, cached until the Transform of the entity or one of its ancestors changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlobalTransform(pub Matrix4x4);
+
+impl Default for GlobalTransform {
+    fn default() -> Self {
+        Self(Matrix4x4::identity())
+    }
+}
+
+//GlobalTransform before the last simulation tick, the renderer blends between the two
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PreviousGlobalTransform(pub Matrix4x4);
+
+//Marks entities whose GlobalTransform and those of their descendants are outdated for reasons other than a change
+//to their Transform, which the Ecs tracks itself, e.g. a new parent
+#[derive(Debug, Clone, Copy)]
+pub struct TransformDirty;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum HierarchyError {
+    #[error("{0:?} is not alive.")]
+    NotAlive(Entity),
+    #[error("{child:?} can not be attached to its own descendant {parent:?}.")]
+    Cycle { child: Entity, parent: Entity },
+    #[error("{0:?} has a zero scale, its world pose can not be expressed relative to a parent.")]
+    DegenerateScale(Entity),
+}
+
+pub fn mark_dirty(ecs: &mut Ecs, entity: Entity) {
+    if ecs.is_alive(entity) {
+        ecs.insert(entity, TransformDirty);
+    }
+}
+
+pub fn parent(ecs: &Ecs, entity: Entity) -> Option<Entity> {
+    ecs.get::<Parent>(entity).map(|parent| parent.0)
+}
+
+pub fn children(ecs: &Ecs, entity: Entity) -> &[Entity] {
+    ecs.get::<Children>(entity).map_or(&[], |children| children.0.as_slice())
+}
+
+pub fn is_ancestor(ecs: &Ecs, ancestor: Entity, entity: Entity) -> bool {
+    let mut current = parent(ecs, entity);
+
+    while let Some(entity) = current {
+        if entity == ancestor {
+            return true;
+        }
+        current = parent(ecs, entity);
+    }
+
+    false
+}
+
+//Up to date only after propagate_transforms, falls back to the local matrix for entities without a GlobalTransform
+pub fn global_matrix(ecs: &Ecs, entity: Entity) -> Matrix4x4 {
+    match ecs.get::<GlobalTransform>(entity) {
+        Some(global) => global.0,
+        None => ecs.get::<Transform>(entity).map_or(Matrix4x4::identity(), Transform::matrix),
+    }
+}
+
+//World matrix between the one before the last tick at alpha 0 and the current one at alpha 1
+pub fn interpolated_matrix(ecs: &Ecs, entity: Entity, alpha: f32) -> Matrix4x4 {
+    let current = global_matrix(ecs, entity);
+
+    match ecs.get::<PreviousGlobalTransform>(entity) {
+        Some(previous) if previous.0 != current => {
+            Transform::from_matrix(previous.0).interpolate(&Transform::from_matrix(current), alpha).matrix()
+        }
+        _ => current,
+    }
+}
+
+//Remembers the current GlobalTransforms before a tick changes them
+pub fn store_previous_transforms(ecs: &mut Ecs) {
+    let globals = ecs.query::<GlobalTransform>().map(|(entity, global)| (entity, global.0)).collect::<Vec<_>>();
+
+    for (entity, global) in globals {
+        ecs.insert(entity, PreviousGlobalTransform(global));
+    }
+}
+
+//Attaches `child` to `parent`, or makes it a root for None. The local Transform is recomputed so the entity
+//keeps its place in the world
+pub fn set_parent(ecs: &mut Ecs, child: Entity, parent: Option<Entity>) -> Result<(), HierarchyError> {
+    for entity in std::iter::once(child).chain(parent) {
+        if !ecs.is_alive(entity) {
+            return Err(HierarchyError::NotAlive(entity));
+        }
+    }
+
+    if let Some(parent) = parent {
+        if parent == child || is_ancestor(ecs, child, parent) {
+            return Err(HierarchyError::Cycle { child, parent });
+        }
+    }
+
+    //Pending changes up the chains have to be applied first
+    propagate_transforms(ecs);
+
+    let child_global = global_matrix(ecs, child);
+    if is_degenerate(&child_global) {
+        return Err(HierarchyError::DegenerateScale(child));
+    }
+
+    let parent_inverse = match parent {
+        Some(parent) => global_matrix(ecs, parent).invert().ok_or(HierarchyError::DegenerateScale(parent))?,
+        None => Matrix4x4::identity(),
+    };
+    let local = parent_inverse * child_global;
+
+    ecs.insert(child, Transform::from_matrix(local));
+
+    match parent {
+        Some(parent) => attach(ecs, child, parent),
+        None => {
+            detach(ecs, child);
+            mark_dirty(ecs, child);
+            Ok(())
+        }
+    }
+}
+
+//Attaches `child` to `parent` keeping its local Transform, so it moves along with the parent as when loading a scene
+pub fn attach(ecs: &mut Ecs, child: Entity, parent: Entity) -> Result<(), HierarchyError> {
+    for entity in [child, parent] {
+        if !ecs.is_alive(entity) {
+            return Err(HierarchyError::NotAlive(entity));
+        }
+    }
+
+    if parent == child || is_ancestor(ecs, child, parent) {
+        return Err(HierarchyError::Cycle { child, parent });
+    }
+
+    detach(ecs, child);
+
+    ecs.insert(child, Parent(parent));
+    match ecs.get_mut::<Children>(parent) {
+        Some(children) => children.0.push(child),
+        None => {
+            ecs.insert(parent, Children(vec![child]));
+        }
+    }
+
+    mark_dirty(ecs, child);
+    Ok(())
+}
+
+//Recomputes the GlobalTransform of every entity whose Transform changed or that is marked dirty, and of their
+//descendants, parents before children
+pub fn propagate_transforms(ecs: &mut Ecs) {
+    let dirty = ecs.query::<TransformDirty>()
+        .map(|(entity, _)| entity)
+        .chain(ecs.changed::<Transform>())
+        .collect::<HashSet<_>>();
+    ecs.clear_changed::<Transform>();
+
+    //Subtrees below another dirty entity are updated together with it
+    let mut roots = dirty
+        .iter()
+        .copied()
+        .filter(|&entity| !has_dirty_ancestor(ecs, &dirty, entity))
+        .collect::<Vec<_>>();
+    roots.sort();
+
+    for root in roots {
+        let parent_matrix = parent(ecs, root).map_or(Matrix4x4::identity(), |parent| global_matrix(ecs, parent));
+        let mut stack = vec![(root, parent_matrix)];
+
+        while let Some((entity, parent_matrix)) = stack.pop() {
+            let local = ecs.get::<Transform>(entity).map_or(Matrix4x4::identity(), Transform::matrix);
+            let global = parent_matrix * local;
+
+            ecs.insert(entity, GlobalTransform(global));
+            ecs.remove::<TransformDirty>(entity);
+
+            stack.extend(children(ecs, entity).iter().map(|&child| (child, global)));
+        }
+    }
+}
+
+//Transform::from_matrix divides by the length of every axis
+fn is_degenerate(matrix: &Matrix4x4) -> bool {
+    [matrix.x, matrix.y, matrix.z].iter().any(|axis| {
+        let length = axis.truncate().magnitude();
+        !length.is_normal()
+    })
+}
+
+fn has_dirty_ancestor(ecs: &Ecs, dirty: &HashSet<Entity>, entity: Entity) -> bool {
+    let mut current = parent(ecs, entity);
+
+    while let Some(entity) = current {
+        if dirty.contains(&entity) {
+            return true;
+        }
+        current = parent(ecs, entity);
+    }
+
+    false
+}
+
+//Removes the child from its parent's Children, the child keeps its local Transform
+pub(crate) fn detach(ecs: &mut Ecs, child: Entity) {
+    if let Some(Parent(parent)) = ecs.remove::<Parent>(child) {
+        if let Some(children) = ecs.get_mut::<Children>(parent) {
+            children.0.retain(|&c| c != child);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::math::{Vector3, Zero};
+
+    fn spawn(ecs: &mut Ecs, transform: Transform) -> Entity {
+        let entity = ecs.spawn();
+        ecs.insert(entity, transform);
+        entity
+    }
+
+    fn world_position(ecs: &Ecs, entity: Entity) -> Vector3 {
+        global_matrix(ecs, entity).w.truncate()
+    }
+
+    fn assert_close(a: Vector3, b: Vector3) {
+        assert!((a - b).magnitude() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn children_follow_their_parent() {
+        let mut ecs = Ecs::new();
+        let car = spawn(&mut ecs, Transform::new(Vector3::new(10.0, 0.0, 0.0), Vector3::new(0.0, 90.0, 0.0), Vector3::new(1.0, 1.0, 1.0)));
+        let wheel = spawn(&mut ecs, Transform::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 1.0)));
+
+        set_parent(&mut ecs, wheel, Some(car)).unwrap();
+        propagate_transforms(&mut ecs);
+
+        //Reparenting keeps the wheel where it was
+        assert_close(world_position(&ecs, wheel), Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(children(&ecs, car), &[wheel]);
+
+        //Moving the parent only changes the parent, the wheel is updated through it
+        ecs.get_mut::<Transform>(car).unwrap().set_location_xyz(20.0, 0.0, 0.0);
+        propagate_transforms(&mut ecs);
+
+        assert_close(world_position(&ecs, wheel), Vector3::new(11.0, 0.0, 0.0));
+        assert_eq!(ecs.count::<TransformDirty>(), 0);
+        assert_eq!(ecs.changed::<Transform>().count(), 0);
+
+        //Changes made through queries are picked up as well
+        ecs.query_mut::<Transform>().filter(|&(entity, _)| entity == car).for_each(|(_, transform)| transform.set_location_xyz(30.0, 0.0, 0.0));
+        propagate_transforms(&mut ecs);
+
+        assert_close(world_position(&ecs, wheel), Vector3::new(21.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn detaching_keeps_world_pose() {
+        let mut ecs = Ecs::new();
+        let base = spawn(&mut ecs, Transform::new(Vector3::new(0.0, 5.0, 0.0), Vector3::new(0.0, 45.0, 0.0), Vector3::new(2.0, 2.0, 2.0)));
+        let child = spawn(&mut ecs, Transform::identity());
+
+        set_parent(&mut ecs, child, Some(base)).unwrap();
+        ecs.get_mut::<Transform>(child).unwrap().set_location_xyz(1.0, 0.0, 0.0);
+        propagate_transforms(&mut ecs);
+        let before = global_matrix(&ecs, child);
+
+        set_parent(&mut ecs, child, None).unwrap();
+        propagate_transforms(&mut ecs);
+
+        assert_eq!(parent(&ecs, child), None);
+        assert!(children(&ecs, base).is_empty());
+        for (a, b) in [(before.x, global_matrix(&ecs, child).x), (before.w, global_matrix(&ecs, child).w)] {
+            assert!((a - b).magnitude() < 1e-4, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn cycles_are_rejected_and_subtrees_despawned() {
+        let mut ecs = Ecs::new();
+        let [a, b, c, d] = [(); 4].map(|_| spawn(&mut ecs, Transform::identity()));
+
+        set_parent(&mut ecs, b, Some(a)).unwrap();
+        set_parent(&mut ecs, c, Some(b)).unwrap();
+        set_parent(&mut ecs, d, Some(a)).unwrap();
+        assert_eq!(set_parent(&mut ecs, a, Some(c)), Err(HierarchyError::Cycle { child: a, parent: c }));
+
+        ecs.despawn(b);
+        assert!(ecs.is_alive(a) && !ecs.is_alive(b) && !ecs.is_alive(c));
+        assert_eq!(children(&ecs, a), &[d]);
+
+        //No Parent is left pointing at a despawned entity
+        ecs.despawn(a);
+        assert_eq!(ecs.count::<Parent>(), 0);
+        assert_eq!(ecs.count::<Children>(), 0);
+    }
+
+    #[test]
+    fn degenerate_scales_are_rejected() {
+        let mut ecs = Ecs::new();
+        let mut flat = Transform::identity();
+        flat.set_scale(1.0, 0.0, 1.0);
+        let [flat, child] = [flat, Transform::identity()].map(|transform| spawn(&mut ecs, transform));
+
+        assert_eq!(set_parent(&mut ecs, child, Some(flat)), Err(HierarchyError::DegenerateScale(flat)));
+        assert_eq!(set_parent(&mut ecs, flat, Some(child)), Err(HierarchyError::DegenerateScale(flat)));
+        assert_eq!(parent(&ecs, child), None);
+        assert_eq!(parent(&ecs, flat), None);
+
+        //Attaching keeps the local Transform and needs no inverse
+        attach(&mut ecs, child, flat).unwrap();
+        propagate_transforms(&mut ecs);
+        assert_eq!(global_matrix(&ecs, child).y.truncate(), Vector3::zero());
+    }
+}
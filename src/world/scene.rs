@@ -0,0 +1,463 @@
+use std::path::Path;
+
+use cgmath::Rad;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::camera::camera::ViewSettings;
+use crate::camera::orbit_camera::OrbitCamera;
+use crate::graphics::rhi::MeshHandle;
+use crate::utils::math::{Deg, EulerRad};
+use crate::world::components::{MeshAsset, MeshRef, MeshSource, Name, NotSaved};
+use crate::world::directional_light::DirectionalLight;
+use crate::world::entity::Entity;
+use crate::world::hierarchy;
+use crate::world::hierarchy::Parent;
+use crate::world::transform::{OwnedTransform, Transform};
+use crate::world::world::{World, MODEL_LOCATION};
+
+//Bumped on every change that old scene files can not be read with
+pub const SCENE_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum SceneError {
+    #[error("Failed to access scene '{path}': {source}.")]
+    Io { path: String, source: std::io::Error },
+    #[error("Invalid scene '{path}': {source}.")]
+    Parse { path: String, source: serde_json::Error },
+    #[error("Scene '{path}' has version {found}, this build reads version {supported}.")]
+    UnsupportedVersion { path: String, found: u32, supported: u32 },
+}
+
+//Location and scale in world units, rotation as Euler angles in degrees as in Transform::new
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransformDesc {
+    pub location: [f32; 3],
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+impl Default for TransformDesc {
+    fn default() -> Self {
+        Self { location: [0.0; 3], rotation: [0.0; 3], scale: [1.0; 3] }
+    }
+}
+
+impl From<&Transform> for TransformDesc {
+    fn from(transform: &Transform) -> Self {
+        let euler = EulerRad::from(transform.rotation());
+
+        Self {
+            location: transform.location().into(),
+            rotation: [euler.x, euler.y, euler.z].map(|angle: Rad<f32>| Deg::from(angle).0),
+            scale: transform.scale().into(),
+        }
+    }
+}
+
+impl From<&TransformDesc> for Transform {
+    fn from(desc: &TransformDesc) -> Self {
+        Transform::new(desc.location.into(), desc.rotation.into(), desc.scale.into())
+    }
+}
+
+//Missing fields keep the OrbitCamera defaults
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraDesc {
+    pub location: [f32; 3],
+    pub rotation: [f32; 3],
+    //Vertical, degrees
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    pub move_speed: f32,
+    pub rotate_speed: f32,
+}
+
+impl Default for CameraDesc {
+    fn default() -> Self {
+        let mut desc = Self::new(&OrbitCamera::default(), &Transform::identity());
+        desc.location = [0.0, 0.0, -105.0];
+        desc
+    }
+}
+
+impl CameraDesc {
+    //Scale is not stored
+    pub fn new(camera: &OrbitCamera, transform: &Transform) -> Self {
+        let transform = TransformDesc::from(transform);
+        let view = camera.view();
+
+        Self {
+            location: transform.location,
+            rotation: transform.rotation,
+            fov: view.fov,
+            near: view.near,
+            far: view.far,
+            move_speed: camera.move_speed(),
+            rotate_speed: camera.rotate_speed(),
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        let mut transform = Transform::identity();
+        transform.set_location(self.location.into());
+        transform.set_rotation_euler_deg(self.rotation[0], self.rotation[1], self.rotation[2]);
+        transform
+    }
+}
+
+impl From<&CameraDesc> for OrbitCamera {
+    fn from(desc: &CameraDesc) -> Self {
+        let mut camera = OrbitCamera::default();
+        camera.set_move_speed(desc.move_speed);
+        camera.set_rotate_speed(desc.rotate_speed);
+        camera.set_view(ViewSettings { near: desc.near, far: desc.far, fov: desc.fov });
+        camera
+    }
+}
+
+//Degrees, see DirectionalLight::new. The illuminance belongs to the atmosphere preset and is not stored
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SunDesc {
+    pub elevation: f32,
+    pub azimuth: f32,
+    pub color: [f32; 3],
+}
+
+impl Default for SunDesc {
+    fn default() -> Self {
+        Self::from(&DirectionalLight::default())
+    }
+}
+
+impl From<&DirectionalLight> for SunDesc {
+    fn from(light: &DirectionalLight) -> Self {
+        Self { elevation: light.elevation(), azimuth: light.azimuth(), color: light.color().into() }
+    }
+}
+
+//DirectionalLight on an entity other than the sun, degrees as in SunDesc
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LightDesc {
+    pub elevation: f32,
+    pub azimuth: f32,
+    pub color: [f32; 3],
+    pub illuminance: f32,
+}
+
+impl Default for LightDesc {
+    fn default() -> Self {
+        Self::from(&DirectionalLight::default())
+    }
+}
+
+impl From<&DirectionalLight> for LightDesc {
+    fn from(light: &DirectionalLight) -> Self {
+        Self { elevation: light.elevation(), azimuth: light.azimuth(), color: light.color().into(), illuminance: light.illuminance() }
+    }
+}
+
+impl From<&LightDesc> for DirectionalLight {
+    fn from(desc: &LightDesc) -> Self {
+        let mut light = DirectionalLight::new(desc.elevation, desc.azimuth, desc.illuminance);
+        light.set_color(desc.color.into());
+        light
+    }
+}
+
+//Transform is relative to the parent entity
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct EntityDesc {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub transform: TransformDesc,
+    //See MeshAsset: "default", a model file or "<glTF file>#<mesh index>"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mesh: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub light: Option<LightDesc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<EntityDesc>,
+}
+
+//Scene files are JSON, unknown fields are rejected so typos don't go unnoticed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SceneFile {
+    pub version: u32,
+    #[serde(default)]
+    pub camera: CameraDesc,
+    #[serde(default)]
+    pub sun: SunDesc,
+    #[serde(default)]
+    pub entities: Vec<EntityDesc>,
+}
+
+impl Default for SceneFile {
+    //The default mesh on top of the planet
+    fn default() -> Self {
+        Self {
+            version: SCENE_VERSION,
+            camera: CameraDesc::default(),
+            sun: SunDesc::default(),
+            entities: vec![EntityDesc {
+                name: "test".into(),
+                transform: TransformDesc { location: MODEL_LOCATION.into(), ..Default::default() },
+                mesh: Some(MeshAsset::DEFAULT.into()),
+                light: None,
+                children: Vec::new(),
+            }],
+        }
+    }
+}
+
+//Only read to check the version before the rest of the file
+#[derive(Deserialize)]
+struct SceneHeader {
+    version: u32,
+}
+
+impl SceneFile {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+
+        let json = std::fs::read_to_string(path).map_err(|source| SceneError::Io { path: name.clone(), source })?;
+        Self::from_json(&name, &json)
+    }
+
+    //`name` is only used in errors
+    pub fn from_json(name: &str, json: &str) -> Result<Self, SceneError> {
+        let parse_error = |source| SceneError::Parse { path: name.to_string(), source };
+
+        let header = serde_json::from_str::<SceneHeader>(json).map_err(parse_error)?;
+        if header.version != SCENE_VERSION {
+            return Err(SceneError::UnsupportedVersion { path: name.to_string(), found: header.version, supported: SCENE_VERSION });
+        }
+
+        serde_json::from_str(json).map_err(parse_error)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Scene serialization can not fail")
+    }
+
+    //Creates missing parent directories
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SceneError> {
+        let path = path.as_ref();
+        let io_error = |source| SceneError::Io { path: path.display().to_string(), source };
+
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(io_error)?;
+        }
+
+        std::fs::write(path, self.to_json()).map_err(io_error)
+    }
+}
+
+impl World {
+    //Entities with the default mesh are drawn right away, other meshes once the renderer has loaded them
+    pub fn from_scene(scene: &SceneFile) -> Self {
+        let mut light = DirectionalLight::default();
+        light.set_angles(scene.sun.elevation, scene.sun.azimuth);
+        light.set_color(scene.sun.color.into());
+
+        let mut world = World::empty(OrbitCamera::from(&scene.camera), scene.camera.transform(), light);
+
+        for desc in &scene.entities {
+            world.spawn_scene_entity(desc, None);
+        }
+
+        world
+    }
+
+    //Entities with a Transform or a DirectionalLight are saved along with their descendants, the camera and the sun
+    //separately. Entities attached to the camera or the sun and NotSaved ones are left out
+    pub fn to_scene(&self) -> SceneFile {
+        let ecs = self.ecs();
+        let mut roots = ecs.query::<Transform>()
+            .map(|(entity, _)| entity)
+            .chain(ecs.query::<DirectionalLight>().map(|(entity, _)| entity))
+            .filter(|&entity| entity != self.main_camera_entity() && entity != self.sun_entity())
+            .filter(|&entity| !ecs.has::<Parent>(entity) && !ecs.has::<NotSaved>(entity))
+            .collect::<Vec<_>>();
+        roots.sort();
+        roots.dedup();
+
+        SceneFile {
+            version: SCENE_VERSION,
+            camera: CameraDesc::new(self.main_camera(), self.active_camera().transform()),
+            sun: SunDesc::from(self.sun()),
+            entities: roots.into_iter().map(|entity| self.entity_desc(entity)).collect(),
+        }
+    }
+
+    fn spawn_scene_entity(&mut self, desc: &EntityDesc, parent: Option<Entity>) {
+        let entity = self.add_entity(desc.name.clone(), Transform::from(&desc.transform), None);
+
+        if let Some(mesh) = &desc.mesh {
+            let asset = MeshAsset(mesh.clone());
+            if asset.source() == MeshSource::Default {
+                self.ecs_mut().insert(entity, MeshRef(MeshHandle::DEFAULT));
+            }
+            self.ecs_mut().insert(entity, asset);
+        }
+
+        if let Some(light) = &desc.light {
+            self.ecs_mut().insert(entity, DirectionalLight::from(light));
+        }
+
+        if let Some(parent) = parent {
+            hierarchy::attach(self.ecs_mut(), entity, parent).expect("New entities can not form cycles");
+        }
+
+        for child in &desc.children {
+            self.spawn_scene_entity(child, Some(entity));
+        }
+    }
+
+    fn entity_desc(&self, entity: Entity) -> EntityDesc {
+        let ecs = self.ecs();
+
+        let mesh = match (ecs.get::<MeshAsset>(entity), ecs.get::<MeshRef>(entity)) {
+            (Some(asset), _) => Some(asset.0.clone()),
+            (None, Some(MeshRef(MeshHandle::DEFAULT))) => Some(MeshAsset::DEFAULT.into()),
+            (None, Some(mesh)) => {
+                warn!("{:?} uses {:?} which was not loaded from a file, its mesh is not saved", entity, mesh.0);
+                None
+            }
+            (None, None) => None,
+        };
+
+        EntityDesc {
+            name: ecs.get::<Name>(entity).map(|name| name.0.clone()).unwrap_or_default(),
+            transform: ecs.get::<Transform>(entity).map(TransformDesc::from).unwrap_or_default(),
+            mesh,
+            light: ecs.get::<DirectionalLight>(entity).map(LightDesc::from),
+            children: hierarchy::children(ecs, entity)
+                .iter()
+                .filter(|&&child| !ecs.has::<NotSaved>(child))
+                .map(|&child| self.entity_desc(child))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::InnerSpace;
+
+    use super::*;
+    use crate::assets::gltf_loader::{GltfNode, GltfScene};
+    use crate::assets::mesh::Mesh;
+    use crate::utils::math::{Matrix4x4, Vector3};
+    use crate::world::game_object::GameObject;
+    use crate::world::hierarchy::GlobalTransform;
+
+    const SCENE: &str = r#"{
+        "version": 1,
+        "camera": { "location": [0.0, 40.0, -20.0], "fov": 60.0 },
+        "sun": { "elevation": 10.0, "azimuth": 270.0 },
+        "entities": [
+            {
+                "name": "car",
+                "transform": { "location": [5.0, 32.5, 0.0], "rotation": [0.0, 90.0, 0.0] },
+                "mesh": "./models/car.glb#0",
+                "children": [
+                    { "name": "wheel", "transform": { "location": [1.0, 0.0, 0.0], "scale": [0.5, 0.5, 0.5] }, "mesh": "default" }
+                ]
+            },
+            { "name": "moon", "light": { "elevation": 30.0, "azimuth": 90.0, "color": [0.8, 0.8, 1.0], "illuminance": 0.3 } }
+        ]
+    }"#;
+
+    fn entity_named(world: &World, name: &str) -> Entity {
+        world.ecs().query::<Name>().find(|(_, n)| n.0 == name).map(|(entity, _)| entity).unwrap()
+    }
+
+    #[test]
+    fn scenes_load_into_the_world() {
+        let mut world = World::from_scene(&SceneFile::from_json("test", SCENE).unwrap());
+        world.update(0.0);
+
+        let view = world.active_camera().view();
+        assert_eq!(view.fov, 60.0);
+        assert_eq!(view.near, OrbitCamera::default().view().near);
+        assert!((world.sun().elevation() - 10.0).abs() < 1e-3);
+
+        let car = entity_named(&world, "car");
+        let wheel = entity_named(&world, "wheel");
+        assert_eq!(hierarchy::children(world.ecs(), car), &[wheel]);
+        //Only the default mesh is known without the renderer
+        assert_eq!(world.ecs().get::<MeshRef>(car), None);
+        assert_eq!(world.ecs().get::<MeshRef>(wheel), Some(&MeshRef(MeshHandle::DEFAULT)));
+
+        //The wheel's transform is relative to the car, which faces +X
+        let position = world.ecs().get::<GlobalTransform>(wheel).unwrap().0.w.truncate();
+        assert!((position - Vector3::new(5.0, 32.5, -1.0)).magnitude() < 1e-4, "{:?}", position);
+    }
+
+    #[test]
+    fn scenes_round_trip() {
+        let scene = SceneFile::from_json("test", SCENE).unwrap();
+        let saved = World::from_scene(&scene).to_scene();
+        let reloaded = SceneFile::from_json("saved", &saved.to_json()).unwrap();
+
+        assert_eq!(saved, reloaded);
+        assert_eq!(reloaded.entities.len(), 2);
+        assert_eq!(reloaded.entities[0].mesh.as_deref(), Some("./models/car.glb#0"));
+        assert_eq!(reloaded.entities[0].children[0].transform.scale, [0.5; 3]);
+        for (a, b) in reloaded.entities[0].transform.rotation.iter().zip(scene.entities[0].transform.rotation) {
+            assert!((a - b).abs() < 1e-3, "{} != {}", a, b);
+        }
+
+        let moon = reloaded.entities[1].light.unwrap();
+        assert!((moon.elevation - 30.0).abs() < 1e-3 && (moon.azimuth - 90.0).abs() < 1e-3, "{:?}", moon);
+        assert_eq!((moon.color, moon.illuminance), ([0.8, 0.8, 1.0], 0.3));
+
+        assert_eq!(World::new().to_scene().entities, SceneFile::default().entities);
+    }
+
+    #[test]
+    fn configured_model_is_not_saved() {
+        let model = GltfScene {
+            meshes: vec![Mesh::cube()],
+            nodes: vec![GltfNode { name: "body".into(), meshes: vec![0], world_matrix: Matrix4x4::from_scale(1.0) }],
+        };
+
+        //The App imports the configured model again after loading the saved scene
+        let start = |scene: &SceneFile| {
+            let mut world = World::from_scene(scene);
+            world.add_model(&model, "./models/car.glb");
+            world
+        };
+
+        let world = start(&SceneFile::from_json("test", SCENE).unwrap());
+        let saved = SceneFile::from_json("saved", &world.to_scene().to_json()).unwrap();
+        let reloaded = start(&saved);
+
+        assert_eq!(reloaded.ecs().entities().count(), world.ecs().entities().count());
+        assert_eq!(reloaded.ecs().query::<Name>().filter(|(_, name)| name.0 == "body").count(), 1);
+        assert_eq!(saved.entities.len(), 2);
+    }
+
+    #[test]
+    fn unknown_fields_and_versions_are_rejected() {
+        let error = SceneFile::from_json("typo", r#"{ "version": 1, "entities": [{ "name": "a", "mseh": "default" }] }"#).unwrap_err();
+        assert!(matches!(error, SceneError::Parse { .. }));
+        assert!(error.to_string().contains("unknown field `mseh`"), "{}", error);
+
+        let error = SceneFile::from_json("future", r#"{ "version": 2, "lights": [] }"#).unwrap_err();
+        assert!(matches!(error, SceneError::UnsupportedVersion { found: 2, supported: 1, .. }));
+
+        assert!(matches!(SceneFile::from_json("unversioned", "{}"), Err(SceneError::Parse { .. })));
+    }
+}
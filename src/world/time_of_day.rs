@@ -0,0 +1,166 @@
+use crate::config::config::TimeOfDayConfig;
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+//2000-01-01 12:00 UTC
+const J2000: f64 = 2451545.0;
+
+//Degrees, elevation above the horizon and azimuth clockwise from north
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolarPosition {
+    pub elevation: f64,
+    pub azimuth: f64
+}
+
+//Simulated clock at a fixed place on Earth, advanced by World::update
+#[derive(Debug, Clone)]
+pub struct TimeOfDay {
+    //Degrees, north and east positive
+    latitude: f64,
+    longitude: f64,
+    //UTC
+    julian_day: f64,
+    //Simulated seconds per real second
+    time_scale: f64
+}
+
+impl TimeOfDay {
+    pub fn new(config: &TimeOfDayConfig) -> Self {
+        let utc_hour = config.hour - config.utc_offset;
+
+        Self {
+            latitude: config.latitude,
+            longitude: config.longitude,
+            julian_day: julian_day(config.year, config.month, config.day) + utc_hour / 24.0,
+            time_scale: config.time_scale
+        }
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        self.julian_day += delta_time as f64 * self.time_scale / SECONDS_PER_DAY;
+    }
+
+    pub fn solar_position(&self) -> SolarPosition {
+        solar_position(self.latitude, self.longitude, self.julian_day)
+    }
+}
+
+//Julian day at 0:00 UTC of a Gregorian calendar date (Meeus, Astronomical Algorithms, ch. 7)
+pub fn julian_day(year: i32, month: u32, day: u32) -> f64 {
+    let (year, month) = if month <= 2 { (year - 1, month + 12) } else { (year, month) };
+
+    let a = (year as f64 / 100.0).floor();
+    let b = 2.0 - a + (a / 4.0).floor();
+
+    (365.25 * (year as f64 + 4716.0)).floor() + (30.6001 * (month as f64 + 1.0)).floor() + day as f64 + b - 1524.5
+}
+
+//NOAA solar position algorithm (Meeus based), geometric position without atmospheric refraction.
+//Accurate to about 0.01 degrees for years 1800-2100
+pub fn solar_position(latitude: f64, longitude: f64, julian_day: f64) -> SolarPosition {
+    let t = (julian_day - J2000) / 36525.0;
+
+    //Sun's geometric mean longitude and anomaly, eccentricity of Earth's orbit
+    let mean_longitude = (280.46646 + t * (36000.76983 + t * 0.0003032)).rem_euclid(360.0);
+    let mean_anomaly = 357.52911 + t * (35999.05029 - t * 0.0001537);
+    let eccentricity = 0.016708634 - t * (0.000042037 + t * 0.0000001267);
+
+    let m = mean_anomaly.to_radians();
+    let center = m.sin() * (1.914602 - t * (0.004817 + t * 0.000014))
+        + (2.0 * m).sin() * (0.019993 - t * 0.000101)
+        + (3.0 * m).sin() * 0.000289;
+
+    //Apparent longitude, corrected for nutation and aberration
+    let omega = (125.04 - 1934.136 * t).to_radians();
+    let apparent_longitude = (mean_longitude + center - 0.00569 - 0.00478 * omega.sin()).to_radians();
+
+    let mean_obliquity = 23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.0) / 60.0;
+    let obliquity = (mean_obliquity + 0.00256 * omega.cos()).to_radians();
+
+    let declination = (obliquity.sin() * apparent_longitude.sin()).asin();
+
+    //Equation of time in minutes
+    let y = (obliquity / 2.0).tan().powi(2);
+    let l0 = mean_longitude.to_radians();
+    let equation_of_time = 4.0 * (y * (2.0 * l0).sin()
+        - 2.0 * eccentricity * m.sin()
+        + 4.0 * eccentricity * y * m.sin() * (2.0 * l0).cos()
+        - 0.5 * y * y * (4.0 * l0).sin()
+        - 1.25 * eccentricity * eccentricity * (2.0 * m).sin()).to_degrees();
+
+    //Julian days start at noon
+    let utc_minutes = (julian_day + 0.5).fract() * 1440.0;
+    let true_solar_time = (utc_minutes + equation_of_time + 4.0 * longitude).rem_euclid(1440.0);
+    let hour_angle = (true_solar_time / 4.0 - 180.0).to_radians();
+
+    let latitude = latitude.to_radians();
+    let cos_zenith = latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos();
+    let elevation = 90.0 - cos_zenith.clamp(-1.0, 1.0).acos().to_degrees();
+
+    let azimuth = hour_angle.sin().atan2(hour_angle.cos() * latitude.sin() - declination.tan() * latitude.cos());
+    let azimuth = (azimuth.to_degrees() + 180.0).rem_euclid(360.0);
+
+    SolarPosition { elevation, azimuth }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f64 = 0.05;
+
+    fn assert_position(actual: SolarPosition, elevation: f64, azimuth: f64) {
+        assert!((actual.elevation - elevation).abs() < TOLERANCE, "elevation {} != {}", actual.elevation, elevation);
+        assert!((actual.azimuth - azimuth).abs() < TOLERANCE, "azimuth {} != {}", actual.azimuth, azimuth);
+    }
+
+    #[test]
+    fn julian_day_matches_meeus() {
+        //Meeus, example 7.a and the J2000 epoch
+        assert!((julian_day(1957, 10, 4) + 0.81 - 2436116.31).abs() < 1e-6);
+        assert_eq!(julian_day(2000, 1, 1) + 0.5, J2000);
+    }
+
+    //Reda & Andreas, "Solar Position Algorithm for Solar Radiation Applications" (NREL 2008), table A4.1:
+    //Golden, Colorado, 2003-10-17 12:30:30 UTC-7, zenith 50.11162 (refracted, about 0.02 above geometric), azimuth 194.34024
+    #[test]
+    fn matches_nrel_spa_example() {
+        let config = TimeOfDayConfig {
+            latitude: 39.742476,
+            longitude: -105.1786,
+            year: 2003,
+            month: 10,
+            day: 17,
+            hour: 12.0 + 30.0 / 60.0 + 30.0 / 3600.0,
+            utc_offset: -7.0,
+            time_scale: 1.0
+        };
+
+        assert_position(TimeOfDay::new(&config).solar_position(), 90.0 - 50.11162 - 0.02, 194.34024);
+    }
+
+    //Greenwich at the 2024 June solstice, the sun culminates 61.96 degrees high due south around 12:02 UTC.
+    //Azimuth moves half a degree per minute at that time, so it is only checked loosely
+    #[test]
+    fn matches_greenwich_solstice_noon() {
+        let julian_day = julian_day(2024, 6, 21) + (12.0 + 2.0 / 60.0) / 24.0;
+        let position = solar_position(51.4769, 0.0, julian_day);
+
+        assert!((position.elevation - 61.96).abs() < TOLERANCE, "elevation {} != 61.96", position.elevation);
+        assert!((position.azimuth - 180.0).abs() < 1.0, "azimuth {} != 180", position.azimuth);
+    }
+
+    #[test]
+    fn update_advances_by_time_scale() {
+        let mut time_of_day = TimeOfDay::new(&TimeOfDayConfig {
+            time_scale: 3600.0,
+            ..Default::default()
+        });
+
+        let start = time_of_day.julian_day;
+        for _ in 0..10 {
+            time_of_day.update(0.1);
+        }
+
+        assert!((time_of_day.julian_day - start - 1.0 / 24.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use crate::config::config::{SimulationConfig, SimulationConfigError};
+
+//Splits frame times into simulation ticks of equal length, the remainder carries over to the next frame.
+//Kept in Duration so the tick count only depends on the sum of the frame times
+#[derive(Debug, Clone, Copy)]
+pub struct FixedTimestep {
+    tick: Duration,
+    //Ticks past this are dropped, so a slow frame doesn't make the next one slower still
+    max_ticks_per_frame: u32,
+    accumulator: Duration,
+}
+
+impl FixedTimestep {
+    pub fn tick(&self) -> Duration {
+        self.tick
+    }
+
+    //Seconds, the delta time of every tick
+    pub fn tick_delta(&self) -> f32 {
+        self.tick.as_secs_f32()
+    }
+
+    //Adds the frame time and returns the number of ticks to run
+    pub fn advance(&mut self, frame_time: Duration) -> u32 {
+        self.accumulator += frame_time;
+
+        let due = self.accumulator.as_nanos() / self.tick.as_nanos();
+        let ticks = due.min(self.max_ticks_per_frame as u128) as u32;
+
+        self.accumulator -= self.tick * ticks;
+        if due > ticks as u128 {
+            self.accumulator = Duration::from_nanos((self.accumulator.as_nanos() % self.tick.as_nanos()) as u64);
+        }
+
+        ticks
+    }
+
+    //How far the time is between the last tick and the next one, in [0, 1)
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator.as_secs_f64() / self.tick.as_secs_f64()) as f32
+    }
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::try_from(SimulationConfig::default()).expect("Default tick rate is valid")
+    }
+}
+
+impl TryFrom<SimulationConfig> for FixedTimestep {
+    type Error = SimulationConfigError;
+
+    fn try_from(config: SimulationConfig) -> Result<Self, Self::Error> {
+        Ok(Self {
+            tick: config.tick()?,
+            max_ticks_per_frame: config.max_ticks_per_frame.max(1),
+            accumulator: Duration::ZERO,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timestep(tick_rate: f64, max_ticks_per_frame: u32) -> Result<FixedTimestep, SimulationConfigError> {
+        FixedTimestep::try_from(SimulationConfig { tick_rate, max_ticks_per_frame })
+    }
+
+    #[test]
+    fn frame_times_carry_over() {
+        let mut timestep = timestep(50.0, 8).unwrap();
+        let ms = Duration::from_millis;
+
+        assert_eq!(timestep.advance(ms(15)), 0);
+        assert!((timestep.alpha() - 0.75).abs() < 1e-6);
+        assert_eq!(timestep.advance(ms(15)), 1);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-6);
+        assert_eq!(timestep.advance(ms(50)), 3);
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(timestep.tick_delta(), 0.02);
+    }
+
+    #[test]
+    fn slow_frames_drop_ticks() {
+        let mut timestep = timestep(100.0, 4).unwrap();
+
+        assert_eq!(timestep.advance(Duration::from_millis(1005)), 4);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-6);
+        assert_eq!(timestep.advance(Duration::from_millis(5)), 1);
+    }
+
+    #[test]
+    fn invalid_tick_rates_are_errors() {
+        assert_eq!(timestep(0.0, 8).unwrap_err(), SimulationConfigError::NotPositive(0.0));
+        assert_eq!(timestep(1e10, 8).unwrap_err(), SimulationConfigError::OutOfRange(1e10));
+    }
+}
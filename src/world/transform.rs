@@ -1,8 +1,8 @@
-use cgmath::{InnerSpace, One, Rotation3, Transform as cgTransform};
+use cgmath::{InnerSpace, One, Rotation3, Transform as cgTransform, VectorSpace};
 
-use crate::utils::math::{Deg, Euler, Matrix4x4, Quaternion, Vector3, Zero};
+use crate::utils::math::{Deg, Euler, Matrix3x3, Matrix4x4, Quaternion, Vector3, Zero};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Transform {
     location: Vector3,
     rotation: Quaternion,
@@ -48,15 +48,28 @@ impl Transform {
         self.scale
     }
 
+    //Scales, rotates and then translates, for column vectors as in the shaders
     pub fn matrix(&self) -> Matrix4x4 {
-        // self.matrix_t() * self.matrix_r() * self.matrix_s()
-        self.matrix_s() * self.matrix_r() * self.matrix_t()
+        self.matrix_t() * Matrix4x4::from(self.rotation) * self.matrix_s()
+    }
+
+    //Inverse of matrix(), expects a matrix without shear
+    pub fn from_matrix(m: Matrix4x4) -> Self {
+        let scale = Vector3::new(m.x.truncate().magnitude(), m.y.truncate().magnitude(), m.z.truncate().magnitude());
+        let rotation = Matrix3x3::from_cols(m.x.truncate() / scale.x, m.y.truncate() / scale.y, m.z.truncate() / scale.z);
+
+        Self {
+            location: m.w.truncate(),
+            rotation: Quaternion::from(rotation).normalize(),
+            scale
+        }
     }
 
     pub fn matrix_t(&self) -> Matrix4x4 {
         Matrix4x4::from_translation(self.location)
     }
 
+    //Inverse rotation, see Camera::view_matrix
     pub fn matrix_r(&self) -> Matrix4x4 {
         let rot = self.rotation;
 
@@ -92,6 +105,10 @@ impl Transform {
         self.location = Vector3::new(x, y, z)
     }
 
+    pub fn set_rotation(&mut self, rotation: Quaternion) {
+        self.rotation = rotation.normalize();
+    }
+
     pub fn set_rotation_euler_deg(&mut self, x: f32, y: f32, z: f32) {
         self.rotation = Quaternion::from(Euler {
             x: Deg(x),
@@ -127,6 +144,15 @@ impl Transform {
     pub fn inverse_transform_vector(&self, vector: Vector3) -> Vector3 {
         self.matrix().inverse_transform_vector(vector).unwrap()
     }
+
+    //Blends from `self` at alpha 0 to `other` at alpha 1, rotating along the shortest arc
+    pub fn interpolate(&self, other: &Transform, alpha: f32) -> Transform {
+        Transform {
+            location: self.location.lerp(other.location, alpha),
+            rotation: self.rotation.slerp(other.rotation, alpha),
+            scale: self.scale.lerp(other.scale, alpha)
+        }
+    }
 }
 
 pub trait OwnedTransform {
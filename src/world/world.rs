@@ -1,86 +1,661 @@
+use std::time::Duration;
+
 use cgmath::InnerSpace;
-use std::time::Instant;
 
 use crate::camera::camera::Camera;
 use crate::camera::orbit_camera::OrbitCamera;
-use crate::utils::math::{Vector3, Zero};
+use crate::graphics::rhi::MeshHandle;
+use crate::assets::gltf_loader::GltfScene;
+use crate::utils::math::Vector3;
+use crate::world::clock::{Clock, RealClock};
+use crate::world::commands::{Command, Commands};
+use crate::world::components::{MeshAsset, MeshRef, Name, NotSaved};
+use crate::world::directional_light::DirectionalLight;
+use crate::world::ecs::{Ecs, Persistent};
 use crate::world::entity::Entity;
 use crate::world::game_object::GameObject;
-use crate::world::transform::{OwnedTransform, Transform};
+use crate::world::hierarchy;
+use crate::world::hierarchy::HierarchyError;
+use crate::world::scene::SceneFile;
+use crate::world::time_of_day::TimeOfDay;
+use crate::world::timestep::FixedTimestep;
+use crate::world::transform::Transform;
+
+//On top of the Earth preset's planet, which has a radius of about 31.9 around the origin
+pub const MODEL_LOCATION: Vector3 = Vector3::new(0.0, 32.5, 0.0);
+
+//Runs every simulation tick with the tick's delta time, in the order the systems were added
+pub type System = Box<dyn FnMut(&mut World, f32) + Send + Sync>;
+
+//Updated every simulation tick after the systems, see World::spawn_behaviour
+pub type Behaviour = Box<dyn GameObject + Send + Sync>;
 
 pub struct World {
-    start_time: Instant,
-    last_frame_time: f32,
-    //ToDo: Make private and handle input
-    pub main_camera: OrbitCamera,
-    entities: Vec<Entity>,
+    clock: Box<dyn Clock>,
+    //Clock reading of the last advance_frame
+    last_frame_time: Duration,
+    timestep: FixedTimestep,
+    ecs: Ecs,
+    //Entities with the OrbitCamera and DirectionalLight the renderer uses
+    main_camera: Entity,
+    sun: Entity,
+    time_of_day: Option<TimeOfDay>,
+    systems: Vec<System>,
+    //In the order they were started, with the entity they are attached to
+    behaviours: Vec<(Entity, Behaviour)>,
+    commands: Commands,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl World {
+    //The built-in scene, see SceneFile::default
     pub fn new() -> Self {
-        let test_entity = Entity {
-            id: 1,
-            name: "test".into(),
-            transform: Transform::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0), Vector3::new(33.0, 33.0, 33.0))
+        Self::from_scene(&SceneFile::default())
+    }
+
+    //World with only the main camera at `camera_transform` and the sun
+    pub fn empty(camera: OrbitCamera, camera_transform: Transform, light: DirectionalLight) -> Self {
+        let mut ecs = Ecs::new();
+
+        let main_camera = ecs.spawn();
+        ecs.insert(main_camera, Name("main_camera".into()));
+        ecs.insert(main_camera, camera);
+        ecs.insert(main_camera, camera_transform);
+        ecs.insert(main_camera, Persistent);
+
+        let sun = ecs.spawn();
+        ecs.insert(sun, Name("sun".into()));
+        ecs.insert(sun, light);
+        ecs.insert(sun, Persistent);
+
+        let clock = RealClock::default();
+
+        let mut world = Self {
+            last_frame_time: clock.now(),
+            clock: Box::new(clock),
+            timestep: FixedTimestep::default(),
+            ecs,
+            main_camera,
+            sun,
+            time_of_day: None,
+            systems: Vec::new(),
+            behaviours: Vec::new(),
+            commands: Commands::default(),
         };
-  
-        let entities = vec![
-            test_entity
-        ];
 
-        let mut orbit_camera = OrbitCamera::default();
-        orbit_camera.camera_mut().transform_mut().set_location_xyz(0.0, 0.0, -105.0);
+        world.add_system(update_orbit_cameras);
+        world.add_system(update_time_of_day);
+        world
+    }
+
+    pub fn ecs(&self) -> &Ecs {
+        &self.ecs
+    }
+
+    pub fn ecs_mut(&mut self) -> &mut Ecs {
+        &mut self.ecs
+    }
+
+    pub fn add_system(&mut self, system: impl FnMut(&mut World, f32) + Send + Sync + 'static) {
+        self.systems.push(Box::new(system));
+    }
+
+    //Shares the World's command queue, for behaviours to keep
+    pub fn commands(&self) -> Commands {
+        self.commands.clone()
+    }
+
+    //Spawns an entity for the behaviour. It is started right away and updated from the next World::update on
+    pub fn spawn_behaviour(&mut self, behaviour: impl GameObject + Send + Sync + 'static) -> Entity {
+        let entity = self.ecs.spawn();
+        self.start_behaviour(entity, Box::new(behaviour));
+        entity
+    }
+
+    //Attaches the behaviour to an existing entity, it is destroyed along with it
+    pub fn add_behaviour(&mut self, entity: Entity, behaviour: impl GameObject + Send + Sync + 'static) {
+        self.start_behaviour(entity, Box::new(behaviour));
+    }
 
-        let start_time = Instant::now();
+    pub fn behaviour_count(&self) -> usize {
+        self.behaviours.len()
+    }
 
-        Self {
-            start_time,
-            last_frame_time: start_time.elapsed().as_secs_f32(),
-            main_camera: orbit_camera,
-            entities,
+    //Despawns the entity and its descendants and destroys their behaviours. Returns false when it was already
+    //despawned, or is the main camera or the sun which can't be. Use Commands::despawn from inside GameObject::update
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.ecs.despawn(entity) {
+            return false;
         }
+
+        self.destroy_dead_behaviours();
+        true
+    }
+
+    pub fn main_camera(&self) -> &OrbitCamera {
+        self.ecs.get(self.main_camera).expect("Main camera entity has no OrbitCamera")
+    }
+
+    pub fn main_camera_mut(&mut self) -> &mut OrbitCamera {
+        self.ecs.get_mut(self.main_camera).expect("Main camera entity has no OrbitCamera")
+    }
+
+    pub fn main_camera_entity(&self) -> Entity {
+        self.main_camera
+    }
+
+    //Main camera at the world pose of its entity, up to date after every tick. Move it with transform_mut
+    pub fn active_camera(&self) -> Camera {
+        Camera::new(self.main_camera().view(), Transform::from_matrix(hierarchy::global_matrix(&self.ecs, self.main_camera)))
+    }
+
+    pub fn sun(&self) -> &DirectionalLight {
+        self.ecs.get(self.sun).expect("Sun entity has no DirectionalLight")
+    }
+
+    pub fn sun_mut(&mut self) -> &mut DirectionalLight {
+        self.ecs.get_mut(self.sun).expect("Sun entity has no DirectionalLight")
+    }
+
+    pub fn sun_entity(&self) -> Entity {
+        self.sun
+    }
+
+    //The sun's direction turned by its entity's world rotation, e.g. when it is attached to another entity
+    pub fn sun_direction(&self) -> Vector3 {
+        let matrix = hierarchy::interpolated_matrix(&self.ecs, self.sun, self.interpolation_alpha());
+        (matrix * self.sun().direction().extend(0.0)).truncate().normalize()
     }
 
-    pub fn active_camera(&self) -> &Camera {
-        self.main_camera.camera()
+    pub fn time_of_day(&self) -> Option<&TimeOfDay> {
+        self.time_of_day.as_ref()
     }
 
-    pub fn active_camera_mut(&mut self) -> &mut Camera {
-        self.main_camera.camera_mut()
+    //Sun direction follows the simulated clock from the next update on
+    pub fn set_time_of_day(&mut self, time_of_day: Option<TimeOfDay>) {
+        self.time_of_day = time_of_day;
     }
 
-    pub fn start_time(&self) -> Instant {
-        self.start_time
+    //Frame times are measured with `clock` from now on, e.g. a ManualClock for deterministic runs
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.last_frame_time = clock.now();
+        self.clock = Box::new(clock);
     }
 
-    pub fn get_entities(&self) -> Vec<&Entity> {
-        self.entities.iter().collect()
+    //Drops the time accumulated towards the next tick
+    pub fn set_timestep(&mut self, timestep: FixedTimestep) {
+        self.timestep = timestep;
+    }
+
+    pub fn timestep(&self) -> &FixedTimestep {
+        &self.timestep
+    }
+
+    //How far the frame is between the last tick and the next one, for the renderer to blend transforms with
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.timestep.alpha()
+    }
+
+    //Main camera blended between the last two ticks
+    pub fn interpolated_camera(&self) -> Camera {
+        let matrix = hierarchy::interpolated_matrix(&self.ecs, self.main_camera, self.interpolation_alpha());
+        Camera::new(self.main_camera().view(), Transform::from_matrix(matrix))
+    }
+
+    //Draws everything where it is now instead of blending from the last tick, e.g. after teleporting the camera
+    pub fn reset_interpolation(&mut self) {
+        hierarchy::propagate_transforms(&mut self.ecs);
+        hierarchy::store_previous_transforms(&mut self.ecs);
+    }
+
+    //Advances the simulation by the clock time since the last frame
+    pub fn advance_frame(&mut self) {
+        let now = self.clock.now();
+        let frame_time = now.saturating_sub(self.last_frame_time);
+        self.last_frame_time = now;
+
+        self.advance(frame_time);
+    }
+
+    //Runs the ticks that fit into the frame time plus what was left over from previous frames
+    pub fn advance(&mut self, frame_time: Duration) {
+        for _ in 0..self.timestep.advance(frame_time) {
+            self.tick();
+        }
+
+        //Entities changed between ticks, e.g. spawned while loading, are drawn at their place right away
+        hierarchy::propagate_transforms(&mut self.ecs);
+    }
+
+    //One simulation step of timestep().tick_delta() seconds
+    pub fn tick(&mut self) {
+        let delta_time = self.timestep.tick_delta();
+
+        //Changes made between ticks are part of the pose the tick starts from
+        hierarchy::propagate_transforms(&mut self.ecs);
+        hierarchy::store_previous_transforms(&mut self.ecs);
+
+        //Taken out so systems can borrow the World mutably, systems added meanwhile run from the next tick
+        let mut systems = std::mem::take(&mut self.systems);
+        systems.iter_mut().for_each(|system| system(self, delta_time));
+        systems.append(&mut self.systems);
+        self.systems = systems;
+
+        self.destroy_dead_behaviours();
+        self.behaviours.iter_mut().for_each(|(_, behaviour)| behaviour.update(delta_time));
+        self.apply_commands();
+
+        hierarchy::propagate_transforms(&mut self.ecs);
+    }
+
+    pub fn add_entity(&mut self, name: String, transform: Transform, mesh: Option<MeshHandle>) -> Entity {
+        let entity = self.ecs.spawn();
+        self.ecs.insert(entity, Name(name));
+        self.ecs.insert(entity, transform);
+
+        if let Some(mesh) = mesh {
+            self.ecs.insert(entity, MeshRef(mesh));
+        }
+
+        entity
+    }
+
+    //World matrices follow at the end of the tick, like for any other mutable access to the Transform
+    pub fn transform_mut(&mut self, entity: Entity) -> Option<&mut Transform> {
+        self.ecs.get_mut::<Transform>(entity)
+    }
+
+    //Keeps the entity's world pose, None detaches it from its parent
+    pub fn set_parent(&mut self, child: Entity, parent: Option<Entity>) -> Result<(), HierarchyError> {
+        hierarchy::set_parent(&mut self.ecs, child, parent)
+    }
+
+    //One entity per non-empty mesh of every node, at the node's scene space transform moved by `origin`. The entities
+    //refer to the meshes of the file at `path` by MeshAsset, the renderer uploads them
+    pub fn add_gltf_scene(&mut self, scene: &GltfScene, path: &str, origin: Vector3) -> Vec<Entity> {
+        scene.nodes
+            .iter()
+            .flat_map(|node| node.meshes.iter().map(move |&mesh| (node, mesh)))
+            .filter(|&(_, mesh)| !scene.meshes[mesh].indices.is_empty())
+            .map(|(node, mesh)| {
+                let mut transform = node.transform();
+                transform.set_location(transform.location() + origin);
+
+                let entity = self.add_entity(node.name.clone(), transform, None);
+                self.ecs.insert(entity, MeshAsset::gltf_mesh(path, mesh));
+                entity
+            })
+            .collect()
+    }
+
+    //add_gltf_scene at MODEL_LOCATION for the model in the config, the entities are not saved to scene files
+    pub fn add_model(&mut self, scene: &GltfScene, path: &str) -> Vec<Entity> {
+        let entities = self.add_gltf_scene(scene, path, MODEL_LOCATION);
+        entities.iter().for_each(|&entity| {
+            self.ecs.insert(entity, NotSaved);
+        });
+        entities
+    }
+
+    fn start_behaviour(&mut self, entity: Entity, mut behaviour: Behaviour) {
+        assert!(self.ecs.is_alive(entity), "{:?} is not alive", entity);
+
+        behaviour.start();
+        self.behaviours.push((entity, behaviour));
+    }
+
+    //Also catches entities despawned through the Ecs directly
+    fn destroy_dead_behaviours(&mut self) {
+        let (alive, dead): (Vec<_>, Vec<_>) = std::mem::take(&mut self.behaviours)
+            .into_iter()
+            .partition(|(entity, _)| self.ecs.is_alive(*entity));
+
+        self.behaviours = alive;
+        dead.into_iter().for_each(|(_, mut behaviour)| behaviour.destroy());
+    }
+
+    //Applies the commands queued so far in order. Commands queued meanwhile, e.g. by the start of a spawned behaviour
+    //or by a command itself, wait for the next call so a command that queues itself can't stall the tick
+    fn apply_commands(&mut self) {
+        for command in self.commands.take() {
+            match command {
+                Command::Spawn(behaviour) => {
+                    let entity = self.ecs.spawn();
+                    self.start_behaviour(entity, behaviour);
+                }
+                Command::Despawn(entity) => {
+                    self.despawn(entity);
+                }
+                Command::Apply(command) => command(self),
+            }
+        }
+    }
+}
+
+//Only cameras with input touch their Transform, so the others are not marked as changed
+fn update_orbit_cameras(world: &mut World, delta_time: f32) {
+    let cameras = world.ecs.query::<OrbitCamera>()
+        .filter(|(_, camera)| camera.has_input())
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+
+    for entity in cameras {
+        let mut transform = world.ecs.get::<Transform>(entity).copied().unwrap_or_default();
+        world.ecs.get_mut::<OrbitCamera>(entity).unwrap().update(&mut transform, delta_time);
+        world.ecs.insert(entity, transform);
     }
 }
 
+fn update_time_of_day(world: &mut World, delta_time: f32) {
+    let Some(time_of_day) = world.time_of_day.as_mut() else {
+        return;
+    };
+
+    time_of_day.update(delta_time);
+
+    let position = time_of_day.solar_position();
+    world.sun_mut().set_angles(position.elevation as f32, position.azimuth as f32);
+}
+
 impl GameObject for World {
+    //Applies commands queued while setting up the World
     fn start(&mut self) {
-        todo!()
+        self.apply_commands();
+    }
+
+    //Advances by `delta_time` seconds instead of the clock time, see advance_frame
+    fn update(&mut self, delta_time: f32) {
+        self.advance(Duration::from_secs_f32(delta_time.max(0.0)));
+    }
+
+    //Destroys all behaviours, queued spawns were never started and are dropped
+    fn destroy(&mut self) {
+        self.commands.take();
+        std::mem::take(&mut self.behaviours).into_iter().for_each(|(_, mut behaviour)| behaviour.destroy());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::config::config::SimulationConfig;
+    use crate::world::clock::ManualClock;
+    use crate::assets::gltf_loader::GltfNode;
+    use crate::assets::mesh::Mesh;
+    use crate::utils::math::Matrix4x4;
+    use crate::world::transform::OwnedTransform;
+
+    #[test]
+    fn gltf_nodes_with_meshes_become_entities() {
+        let node = |name: &str, meshes: &[usize], x| GltfNode { name: name.into(), meshes: meshes.to_vec(), world_matrix: Matrix4x4::from_translation(Vector3::new(x, 0.0, 0.0)) };
+        let scene = GltfScene {
+            meshes: vec![Mesh::cube(), Mesh::cube(), Mesh::default()],
+            nodes: vec![node("root", &[], 0.0), node("a", &[1], 1.0), node("b", &[0, 1], 2.0), node("empty", &[2], 3.0)],
+        };
+
+        let mut world = World::new();
+        let entities = world.add_gltf_scene(&scene, "./models/car.glb", MODEL_LOCATION);
+        assert_eq!(entities.len(), 3);
+
+        let a = entities[0];
+        assert_eq!(world.ecs().get::<Name>(a), Some(&Name("a".into())));
+        assert_eq!(world.ecs().get::<MeshAsset>(a), Some(&MeshAsset::gltf_mesh("./models/car.glb", 1)));
+        assert!(!world.ecs().has::<MeshRef>(a));
+        assert_eq!(world.ecs().get::<Transform>(a).unwrap().location(), MODEL_LOCATION + Vector3::new(1.0, 0.0, 0.0));
     }
-    
-    fn update(&mut self, _: f32) {
-        let frame_time = self.start_time.elapsed().as_secs_f32();
-        let delta_time = frame_time - self.last_frame_time;
-        self.last_frame_time = frame_time;
 
-        self.main_camera.update(delta_time);
+    #[test]
+    fn systems_run_in_order_every_tick() {
+        struct Ticks(u32);
 
-        let entity = self.entities.get_mut(0).unwrap();
-        let mut location = entity.transform.location();
-        location = location + Vector3::new(1.0, 1.0, 1.0).normalize() * delta_time;
-        if location.magnitude() > 5.0 {
-            location = Vector3::zero();
+        let mut world = World::new();
+        let counter = world.ecs_mut().spawn();
+        world.ecs_mut().insert(counter, Ticks(0));
+
+        let order = Arc::new(AtomicU32::new(0));
+        let first = order.clone();
+        world.add_system(move |world, _| {
+            world.ecs_mut().query_mut::<Ticks>().for_each(|(_, ticks)| ticks.0 += 1);
+            first.store(first.load(Ordering::Relaxed) * 10 + 1, Ordering::Relaxed);
+        });
+        let second = order.clone();
+        world.add_system(move |_, _| second.store(second.load(Ordering::Relaxed) * 10 + 2, Ordering::Relaxed));
+
+        world.tick();
+        world.tick();
+
+        assert_eq!(world.ecs().get::<Ticks>(counter).unwrap().0, 2);
+        assert_eq!(order.load(Ordering::Relaxed), 1212);
+    }
+
+    //Records its calls, optionally spawning another Probe and despawning an entity from its first update
+    struct Probe {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+        commands: Option<Commands>,
+        spawn: Option<Box<Probe>>,
+        despawn: Option<Entity>,
+    }
+
+    impl Probe {
+        fn new(name: &'static str, log: &Arc<Mutex<Vec<String>>>) -> Self {
+            Self { name, log: log.clone(), commands: None, spawn: None, despawn: None }
         }
 
-        // entity.transform.set_location(location);
+        fn record(&self, event: &str) {
+            self.log.lock().unwrap().push(format!("{} {}", self.name, event));
+        }
     }
 
-    fn destroy(&mut self) {
-        todo!()
+    impl GameObject for Probe {
+        fn start(&mut self) {
+            self.record("start");
+        }
+
+        fn update(&mut self, _: f32) {
+            self.record("update");
+
+            if let Some(commands) = &self.commands {
+                if let Some(probe) = self.spawn.take() {
+                    commands.spawn(*probe);
+                }
+                if let Some(entity) = self.despawn.take() {
+                    commands.despawn(entity);
+                }
+            }
+        }
+
+        fn destroy(&mut self) {
+            self.record("destroy");
+        }
+    }
+
+    //Moves along +X at one unit per second
+    struct Mover;
+
+    fn move_system(world: &mut World, delta_time: f32) {
+        let movers = world.ecs().query::<Mover>().map(|(entity, _)| entity).collect::<Vec<_>>();
+        for entity in movers {
+            let transform = world.transform_mut(entity).unwrap();
+            transform.set_location(transform.location() + Vector3::new(delta_time, 0.0, 0.0));
+        }
+    }
+
+    fn mover_world(clock: &ManualClock) -> (World, Entity) {
+        let mut world = World::new();
+        world.set_clock(clock.clone());
+        world.set_timestep(FixedTimestep::try_from(SimulationConfig { tick_rate: 10.0, max_ticks_per_frame: 8 }).unwrap());
+        world.add_system(move_system);
+
+        let entity = world.add_entity("mover".into(), Transform::identity(), None);
+        world.ecs_mut().insert(entity, Mover);
+        (world, entity)
+    }
+
+    #[test]
+    fn ticks_are_independent_of_frame_rate() {
+        let slow_clock = ManualClock::default();
+        let fast_clock = ManualClock::default();
+        let (mut slow, slow_mover) = mover_world(&slow_clock);
+        let (mut fast, fast_mover) = mover_world(&fast_clock);
+
+        //One second at 4 and at 100 frames per second
+        for _ in 0..4 {
+            slow_clock.advance(Duration::from_millis(250));
+            slow.advance_frame();
+        }
+        for _ in 0..100 {
+            fast_clock.advance(Duration::from_millis(10));
+            fast.advance_frame();
+        }
+
+        let location = |world: &World, entity| world.ecs().get::<Transform>(entity).unwrap().location();
+        assert_eq!(location(&slow, slow_mover), location(&fast, fast_mover));
+        assert!((location(&slow, slow_mover).x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn transforms_are_interpolated_between_ticks() {
+        let clock = ManualClock::default();
+        let (mut world, mover) = mover_world(&clock);
+
+        clock.advance(Duration::from_millis(125));
+        world.advance_frame();
+        assert!((world.interpolation_alpha() - 0.25).abs() < 1e-5);
+
+        //Last tick moved from 0.0 to 0.1, a quarter of the way to the next tick is 0.025
+        let x = hierarchy::interpolated_matrix(world.ecs(), mover, world.interpolation_alpha()).w.x;
+        assert!((x - 0.025).abs() < 1e-5, "{}", x);
+
+        world.reset_interpolation();
+        assert!((hierarchy::interpolated_matrix(world.ecs(), mover, world.interpolation_alpha()).w.x - 0.1).abs() < 1e-5);
+
+        //Entities that didn't move are drawn where they are
+        let test = world.ecs().query::<Name>().find(|(_, name)| name.0 == "test").unwrap().0;
+        assert_eq!(hierarchy::interpolated_matrix(world.ecs(), test, 0.5).w.truncate(), MODEL_LOCATION);
+    }
+
+    #[test]
+    fn attached_camera_and_sun_follow_their_parent() {
+        let mut world = World::new();
+        let (main_camera, sun) = (world.main_camera_entity(), world.sun_entity());
+        let car = world.add_entity("car".into(), Transform::identity(), None);
+
+        world.set_parent(main_camera, Some(car)).unwrap();
+        world.set_parent(sun, Some(main_camera)).unwrap();
+        let (camera_location, sun_direction) = (world.active_camera().transform().location(), world.sun_direction());
+
+        //Turning the car half way around turns the camera and the light with it
+        let transform = world.transform_mut(car).unwrap();
+        transform.set_location(Vector3::new(10.0, 0.0, 0.0));
+        transform.set_rotation_euler_deg(0.0, 180.0, 0.0);
+        world.tick();
+        world.reset_interpolation();
+
+        let expected_location = Vector3::new(10.0 - camera_location.x, camera_location.y, -camera_location.z);
+        assert!((world.active_camera().transform().location() - expected_location).magnitude() < 1e-3);
+        assert!((world.interpolated_camera().transform().location() - expected_location).magnitude() < 1e-3);
+
+        let expected_direction = Vector3::new(-sun_direction.x, sun_direction.y, -sun_direction.z);
+        assert!((world.sun_direction() - expected_direction).magnitude() < 1e-3, "{:?}", world.sun_direction());
+    }
+
+    #[test]
+    fn main_camera_and_sun_can_not_be_despawned() {
+        let mut world = World::new();
+        let (main_camera, sun) = (world.main_camera_entity(), world.sun_entity());
+
+        assert!(!world.despawn(main_camera));
+        assert!(!world.ecs_mut().despawn(sun));
+        world.tick();
+
+        assert!(world.ecs().is_alive(main_camera) && world.ecs().is_alive(sun));
+    }
+
+    fn drain(log: &Arc<Mutex<Vec<String>>>) -> Vec<String> {
+        std::mem::take(&mut *log.lock().unwrap())
+    }
+
+    #[test]
+    fn behaviours_are_started_updated_and_destroyed() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut world = World::new();
+
+        let parent = world.spawn_behaviour(Probe::new("parent", &log));
+        let child = world.add_entity("child".into(), Transform::identity(), None);
+        world.add_behaviour(child, Probe::new("child", &log));
+        world.set_parent(child, Some(parent)).unwrap();
+        world.spawn_behaviour(Probe::new("other", &log));
+        assert_eq!(drain(&log), ["parent start", "child start", "other start"]);
+
+        world.tick();
+        assert_eq!(drain(&log), ["parent update", "child update", "other update"]);
+
+        //Descendants go with their parent
+        assert!(world.despawn(parent));
+        assert!(!world.despawn(parent));
+        assert_eq!(drain(&log), ["parent destroy", "child destroy"]);
+
+        world.destroy();
+        assert_eq!(drain(&log), ["other destroy"]);
+        assert_eq!(world.behaviour_count(), 0);
+    }
+
+    #[test]
+    fn commands_are_deferred_until_after_the_tick() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut world = World::new();
+
+        let victim = world.spawn_behaviour(Probe::new("victim", &log));
+        let mut spawner = Probe::new("spawner", &log);
+        spawner.commands = Some(world.commands());
+        spawner.spawn = Some(Box::new(Probe::new("spawned", &log)));
+        spawner.despawn = Some(victim);
+        world.spawn_behaviour(spawner);
+
+        let commands = world.commands();
+        commands.apply(move |world| {
+            world.add_entity("queued".into(), Transform::identity(), None);
+        });
+        world.start();
+        drain(&log);
+
+        //The victim is still updated, the spawned behaviour only from the next tick on
+        world.tick();
+        assert_eq!(drain(&log), ["victim update", "spawner update", "spawned start", "victim destroy"]);
+        assert!(!world.ecs().is_alive(victim));
+        assert!(world.ecs().query::<Name>().any(|(_, name)| name.0 == "queued"));
+
+        world.tick();
+        assert_eq!(drain(&log), ["spawner update", "spawned update"]);
+        assert!(commands.is_empty());
+    }
+
+    //Queues itself again every time it runs
+    fn requeue(count: Arc<AtomicU32>) -> impl FnOnce(&mut World) + Send + 'static {
+        move |world| {
+            count.fetch_add(1, Ordering::Relaxed);
+            world.commands().apply(requeue(count));
+        }
+    }
+
+    #[test]
+    fn commands_queued_while_applying_wait_for_the_next_tick() {
+        let count = Arc::new(AtomicU32::new(0));
+        let mut world = World::new();
+        world.commands().apply(requeue(count.clone()));
+
+        world.tick();
+        world.tick();
+        assert_eq!(count.load(Ordering::Relaxed), 2);
+        assert!(!world.commands().is_empty());
     }
 }